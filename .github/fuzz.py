@@ -0,0 +1,34 @@
+#!/usr/bin/env -S uv run
+# /// script
+# dependencies = ["wasmtime"]
+# ///
+
+import argparse
+import subprocess
+from pathlib import Path
+
+import wasmtime
+
+
+def run(cmd: list[str]) -> None:
+    subprocess.run(cmd, check=True)
+
+
+def seed(corpus: Path) -> None:
+    corpus.mkdir(parents=True, exist_ok=True)
+    for wat in sorted(Path("crates/floretta/src/wat").glob("*.wat")):
+        wasm = wasmtime.wat2wasm(wat.read_text())
+        (corpus / f"{wat.stem}.wasm").write_bytes(wasm)
+
+
+def main() -> None:
+    parser = argparse.ArgumentParser()
+    parser.add_argument("args", nargs="*", help="passed through to libFuzzer")
+    args = parser.parse_args()
+    target = "transform"
+    seed(Path("fuzz/corpus") / target)
+    run(["cargo", "+nightly", "fuzz", "run", target, "--", *args.args])
+
+
+if __name__ == "__main__":
+    main()
@@ -3,7 +3,6 @@
 import argparse
 import gzip
 import os
-import shutil
 import subprocess
 import sys
 from pathlib import Path
@@ -29,6 +28,18 @@ def compile() -> str:
     return "target/wasm32-unknown-unknown/tiny/floretta_wasm.wasm"
 
 
+def bindgen(wasm: str, out_dir: str) -> None:
+    run(
+        [
+            "wasm-bindgen",
+            "--target=web",
+            f"--out-dir={out_dir}",
+            "--out-name=floretta",
+            wasm,
+        ]
+    )
+
+
 def print_sizes(files: dict[str, int], *, out) -> None:
     m = max(len(k) for k in files.keys())
     n = max(len(str(v)) for v in files.values())
@@ -44,11 +55,14 @@ def main() -> None:
         "-o", "--output", type=argparse.FileType("a"), default=sys.stdout
     )
     args = parser.parse_args()
-    name = "floretta.wasm"
-    shutil.copy(compile(), name)
-    wasm = Path(name).read_bytes()
-    gz = gzip.compress(wasm)
-    print_sizes({name: len(wasm), f"{name}.gz": len(gz)}, out=args.output)
+    out_dir = Path("pkg")
+    bindgen(compile(), str(out_dir))
+    sizes = {}
+    for name in ["floretta_bg.wasm", "floretta.js"]:
+        contents = (out_dir / name).read_bytes()
+        sizes[name] = len(contents)
+        sizes[f"{name}.gz"] = len(gzip.compress(contents))
+    print_sizes(sizes, out=args.output)
 
 
 if __name__ == "__main__":
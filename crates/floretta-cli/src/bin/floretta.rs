@@ -9,7 +9,9 @@ use anyhow::bail;
 use clap::Parser;
 use floretta::Autodiff;
 use itertools::Itertools;
-use termcolor::{ColorChoice, NoColor, StandardStream, WriteColor};
+use termcolor::{Color, ColorChoice, ColorSpec, NoColor, StandardStream, WriteColor};
+#[cfg(feature = "verify")]
+use wasmtime::{Engine, Instance, Module, Store, Val, ValType};
 
 /// Apply automatic differentiation to a WebAssembly module.
 #[derive(Debug, Parser)]
@@ -18,6 +20,14 @@ struct Cli {
     /// Input file path, or `-` to read from stdin.
     input: PathBuf,
 
+    /// Parse the input as the WebAssembly text format, regardless of its file extension.
+    #[clap(long, conflicts_with = "input_wasm")]
+    input_wat: bool,
+
+    /// Parse the input as the WebAssembly binary format, regardless of its file extension.
+    #[clap(long)]
+    input_wasm: bool,
+
     /// Forward mode.
     #[clap(short, long)]
     forward: bool,
@@ -34,6 +44,10 @@ struct Cli {
     #[clap(long)]
     no_names: bool,
 
+    /// Suffix for the names of backward passes in the name section and in `--export-all`.
+    #[clap(long, value_name = "SUFFIX", default_value = "_bwd")]
+    backward_suffix: String,
+
     /// In reverse mode, import the backward pass of a function that is already imported.
     #[clap(short, long, value_names=["MODULE", "NAME", "MODULE", "NAME"])]
     import: Vec<String>,
@@ -42,6 +56,57 @@ struct Cli {
     #[clap(short, long, value_names=["NAME", "NAME"])]
     export: Vec<String>,
 
+    /// In reverse mode, also export the backward pass of every export not covered by `--export`,
+    /// under its name with the backward suffix appended.
+    #[clap(long)]
+    export_all: bool,
+
+    /// In reverse mode, start the tape memory with this many 64 KiB pages.
+    ///
+    /// The tape grows on demand, so this only avoids calls to `memory.grow` during the forward
+    /// pass. Each page holds 8,192 `f64` values; most nonlinear floating-point instructions save
+    /// one or two of those per execution, so a model doing a million multiplications per forward
+    /// pass needs up to about 250 pages.
+    #[clap(long, value_name = "N")]
+    tape_pages: Option<u32>,
+
+    /// In reverse mode, also export a function that empties the tape.
+    #[clap(
+        long,
+        value_name = "NAME",
+        num_args = 0..=1,
+        default_missing_value = "_tape_reset"
+    )]
+    emit_tape_reset: Option<String>,
+
+    /// In reverse mode, run the first `--export` pair in Wasmtime on these comma-separated inputs.
+    ///
+    /// This checks that the forward pass gives the same result as the original function, then
+    /// calls the backward pass with a cotangent of 1 and prints the gradient to stderr. Only
+    /// available if built with the `verify` feature.
+    #[cfg(feature = "verify")]
+    #[clap(long, value_name = "INPUTS", requires = "reverse")]
+    verify: Option<String>,
+
+    /// In reverse mode, print how many functions got transformed, how much helper code got
+    /// injected, and how many bytes of tape each function uses, to stderr.
+    ///
+    /// The tape usage of a function is an upper bound for one call to its forward pass, including
+    /// whatever it calls; this helps to pick a value for `--tape-pages`. Functions with loops,
+    /// indirect calls, or recursion have no such bound.
+    #[clap(long, requires = "reverse")]
+    stats: bool,
+
+    /// In reverse mode, have the backward passes from `--export` return only the adjoints of these
+    /// comma-separated parameter indices, in that order.
+    #[clap(
+        long,
+        value_name = "INDICES",
+        value_delimiter = ',',
+        requires = "reverse"
+    )]
+    partial: Vec<u32>,
+
     /// Output file path; if not provided, will write to stdout.
     #[clap(short, long)]
     output: Option<PathBuf>,
@@ -53,35 +118,69 @@ struct Cli {
 
 fn main() -> anyhow::Result<()> {
     let args = Cli::parse();
+    let extension = args.input.extension().and_then(|ext| ext.to_str());
+    let format = if args.input_wat {
+        Some(Format::Wat)
+    } else if args.input_wasm {
+        Some(Format::Wasm)
+    } else {
+        match extension {
+            Some("wat") => Some(Format::Wat),
+            Some("wasm") => Some(Format::Wasm),
+            _ => None,
+        }
+    };
     let raw = if args.input.to_str() == Some("-") {
         let mut stdin = Vec::new();
         io::stdin().read_to_end(&mut stdin)?;
         stdin
     } else {
-        fs::read(args.input)?
+        fs::read(&args.input)?
     };
-    let before = match wat::parse_bytes(&raw)? {
-        Cow::Borrowed(bytes) => {
-            assert_eq!((bytes.as_ptr(), bytes.len()), (raw.as_ptr(), raw.len()));
+    let before = match format {
+        Some(Format::Wat) => wat::parse_str(std::str::from_utf8(&raw)?)?,
+        Some(Format::Wasm) => {
+            if !raw.starts_with(b"\0asm") {
+                bail!("input is not a WebAssembly binary; did you mean to pass `--input-wat`?");
+            }
             raw
         }
-        Cow::Owned(bytes) => bytes,
-    };
-    let mut ad = if args.no_validate {
-        Autodiff::no_validate()
-    } else {
-        Autodiff::new()
+        // Binary modules start with a magic number, so anything else must be text.
+        None => match wat::parse_bytes(&raw)? {
+            Cow::Borrowed(bytes) => {
+                assert_eq!((bytes.as_ptr(), bytes.len()), (raw.as_ptr(), raw.len()));
+                raw
+            }
+            Cow::Owned(bytes) => bytes,
+        },
     };
-    if !args.no_names {
-        ad.names();
-    }
+    let mut ad = Autodiff::with_validation(!args.no_validate).with_names(!args.no_names);
+    ad.backward_suffix(args.backward_suffix);
     for quadruple in args.import.into_iter().chunks(4).into_iter() {
         let (fwd_module, fwd_name, bwd_module, bwd_name) = quadruple.collect_tuple().unwrap();
         ad.import((fwd_module, fwd_name), (bwd_module, bwd_name));
     }
-    for pair in args.export.into_iter().chunks(2).into_iter() {
-        let (forward, backward) = pair.collect_tuple().unwrap();
+    let exports: Vec<(String, String)> = args
+        .export
+        .into_iter()
+        .chunks(2)
+        .into_iter()
+        .map(|pair| pair.collect_tuple().unwrap())
+        .collect();
+    for (forward, backward) in &exports {
         ad.export(forward, backward);
+        if !args.partial.is_empty() {
+            ad.differentiate_params(forward, args.partial.iter().copied());
+        }
+    }
+    if args.export_all {
+        ad.export_all();
+    }
+    if let Some(pages) = args.tape_pages {
+        ad.tape_initial_pages(pages);
+    }
+    if let Some(name) = args.emit_tape_reset {
+        ad.export_tape_reset(name);
     }
     let after = match (args.forward, args.reverse) {
         (false, false) => bail!("must select either `--forward` mode or `--reverse` mode"),
@@ -89,6 +188,26 @@ fn main() -> anyhow::Result<()> {
         (true, false) => ad.forward(&before)?,
         (false, true) => ad.reverse(&before)?,
     };
+    if args.stats {
+        let stats = ad.stats(&before)?;
+        eprintln!("{} functions transformed", stats.functions);
+        eprintln!("{} bytes of helper code injected", stats.helper_bytes);
+        for (index, bytes) in (stats.imported_functions..).zip(stats.tape_bytes) {
+            match bytes {
+                Some(bytes) => {
+                    eprintln!("function {index}: at most {bytes} bytes of tape per call")
+                }
+                None => eprintln!("function {index}: no static bound on tape usage"),
+            }
+        }
+    }
+    #[cfg(feature = "verify")]
+    if let Some(inputs) = args.verify {
+        let Some((forward, backward)) = exports.first() else {
+            bail!("`--verify` needs an `--export` pair to know which function to call");
+        };
+        verify(&before, &after, forward, backward, &inputs)?;
+    }
     if args.wat {
         match args.output {
             Some(path) => {
@@ -119,7 +238,149 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "verify")]
+fn verify(
+    before: &[u8],
+    after: &[u8],
+    forward: &str,
+    backward: &str,
+    inputs: &str,
+) -> anyhow::Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let original = Instance::new(&mut store, &Module::new(&engine, before)?, &[])?;
+    let transformed = Instance::new(&mut store, &Module::new(&engine, after)?, &[])?;
+    let Some(function) = original.get_func(&mut store, forward) else {
+        bail!("input module has no exported function named {forward:?}");
+    };
+    let ty = function.ty(&store);
+    let values: Vec<&str> = inputs.split(',').map(str::trim).collect();
+    if values.len() != ty.params().len() {
+        bail!(
+            "{forward:?} takes {} parameters but `--verify` got {}",
+            ty.params().len(),
+            values.len(),
+        );
+    }
+    let params = ty
+        .params()
+        .zip(values)
+        .map(|(ty, value)| float(&ty, value.parse()?))
+        .collect::<anyhow::Result<Vec<Val>>>()?;
+    let mut expected = vec![Val::I32(0); ty.results().len()];
+    function.call(&mut store, &params, &mut expected)?;
+    let mut actual = expected.clone();
+    let Some(fwd) = transformed.get_func(&mut store, forward) else {
+        bail!("output module has no exported function named {forward:?}");
+    };
+    fwd.call(&mut store, &params, &mut actual)?;
+    if !expected
+        .iter()
+        .zip(&actual)
+        .all(|(a, b)| bits(a) == bits(b))
+    {
+        bail!("forward pass returned {actual:?} but the original function returned {expected:?}");
+    }
+    let cotangents = ty
+        .results()
+        .map(|ty| float(&ty, 1.))
+        .collect::<anyhow::Result<Vec<Val>>>()?;
+    let Some(bwd) = transformed.get_func(&mut store, backward) else {
+        bail!("output module has no exported function named {backward:?}");
+    };
+    let mut gradient = vec![Val::I32(0); bwd.ty(&store).results().len()];
+    bwd.call(&mut store, &cotangents, &mut gradient)?;
+    eprintln!("{forward}: {}", show(&actual));
+    eprintln!("{backward}: {}", show(&gradient));
+    Ok(())
+}
+
+#[cfg(feature = "verify")]
+fn float(ty: &ValType, x: f64) -> anyhow::Result<Val> {
+    match ty {
+        ValType::F32 => Ok(Val::F32((x as f32).to_bits())),
+        ValType::F64 => Ok(Val::F64(x.to_bits())),
+        _ => bail!("`--verify` only supports `f32` and `f64` parameters and results"),
+    }
+}
+
+#[cfg(feature = "verify")]
+fn bits(val: &Val) -> Option<u64> {
+    match val {
+        Val::F32(x) => Some((*x).into()),
+        Val::F64(x) => Some(*x),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "verify")]
+fn show(vals: &[Val]) -> String {
+    vals.iter()
+        .map(|val| match val {
+            Val::F32(x) => f32::from_bits(*x).to_string(),
+            Val::F64(x) => f64::from_bits(*x).to_string(),
+            _ => unreachable!(),
+        })
+        .join(", ")
+}
+
+enum Format {
+    Wat,
+    Wasm,
+}
+
 fn print_wat(wasm: &[u8], writer: impl WriteColor) -> anyhow::Result<()> {
-    wasmprinter::Config::new().print(wasm, &mut wasmprinter::PrintTermcolor(writer))?;
+    wasmprinter::Config::new()
+        .print_skeleton(false)
+        .print(wasm, &mut Highlight(writer))?;
     Ok(())
 }
+
+/// Our own color scheme for the WebAssembly text format, instead of the one that comes with
+/// [`wasmprinter::PrintTermcolor`].
+struct Highlight<T>(T);
+
+impl<T: WriteColor> Highlight<T> {
+    fn fg(&mut self, color: Color) -> io::Result<()> {
+        self.0.set_color(ColorSpec::new().set_fg(Some(color)))
+    }
+}
+
+impl<T: WriteColor> wasmprinter::Print for Highlight<T> {
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        self.0.write_all(s.as_bytes())
+    }
+
+    fn start_keyword(&mut self) -> io::Result<()> {
+        self.fg(Color::Blue)
+    }
+
+    fn start_name(&mut self) -> io::Result<()> {
+        self.fg(Color::Yellow)
+    }
+
+    fn start_literal(&mut self) -> io::Result<()> {
+        self.fg(Color::Green)
+    }
+
+    fn start_type(&mut self) -> io::Result<()> {
+        self.fg(Color::Cyan)
+    }
+
+    fn start_comment(&mut self) -> io::Result<()> {
+        // Bright black is what most terminals show as dark gray.
+        self.0.set_color(
+            ColorSpec::new()
+                .set_fg(Some(Color::Black))
+                .set_intense(true),
+        )
+    }
+
+    fn reset_color(&mut self) -> io::Result<()> {
+        self.0.reset()
+    }
+
+    fn supports_async_color(&self) -> bool {
+        self.0.supports_color() && !self.0.is_synchronous()
+    }
+}
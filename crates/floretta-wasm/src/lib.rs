@@ -1,11 +1,128 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use floretta::Autodiff;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error("invalid JSON configuration: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("duplicate export configuration for {name:?}")]
+    DuplicateExport { name: String },
+
+    #[error("duplicate import configuration for {module:?} {name:?}")]
+    DuplicateImport { module: String, name: String },
+
+    #[error(transparent)]
+    Autodiff(#[from] floretta::Error),
+}
+
+/// Configuration passed from JavaScript as a JSON object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Config {
+    /// An object with `exports` and `imports` arrays, both optional.
+    Full(FullConfig),
+
+    /// An object mapping each primal export name to its derivative export name.
+    Exports(BTreeMap<String, String>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FullConfig {
+    #[serde(default)]
+    exports: Vec<Export>,
+
+    #[serde(default)]
+    imports: Vec<Import>,
+}
+
+/// An exported function whose derivative should also be exported.
+#[derive(Deserialize)]
+struct Export {
+    primal: String,
+    derivative: String,
+}
+
+/// An imported function along with where to import its backward pass from.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Import {
+    forward_module: String,
+    forward_name: String,
+    backward_module: String,
+    backward_name: String,
+}
+
+/// Parse a JSON configuration in either of the forms accepted by [`Config`].
+fn config(config_json: &str) -> Result<Autodiff, Error> {
+    let config = match serde_json::from_str(config_json)? {
+        Config::Full(config) => config,
+        Config::Exports(exports) => FullConfig {
+            exports: exports
+                .into_iter()
+                .map(|(primal, derivative)| Export { primal, derivative })
+                .collect(),
+            imports: Vec::new(),
+        },
+    };
+    let mut ad = Autodiff::no_validate();
+    // Check for duplicates up front, because `Autodiff::export` and `Autodiff::import` panic on
+    // them.
+    let mut seen = BTreeSet::new();
+    for export in &config.exports {
+        if !seen.insert(&export.primal) {
+            return Err(Error::DuplicateExport {
+                name: export.primal.clone(),
+            });
+        }
+    }
+    for Export { primal, derivative } in config.exports {
+        ad.export(primal, derivative);
+    }
+    let mut seen = BTreeSet::new();
+    for import in &config.imports {
+        if !seen.insert((&import.forward_module, &import.forward_name)) {
+            return Err(Error::DuplicateImport {
+                module: import.forward_module.clone(),
+                name: import.forward_name.clone(),
+            });
+        }
+    }
+    for import in config.imports {
+        ad.import(
+            (import.forward_module, import.forward_name),
+            (import.backward_module, import.backward_name),
+        );
+    }
+    Ok(ad)
+}
+
+/// Transform a Wasm module in forward mode.
+#[wasm_bindgen]
+pub fn forward(wasm: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(Autodiff::no_validate().forward(wasm)?)
+}
+
+/// Transform a Wasm module in reverse mode.
+#[wasm_bindgen]
+pub fn reverse(wasm: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(Autodiff::no_validate().reverse(wasm)?)
+}
 
-#[no_mangle]
-fn forward(wasm: &[u8]) -> Result<Vec<u8>, floretta::Error> {
-    Autodiff::no_validate().forward(wasm)
+/// Transform a Wasm module in forward mode, given a JSON configuration: either an object mapping
+/// export names, or an object with `exports` and `imports` arrays.
+#[wasm_bindgen(js_name = forwardWithExports)]
+pub fn forward_with_exports(wasm: &[u8], config_json: &str) -> Result<Vec<u8>, JsError> {
+    Ok(config(config_json)?.forward(wasm)?)
 }
 
-#[no_mangle]
-fn reverse(wasm: &[u8]) -> Result<Vec<u8>, floretta::Error> {
-    Autodiff::no_validate().reverse(wasm)
+/// Transform a Wasm module in reverse mode, given a JSON configuration: either an object mapping
+/// export names, or an object with `exports` and `imports` arrays.
+#[wasm_bindgen(js_name = reverseWithExports)]
+pub fn reverse_with_exports(wasm: &[u8], config_json: &str) -> Result<Vec<u8>, JsError> {
+    Ok(config(config_json)?.reverse(wasm)?)
 }
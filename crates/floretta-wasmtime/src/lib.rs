@@ -0,0 +1,346 @@
+//! [Wasmtime][] integration for [Floretta][].
+//!
+//! ```rust
+//! use floretta::Autodiff;
+//! use floretta_wasmtime::AutodiffInstance;
+//!
+//! let input = wat::parse_str(r#"
+//! (module
+//!   (func (export "square") (param f64) (result f64)
+//!     (f64.mul (local.get 0) (local.get 0))))
+//! "#).unwrap();
+//!
+//! let mut ad = Autodiff::new();
+//! ad.export("square", "backprop");
+//! let mut instance = AutodiffInstance::new(&ad, &input).unwrap();
+//!
+//! assert_eq!(instance.call_forward::<f64, f64>("square", 3.).unwrap(), 9.);
+//! assert_eq!(instance.call_backward::<f64, f64>("backprop", 1.).unwrap(), 6.);
+//! ```
+//!
+//! [floretta]: https://github.com/samestep/floretta
+//! [wasmtime]: https://crates.io/crates/wasmtime
+
+use floretta::{Autodiff, ReverseTyped};
+use wasmtime::{
+    AsContextMut, Engine, Instance, Linker, Module, Store, TypedFunc, WasmParams, WasmResults,
+};
+
+/// A Wasmtime instance of a Wasm module that has been transformed in reverse mode.
+pub struct AutodiffInstance<T = ()> {
+    store: Store<T>,
+    instance: Instance,
+}
+
+impl AutodiffInstance {
+    /// Transform `wasm` in reverse mode and instantiate it with a default engine and no imports.
+    pub fn new(config: &Autodiff, wasm: &[u8]) -> wasmtime::Result<Self> {
+        let engine = Engine::default();
+        Self::with_linker(&Linker::new(&engine), (), config, wasm)
+    }
+}
+
+impl<T> AutodiffInstance<T> {
+    /// Transform `wasm` in reverse mode and instantiate it using `linker` to resolve imports.
+    pub fn with_linker(
+        linker: &Linker<T>,
+        data: T,
+        config: &Autodiff,
+        wasm: &[u8],
+    ) -> wasmtime::Result<Self> {
+        let output = config.reverse(wasm)?;
+        let engine = linker.engine();
+        let mut store = Store::new(engine, data);
+        let module = Module::new(engine, &output)?;
+        let instance = linker.instantiate(&mut store, &module)?;
+        Ok(Self { store, instance })
+    }
+
+    /// Call the forward pass of an exported function.
+    pub fn call_forward<P: WasmParams, R: WasmResults>(
+        &mut self,
+        name: &str,
+        args: P,
+    ) -> wasmtime::Result<R> {
+        let func = self
+            .instance
+            .get_typed_func::<P, R>(&mut self.store, name)?;
+        func.call(&mut self.store, args)
+    }
+
+    /// Call an exported backward pass, taking cotangents for the results of the forward pass and
+    /// returning gradients for its parameters.
+    pub fn call_backward<DR: WasmParams, DP: WasmResults>(
+        &mut self,
+        name: &str,
+        args: DR,
+    ) -> wasmtime::Result<DP> {
+        let func = self
+            .instance
+            .get_typed_func::<DR, DP>(&mut self.store, name)?;
+        func.call(&mut self.store, args)
+    }
+
+    /// Look up an exported function and its exported backward pass as a [`WasmtimeAdDiff`].
+    pub fn typed<P, R>(
+        &mut self,
+        name: &str,
+        name_bwd: &str,
+    ) -> wasmtime::Result<WasmtimeAdDiff<P, R>>
+    where
+        P: WasmParams + WasmResults,
+        R: WasmParams + WasmResults,
+    {
+        WasmtimeAdDiff::new(&mut self.store, self.instance, name, name_bwd)
+    }
+
+    /// The underlying Wasmtime instance.
+    pub fn instance(&self) -> Instance {
+        self.instance
+    }
+
+    /// The store that owns the underlying Wasmtime instance.
+    pub fn store(&self) -> &Store<T> {
+        &self.store
+    }
+
+    /// Mutable access to the store that owns the underlying Wasmtime instance.
+    pub fn store_mut(&mut self) -> &mut Store<T> {
+        &mut self.store
+    }
+}
+
+/// Typed handles to an exported function and its backward pass, for functions whose parameters
+/// and results are all floating-point, so that the cotangents have the same types as the results
+/// and the gradients have the same types as the parameters.
+///
+/// The easiest way to get one is from [`Autodiff::reverse_typed`], via
+/// [`WasmtimeAdDiff::instantiate`]:
+///
+/// ```rust
+/// use floretta::Autodiff;
+/// use floretta_wasmtime::WasmtimeAdDiff;
+/// use wasmtime::{Engine, Linker, Store};
+///
+/// let input = wat::parse_str(r#"
+/// (module
+///   (func (export "mul") (param f64 f64) (result f64)
+///     (f64.mul (local.get 0) (local.get 1))))
+/// "#).unwrap();
+///
+/// let mut ad = Autodiff::new();
+/// ad.export_tape_reset("reset");
+/// let typed = ad
+///     .reverse_typed::<(f64, f64), f64, (f64, f64), f64>(&input, "mul", "backprop")
+///     .unwrap();
+///
+/// let engine = Engine::default();
+/// let mut store = Store::new(&engine, ());
+/// let mul = WasmtimeAdDiff::instantiate(&mut store, &Linker::new(&engine), &typed).unwrap();
+///
+/// assert_eq!(mul.call(&mut store, (2., 3.)).unwrap(), 6.);
+/// assert_eq!(mul.backward(&mut store, 1.).unwrap(), (3., 2.));
+/// ```
+pub struct WasmtimeAdDiff<P, R> {
+    forward: TypedFunc<P, R>,
+    backward: TypedFunc<R, P>,
+    tape_reset: Option<TypedFunc<(), ()>>,
+}
+
+impl<P, R> WasmtimeAdDiff<P, R>
+where
+    P: WasmParams + WasmResults,
+    R: WasmParams + WasmResults,
+{
+    /// Instantiate a module transformed via [`Autodiff::reverse_typed`], using `linker` to resolve
+    /// imports.
+    ///
+    /// If a tape reset function was configured via [`Autodiff::export_tape_reset`], this also uses
+    /// it as if via [`WasmtimeAdDiff::with_tape_reset`].
+    pub fn instantiate<T>(
+        mut store: impl AsContextMut<Data = T>,
+        linker: &Linker<T>,
+        typed: &ReverseTyped<P, R, P, R>,
+    ) -> wasmtime::Result<Self> {
+        let module = Module::new(linker.engine(), typed.wasm())?;
+        let instance = linker.instantiate(&mut store, &module)?;
+        let this = Self::new(&mut store, instance, typed.name(), typed.name_bwd())?;
+        match typed.tape_reset() {
+            Some(name) => this.with_tape_reset(&mut store, instance, name),
+            None => Ok(this),
+        }
+    }
+
+    /// Look up the forward pass exported as `name` and the backward pass exported as `name_bwd`.
+    ///
+    /// Returns an error if either function doesn't have the expected type.
+    pub fn new(
+        mut store: impl AsContextMut,
+        instance: Instance,
+        name: &str,
+        name_bwd: &str,
+    ) -> wasmtime::Result<Self> {
+        Ok(Self {
+            forward: instance.get_typed_func::<P, R>(&mut store, name)?,
+            backward: instance.get_typed_func::<R, P>(&mut store, name_bwd)?,
+            tape_reset: None,
+        })
+    }
+
+    /// Empty the tape before every call to the forward pass, using the function exported under
+    /// the `name` given to [`Autodiff::export_tape_reset`].
+    ///
+    /// This way, calling the forward pass several times without calling the backward pass in
+    /// between doesn't keep growing the tape; but then the backward pass can only be called for
+    /// the most recent call to the forward pass, and only if no other forward passes sharing the
+    /// same tape got called in the meantime.
+    pub fn with_tape_reset(
+        mut self,
+        mut store: impl AsContextMut,
+        instance: Instance,
+        name: &str,
+    ) -> wasmtime::Result<Self> {
+        self.tape_reset = Some(instance.get_typed_func::<(), ()>(&mut store, name)?);
+        Ok(self)
+    }
+
+    /// Call the forward pass.
+    pub fn call(&self, mut store: impl AsContextMut, input: P) -> wasmtime::Result<R> {
+        if let Some(tape_reset) = &self.tape_reset {
+            tape_reset.call(&mut store, ())?;
+        }
+        self.forward.call(&mut store, input)
+    }
+
+    /// Call the backward pass, taking cotangents for the results of the forward pass and
+    /// returning gradients for its parameters.
+    pub fn backward(&self, mut store: impl AsContextMut, cotangent: R) -> wasmtime::Result<P> {
+        self.backward.call(&mut store, cotangent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use floretta::Autodiff;
+    use wasmtime::{Engine, Instance, Linker, Module, Store};
+
+    use crate::{AutodiffInstance, WasmtimeAdDiff};
+
+    #[test]
+    fn test_typed_tape_reset() {
+        let input = wat::parse_str(
+            r#"
+(module
+  (func (export "square") (param f64) (result f64)
+    (f64.mul (local.get 0) (local.get 0))))
+"#,
+        )
+        .unwrap();
+        let mut ad = Autodiff::new();
+        ad.export("square", "backprop");
+        ad.export_tape_reset("reset");
+        ad.export_tape_pointer("tape");
+        let output = ad.reverse(&input).unwrap();
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let square = WasmtimeAdDiff::<f64, f64>::new(&mut store, instance, "square", "backprop")
+            .unwrap()
+            .with_tape_reset(&mut store, instance, "reset")
+            .unwrap();
+        let tape = instance
+            .get_typed_func::<(), i32>(&mut store, "tape")
+            .unwrap();
+        assert_eq!(square.call(&mut store, 2.).unwrap(), 4.);
+        let bytes = tape.call(&mut store, ()).unwrap();
+        assert_eq!(square.call(&mut store, 3.).unwrap(), 9.);
+        // The second call starts from an empty tape instead of pushing on top of the first.
+        assert_eq!(tape.call(&mut store, ()).unwrap(), bytes);
+        assert_eq!(square.backward(&mut store, 1.).unwrap(), 6.);
+        assert_eq!(tape.call(&mut store, ()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_instantiate_reverse_typed() {
+        let input = wat::parse_str(
+            r#"
+(module
+  (func (export "square") (param f64) (result f64)
+    (f64.mul (local.get 0) (local.get 0))))
+"#,
+        )
+        .unwrap();
+        let mut ad = Autodiff::new();
+        ad.export_tape_reset("reset");
+        let typed = ad
+            .reverse_typed::<f64, f64, f64, f64>(&input, "square", "backprop")
+            .unwrap();
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let square =
+            WasmtimeAdDiff::instantiate(&mut store, &Linker::new(&engine), &typed).unwrap();
+        assert_eq!(square.call(&mut store, 2.).unwrap(), 4.);
+        assert_eq!(square.call(&mut store, 3.).unwrap(), 9.);
+        assert_eq!(square.backward(&mut store, 1.).unwrap(), 6.);
+    }
+
+    #[test]
+    fn test_typed() {
+        let input = wat::parse_str(
+            r#"
+(module
+  (func (export "square") (param f64) (result f64)
+    (f64.mul (local.get 0) (local.get 0))))
+"#,
+        )
+        .unwrap();
+        let mut ad = Autodiff::new();
+        ad.export("square", "backprop");
+        let mut instance = AutodiffInstance::new(&ad, &input).unwrap();
+        let square = instance.typed::<f64, f64>("square", "backprop").unwrap();
+        assert_eq!(square.call(instance.store_mut(), 3.).unwrap(), 9.);
+        assert_eq!(square.backward(instance.store_mut(), 1.).unwrap(), 6.);
+    }
+
+    #[test]
+    fn test_import_func() {
+        let input = wat::parse_str(
+            r#"
+(module
+  (import "f64" "id" (func $id (param f64) (result f64)))
+  (func (export "id") (param f64) (result f64)
+    (call $id (local.get 0))))
+"#,
+        )
+        .unwrap();
+        let engine = Engine::default();
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap("f64", "id", |x: f64| x).unwrap();
+        let mut ad = Autodiff::new();
+        ad.import(("f64", "id"), ("f64", "id"));
+        ad.export("id", "backprop");
+        let mut instance = AutodiffInstance::with_linker(&linker, (), &ad, &input).unwrap();
+        assert_eq!(instance.call_forward::<f64, f64>("id", 2.).unwrap(), 2.);
+        assert_eq!(
+            instance.call_backward::<f64, f64>("backprop", 1.).unwrap(),
+            1.
+        );
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let input = wat::parse_str(
+            r#"
+(module
+  (func (export "square") (param f64) (result f64)
+    (f64.mul (local.get 0) (local.get 0))))
+"#,
+        )
+        .unwrap();
+        let mut ad = Autodiff::new();
+        ad.export("square", "backprop");
+        let mut instance = AutodiffInstance::new(&ad, &input).unwrap();
+        assert!(instance.call_forward::<f32, f32>("square", 3.).is_err());
+    }
+}
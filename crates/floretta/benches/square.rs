@@ -0,0 +1,264 @@
+//! Compare the per-call overhead of a reverse-mode `square` produced by Floretta against a
+//! hand-written backward pass that just stashes its input in a global, and against Floretta's own
+//! output with the `f64_mul` helpers inlined, to see how much of the overhead is the helper calls.
+
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use floretta::Autodiff;
+use wasmtime::{Engine, Instance, Module, Store, TypedFunc};
+
+const SQUARE: &str = r#"
+(module
+  (func (export "square") (param f64) (result f64)
+    (f64.mul (local.get 0) (local.get 0))))
+"#;
+
+const HANDWRITTEN: &str = r#"
+(module
+  (global $x (mut f64) (f64.const 0))
+  (func (export "square") (param f64) (result f64)
+    (global.set $x (local.get 0))
+    (f64.mul (local.get 0) (local.get 0)))
+  (func (export "backprop") (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (f64.mul (f64.const 2) (global.get $x)))))
+"#;
+
+/// What Floretta produces for [`SQUARE`], minus the unused helpers, with the bodies of `f64_mul`
+/// and `f64_mul_bwd` pasted in place of the calls to them.
+const INLINED: &str = r#"
+(module
+  (type $dispatch (func (param i32)))
+  (memory $tape 0)
+  (global $tape (mut i32) (i32.const 0))
+  (func $tape_i32 (param i32)
+    (local i32 i32)
+    global.get $tape
+    local.tee 1
+    i32.const 65539
+    i32.add
+    i32.const 16
+    i32.shr_u
+    memory.size
+    i32.sub
+    local.tee 2
+    i32.const 0
+    i32.gt_s
+    if
+      local.get 2
+      i32.const 16
+      local.get 2
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
+      drop
+    end
+    local.get 1
+    i32.const 4
+    i32.add
+    global.set $tape
+    local.get 1
+    local.get 0
+    i32.store align=1)
+  (func $tape_i32_bwd (result i32)
+    (local i32)
+    global.get $tape
+    i32.const 4
+    i32.sub
+    local.tee 0
+    global.set $tape
+    local.get 0
+    i32.load align=1)
+  (func (export "square") (param f64) (result f64)
+    (local i32 i32)
+    global.get $tape
+    local.tee 1
+    i32.const 65551
+    i32.add
+    i32.const 16
+    i32.shr_u
+    memory.size
+    i32.sub
+    local.tee 2
+    i32.const 0
+    i32.gt_s
+    if
+      local.get 2
+      i32.const 16
+      local.get 2
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
+      drop
+    end
+    local.get 1
+    i32.const 16
+    i32.add
+    global.set $tape
+    local.get 1
+    local.get 0
+    f64.store align=1
+    local.get 1
+    local.get 0
+    f64.store offset=8 align=1
+    local.get 0
+    local.get 0
+    f64.mul
+    i32.const 0
+    call $tape_i32)
+  (func (export "backprop") (param f64) (result f64)
+    (local i32)
+    call $tape_i32_bwd
+    loop $dispatch (type $dispatch) (param i32)
+      block $bb_0 (type $dispatch) (param i32)
+        block $invalid (type $dispatch) (param i32)
+          br_table $bb_0 $invalid
+        end
+        unreachable
+      end
+    end
+    global.get $tape
+    i32.const 16
+    i32.sub
+    local.tee 1
+    global.set $tape
+    local.get 0
+    local.get 1
+    f64.load offset=8 align=1
+    f64.mul
+    local.get 0
+    local.get 1
+    f64.load align=1
+    f64.mul
+    f64.add))
+"#;
+
+/// How many calls to time at once, so the cost of reading the clock doesn't swamp them.
+const BATCH: u64 = 1000;
+
+/// How many forward passes to run against a fresh tape.
+const TAPE_CALLS: u32 = 10_000;
+
+/// Bytes that each forward pass of [`SQUARE`] pushes onto the tape: two `f64` operands and an
+/// `i32` block index.
+const TAPE_BYTES_PER_CALL: u32 = 20;
+
+fn floretta(tape_pages: u32) -> Vec<u8> {
+    let mut ad = Autodiff::new();
+    ad.export("square", "backprop");
+    ad.tape_initial_pages(tape_pages);
+    ad.reverse(&wat::parse_str(SQUARE).unwrap()).unwrap()
+}
+
+fn modules() -> [(&'static str, Vec<u8>); 3] {
+    [
+        ("floretta", floretta(0)),
+        ("inlined", wat::parse_str(INLINED).unwrap()),
+        ("handwritten", wat::parse_str(HANDWRITTEN).unwrap()),
+    ]
+}
+
+fn instantiate(wasm: &[u8]) -> (Store<()>, TypedFunc<f64, f64>, TypedFunc<f64, f64>) {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, wasm).unwrap();
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let square = instance.get_typed_func(&mut store, "square").unwrap();
+    let backprop = instance.get_typed_func(&mut store, "backprop").unwrap();
+    (store, square, backprop)
+}
+
+fn bench_transform(c: &mut Criterion) {
+    let input = wat::parse_str(SQUARE).unwrap();
+    c.bench_function("transform", |b| {
+        b.iter(|| {
+            let mut ad = Autodiff::new();
+            ad.export("square", "backprop");
+            ad.reverse(black_box(&input)).unwrap()
+        })
+    });
+}
+
+fn bench_backprop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("backprop");
+    for (name, wasm) in modules() {
+        let (mut store, square, backprop) = instantiate(&wasm);
+        // Make sure all the modules really compute the same thing.
+        assert_eq!(square.call(&mut store, 3.).unwrap(), 9.);
+        assert_eq!(backprop.call(&mut store, 1.).unwrap(), 6.);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let y = square.call(&mut store, black_box(3.)).unwrap();
+                let dx = backprop.call(&mut store, black_box(1.)).unwrap();
+                (y, dx)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_forward(c: &mut Criterion) {
+    // Only the forward pass, to separate the cost of pushing onto the tape from the cost of
+    // popping it back off. The backward pass still has to run to keep the tape from overflowing,
+    // so it's done outside of the measured routine, once per batch.
+    let mut group = c.benchmark_group("forward");
+    for (name, wasm) in modules() {
+        let (mut store, square, backprop) = instantiate(&wasm);
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                let mut remaining = iters;
+                while remaining > 0 {
+                    let n = remaining.min(BATCH);
+                    let start = Instant::now();
+                    for _ in 0..n {
+                        black_box(square.call(&mut store, black_box(3.)).unwrap());
+                    }
+                    total += start.elapsed();
+                    for _ in 0..n {
+                        backprop.call(&mut store, 1.).unwrap();
+                    }
+                    remaining -= n;
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_tape(c: &mut Criterion) {
+    // Many forward passes against a fresh tape, which has to grow along the way unless it starts
+    // with enough pages to hold everything they push.
+    let mut group = c.benchmark_group("tape");
+    let pages = (TAPE_CALLS * TAPE_BYTES_PER_CALL).div_ceil(65536);
+    for (name, tape_pages) in [("grow", 0), ("preallocated", pages)] {
+        let wasm = floretta(tape_pages);
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || instantiate(&wasm),
+                |(mut store, square, _)| {
+                    for _ in 0..TAPE_CALLS {
+                        black_box(square.call(&mut store, black_box(3.)).unwrap());
+                    }
+                    store
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_transform,
+    bench_backprop,
+    bench_forward,
+    bench_tape,
+);
+criterion_main!(benches);
@@ -1,20 +1,173 @@
-use hashbrown::{hash_map::Entry, HashMap};
+use std::{borrow::Cow, rc::Rc};
+
+use hashbrown::{hash_map::Entry, HashMap, HashSet};
 
 use crate::{ErrorImpl, NoValidate, Transform, Validate};
 
 /// An error that occurred during code transformation.
+///
+/// This type implements [`std::error::Error`] and is [`Send`], [`Sync`], and `'static`, so it can
+/// already be converted into an [`anyhow::Error`] via the `?` operator without any extra feature
+/// flags.
+///
+/// [`anyhow::Error`]: https://docs.rs/anyhow/latest/anyhow/struct.Error.html
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 pub struct Error {
+    #[from]
     inner: ErrorImpl,
 }
 
+impl Error {
+    /// A short description of what failed.
+    ///
+    /// For most errors this omits further details, but for unsupported instructions it names the
+    /// instruction and its byte offset, e.g. `unsupported Wasm instruction: F64Abs at offset 42`.
+    pub fn description(&self) -> Cow<'_, str> {
+        self.inner.description()
+    }
+
+    /// The category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.inner.kind()
+    }
+
+    /// If this error is due to an instruction that the transformation doesn't support, the name of
+    /// that instruction's [`wasmparser::Operator`] variant, such as `"I32WrapI64"`.
+    pub fn unsupported_instruction(&self) -> Option<&'static str> {
+        match self.inner {
+            ErrorImpl::Unsupported { instruction, .. } => Some(instruction),
+            _ => None,
+        }
+    }
+
+    /// If the input Wasm could not be parsed or validated, the underlying error.
+    pub fn as_parse_error(&self) -> Option<&wasmparser::BinaryReaderError> {
+        match &self.inner {
+            ErrorImpl::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// If the output Wasm could not be encoded, the underlying error.
+    pub fn as_reencode_error(&self) -> Option<&wasm_encoder::reencode::Error> {
+        match &self.inner {
+            ErrorImpl::Reencode(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// The index of the function in which this error occurred, if known.
+    pub fn function_index(&self) -> Option<u32> {
+        match self.inner {
+            ErrorImpl::Unsupported { function_index, .. } => Some(function_index),
+            _ => None,
+        }
+    }
+
+    /// The offset in the input Wasm of the instruction at which this error occurred, if known.
+    pub fn byte_offset(&self) -> Option<u32> {
+        match self.inner {
+            ErrorImpl::Unsupported { byte_offset, .. } => Some(byte_offset),
+            _ => None,
+        }
+    }
+}
+
+/// The category of an [`Error`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The input is not valid Wasm.
+    Parse,
+
+    /// The input is valid Wasm but uses a feature that the transformation doesn't handle.
+    Transform,
+
+    /// An imported function, memory, or global has no derivative counterpart configured via
+    /// [`Autodiff::import`].
+    Import,
+
+    /// An export that the operation needs to configure already has a derivative counterpart
+    /// configured via [`Autodiff::export`].
+    Export,
+
+    /// The output Wasm could not be encoded.
+    Reencode,
+
+    /// The input contains an instruction that the transformation doesn't support; see
+    /// [`Error::unsupported_instruction`].
+    UnsupportedInstruction,
+}
+
+/// The transformation to use depending on whether to validate input Wasm.
+fn validation(enabled: bool) -> Box<dyn Transform> {
+    if enabled {
+        Box::new(Validate)
+    } else {
+        Box::new(NoValidate)
+    }
+}
+
+/// Computes a new export name from an existing one.
+type Rename = Rc<dyn Fn(&str) -> String>;
+
+/// How to name the exported backward pass of every exported function.
+#[derive(Clone)]
+pub(crate) enum ExportAll {
+    /// Append [`Autodiff::backward_suffix`] to the name of the original export.
+    Suffix,
+
+    /// Call a custom function on the name of the original export.
+    Rename(Rename),
+}
+
+/// What to do when reverse mode encounters an instruction it doesn't know how to differentiate.
+///
+/// Only [`UnsupportedInstructionPolicy::Fail`] applies to every instruction; the other policies
+/// apply only to numeric instructions whose operand and result types are fixed, and still fail for
+/// anything else.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnsupportedInstructionPolicy {
+    /// Return an error.
+    #[default]
+    Fail,
+
+    /// Emit the instruction unchanged in the forward pass, and treat all its float operands as
+    /// having zero adjoint in the backward pass.
+    ZeroGradient,
+
+    /// Emit the instruction unchanged in the forward pass and omit it from the backward pass. This
+    /// only works for instructions that neither consume nor produce float values.
+    Passthrough,
+}
+
+/// Statistics about the output of [`Autodiff::reverse`], from [`Autodiff::stats`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Stats {
+    /// Number of functions imported by the input module, which come before all the functions it
+    /// defines in the function index space.
+    pub imported_functions: u32,
+
+    /// Number of functions defined in the input module, each of which got a forward pass and a
+    /// backward pass.
+    pub functions: u32,
+
+    /// Total size in bytes of the bodies of the functions that got injected to manage the tape.
+    pub helper_bytes: usize,
+
+    /// For each function defined in the input module, an upper bound on the number of bytes that
+    /// one call to its forward pass pushes onto the tape; or `None` if there is no static bound,
+    /// because the function contains a loop or an indirect call, or is recursive.
+    pub tape_bytes: Vec<Option<u32>>,
+}
+
 /// WebAssembly code transformations for automatic differentiation.
 pub struct Autodiff {
     /// Name is a bit of a misnomer; this is just dynamic dispatch to choose whether or not to
     /// validate at the very beginning, so when doing the actual code transformation, validation
     /// dispatch is static.
-    transform: Box<dyn Transform>,
+    pub(crate) transform: Box<dyn Transform>,
 
     /// Import identifiers for the backward passes of imported functions.
     pub(crate) imports: HashMap<(String, String), (String, String)>,
@@ -22,9 +175,67 @@ pub struct Autodiff {
     /// Exported functions whose backward passes should also be exported.
     pub(crate) exports: HashMap<String, String>,
 
+    /// How to name the backward pass of every exported function not already in `exports`, if at
+    /// all.
+    pub(crate) export_all: Option<ExportAll>,
+
+    /// Exported functions whose exported backward passes return only some parameter adjoints.
+    pub(crate) partials: HashMap<String, Vec<u32>>,
+
+    /// Exported functions to leave undifferentiated.
+    pub(crate) skip: HashSet<String>,
+
+    /// Exported functions whose backward passes are given by other exported functions.
+    pub(crate) custom_gradients: HashMap<String, String>,
+
+    /// Whether to give each function its own tape.
+    pub(crate) tape_per_function: bool,
+
+    /// Name under which to export a function that resets the tape, if any.
+    pub(crate) tape_reset: Option<String>,
+
+    /// Name under which to export a function that returns the size of the tape, if any.
+    pub(crate) tape_pointer: Option<String>,
+
+    /// Number of pages the tape memory starts with.
+    pub(crate) tape_initial_pages: u32,
+
+    /// In forward mode, the index of the parameter whose tangent is fixed to one, if any.
+    pub(crate) seed: Option<u32>,
+
+    /// What to do with unsupported instructions in reverse mode.
+    pub(crate) error_policy: UnsupportedInstructionPolicy,
+
     /// Whether to include the names section in the output Wasm.
     #[cfg(feature = "names")]
     pub(crate) names: bool,
+
+    /// Suffix for names of backward-pass counterparts in the names section and in
+    /// [`Autodiff::export_all`].
+    pub(crate) backward_suffix: String,
+}
+
+impl Clone for Autodiff {
+    fn clone(&self) -> Self {
+        Self {
+            transform: self.transform.clone_box(),
+            imports: self.imports.clone(),
+            exports: self.exports.clone(),
+            export_all: self.export_all.clone(),
+            partials: self.partials.clone(),
+            skip: self.skip.clone(),
+            custom_gradients: self.custom_gradients.clone(),
+            tape_per_function: self.tape_per_function,
+            tape_reset: self.tape_reset.clone(),
+            tape_pointer: self.tape_pointer.clone(),
+            tape_initial_pages: self.tape_initial_pages,
+            seed: self.seed,
+            error_policy: self.error_policy,
+            #[cfg(feature = "names")]
+            names: self.names,
+            backward_suffix: self.backward_suffix.clone(),
+        }
+    }
 }
 
 impl Default for Autodiff {
@@ -36,29 +247,63 @@ impl Default for Autodiff {
 impl Autodiff {
     /// Default configuration.
     pub fn new() -> Self {
+        Self::with_validation(true)
+    }
+
+    /// Do not validate input Wasm.
+    pub fn no_validate() -> Self {
+        Self::with_validation(false)
+    }
+
+    /// Default configuration, except for whether to validate input Wasm.
+    pub fn with_validation(enabled: bool) -> Self {
         Self {
-            transform: Box::new(Validate),
+            transform: validation(enabled),
 
             imports: HashMap::new(),
 
             exports: HashMap::new(),
 
+            export_all: None,
+
+            partials: HashMap::new(),
+
+            skip: HashSet::new(),
+
+            custom_gradients: HashMap::new(),
+
+            tape_per_function: false,
+
+            tape_reset: None,
+
+            tape_pointer: None,
+
+            tape_initial_pages: 0,
+
+            seed: None,
+
+            error_policy: UnsupportedInstructionPolicy::Fail,
+
             #[cfg(feature = "names")]
             names: false,
+
+            backward_suffix: "_bwd".to_string(),
         }
     }
 
-    /// Do not validate input Wasm.
-    pub fn no_validate() -> Self {
+    /// Set whether to validate input Wasm.
+    pub fn with_validate(self, enabled: bool) -> Self {
         Self {
-            transform: Box::new(NoValidate),
-
-            imports: HashMap::new(),
-
-            exports: HashMap::new(),
+            transform: validation(enabled),
+            ..self
+        }
+    }
 
-            #[cfg(feature = "names")]
-            names: false,
+    /// Set what to do when reverse mode encounters an unsupported instruction.
+    pub fn with_error_policy(self, policy: UnsupportedInstructionPolicy) -> Self {
+        Self {
+            error_policy: policy,
+            ..self
         }
     }
 
@@ -68,6 +313,22 @@ impl Autodiff {
         self.names = true;
     }
 
+    /// Set whether to include the name section in the output Wasm.
+    #[cfg(feature = "names")]
+    pub fn with_names(self, enabled: bool) -> Self {
+        Self {
+            names: enabled,
+            ..self
+        }
+    }
+
+    /// Set the suffix used to name the backward pass of each named function, type, and memory in
+    /// the name section of the output Wasm, and of each export added by [`Autodiff::export_all`].
+    /// The default is `_bwd`.
+    pub fn backward_suffix(&mut self, suffix: impl Into<String>) {
+        self.backward_suffix = suffix.into();
+    }
+
     pub fn import(
         &mut self,
         primal: (impl Into<String>, impl Into<String>),
@@ -81,6 +342,21 @@ impl Autodiff {
         }
     }
 
+    /// Call [`Autodiff::import`] on each pair of import names.
+    pub fn import_batch<A, B, C, D>(&mut self, pairs: impl IntoIterator<Item = ((A, B), (C, D))>)
+    where
+        A: Into<String>,
+        B: Into<String>,
+        C: Into<String>,
+        D: Into<String>,
+    {
+        let pairs = pairs.into_iter();
+        self.imports.reserve(pairs.size_hint().0);
+        for (primal, derivative) in pairs {
+            self.import(primal, derivative);
+        }
+    }
+
     /// In the output Wasm, also export the derivative counterpart of an export from the input Wasm.
     pub fn export(&mut self, primal: impl Into<String>, derivative: impl Into<String>) {
         match self.exports.entry(primal.into()) {
@@ -91,17 +367,303 @@ impl Autodiff {
         }
     }
 
+    /// Like [`Autodiff::export`], but return an error instead of panicking if `primal` already has
+    /// a mapping.
+    pub(crate) fn try_export(&mut self, primal: String, derivative: String) -> crate::Result<()> {
+        match self.exports.entry(primal) {
+            Entry::Occupied(entry) => Err(ErrorImpl::Export {
+                name: entry.key().clone(),
+            }),
+            Entry::Vacant(entry) => {
+                entry.insert(derivative);
+                Ok(())
+            }
+        }
+    }
+
+    /// In the output Wasm, also export the backward pass of every exported function, under its
+    /// name with the [backward suffix](Autodiff::backward_suffix) appended.
+    ///
+    /// Exports configured individually via [`Autodiff::export`] take precedence.
+    pub fn export_all(&mut self) {
+        self.export_all = Some(ExportAll::Suffix);
+    }
+
+    /// In the output Wasm, also export the backward pass of every exported function, under the
+    /// name that `rename` returns for the name of the original export.
+    ///
+    /// Exports configured individually via [`Autodiff::export`] take precedence.
+    pub fn export_all_with(&mut self, rename: impl Fn(&str) -> String + 'static) {
+        self.export_all = Some(ExportAll::Rename(Rc::new(rename)));
+    }
+
+    /// The name under which to export the backward pass of the function exported as `name`, if
+    /// any.
+    pub(crate) fn export_name(&self, name: &str) -> Option<String> {
+        match self.exports.get(name) {
+            Some(name_bwd) => Some(name_bwd.clone()),
+            None => self.export_all.as_ref().map(|export_all| match export_all {
+                ExportAll::Suffix => format!("{name}{}", self.backward_suffix),
+                ExportAll::Rename(rename) => rename(name),
+            }),
+        }
+    }
+
+    /// In reverse mode, make the exported backward pass of the function exported as `name` return
+    /// only the adjoints of the parameters at the given `indices`, in that order.
+    ///
+    /// This only has an effect if the backward pass gets exported, via [`Autodiff::export`] or
+    /// [`Autodiff::export_all`]. The backward pass still computes every adjoint internally; the
+    /// rest just get dropped before returning. Every index must refer to a floating-point
+    /// parameter, because integer parameters have no adjoints.
+    pub fn differentiate_params(
+        &mut self,
+        name: impl Into<String>,
+        indices: impl IntoIterator<Item = u32>,
+    ) {
+        self.partials
+            .insert(name.into(), indices.into_iter().collect());
+    }
+
+    /// In reverse mode, leave the function exported as `name` undifferentiated.
+    ///
+    /// Its forward pass is just the original function, so it can use instructions that reverse
+    /// mode doesn't otherwise support. Other functions can still call it, and its backward pass
+    /// ignores the result adjoints and returns zero for every parameter adjoint. Calls from a
+    /// skipped function into functions that are not skipped still push onto the tape, so those
+    /// should be avoided.
+    pub fn skip(&mut self, name: impl Into<String>) {
+        self.skip.insert(name.into());
+    }
+
+    /// In reverse mode, use the function exported as `derivative` as the backward pass of the
+    /// function exported as `primal`, instead of differentiating the body of `primal`.
+    ///
+    /// The forward pass of `primal` is just the original function, and its backward pass calls
+    /// `derivative` with the result adjoints of `primal`, so `derivative` must take the
+    /// floating-point results of `primal` as parameters and return its floating-point parameters.
+    /// Like the backward pass of an import, `derivative` gets no other information about the call,
+    /// so `primal` needs to save anything else it needs somewhere, such as in a global. Like
+    /// functions passed to [`Autodiff::skip`], `derivative` itself is not differentiated.
+    pub fn custom_gradient(&mut self, primal: impl Into<String>, derivative: impl Into<String>) {
+        match self.custom_gradients.entry(primal.into()) {
+            Entry::Occupied(entry) => {
+                panic!("custom gradient already exists for {:?}", entry.key())
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(derivative.into());
+            }
+        }
+    }
+
+    /// Whether the body of the function exported as `name` gets copied as-is instead of being
+    /// differentiated.
+    pub(crate) fn is_opaque(&self, name: &str) -> bool {
+        self.skip.contains(name)
+            || self.custom_gradients.contains_key(name)
+            || self
+                .custom_gradients
+                .values()
+                .any(|derivative| derivative == name)
+    }
+
+    /// In reverse mode, give each function its own tape instead of sharing one tape across the
+    /// whole module.
+    ///
+    /// By default, the forward pass of every function pushes onto the same tape, so the backward
+    /// passes must be called in exactly the reverse order of the forward passes. With this option,
+    /// the forward and backward passes of different functions can be interleaved freely, at the
+    /// cost of duplicating the tape helper functions for every function in the module.
+    pub fn tape_per_function(&mut self) {
+        self.tape_per_function = true;
+    }
+
+    /// In reverse mode, also export a function with the given `name` that takes no arguments and
+    /// empties the tape.
+    ///
+    /// Every call to a forward pass pushes onto the tape, and only the matching call to the
+    /// backward pass pops it back off; so if the host calls forward passes without ever calling
+    /// their backward passes, the tape grows without bound unless it gets reset.
+    pub fn export_tape_reset(&mut self, name: impl Into<String>) {
+        self.tape_reset = Some(name.into());
+    }
+
+    /// In reverse mode, also export a function with the given `name` that takes no arguments and
+    /// returns the total number of bytes currently on the tape, as an `i32`.
+    pub fn export_tape_pointer(&mut self, name: impl Into<String>) {
+        self.tape_pointer = Some(name.into());
+    }
+
+    /// In reverse mode, start the tape memory with the given number of pages instead of zero.
+    ///
+    /// The tape grows whenever it runs out of space, so this isn't necessary for correctness, but
+    /// if the tape usage of a forward pass fits in these pages, it never has to call `memory.grow`.
+    pub fn tape_initial_pages(&mut self, pages: u32) {
+        self.tape_initial_pages = pages;
+    }
+
     /// Transform a WebAssembly module to compute derivatives in forward mode.
     pub fn forward(&self, wasm: &[u8]) -> Result<Vec<u8>, Error> {
+        self.transform.forward(self, wasm, 1).map_err(Error::from)
+    }
+
+    /// Transform a WebAssembly module to compute partial derivatives in forward mode.
+    ///
+    /// Unlike [`Autodiff::forward`], the tangents of the parameters are not passed in. Instead,
+    /// every exported function takes only its original parameters, and computes its results along
+    /// with their partial derivatives with respect to the parameter at index `seed_index`, which
+    /// must be a float parameter of every exported function. Functions still call each other with
+    /// tangents as in [`Autodiff::forward`], so only the exports are seeded.
+    pub fn forward_seeded(&self, wasm: &[u8], seed_index: u32) -> Result<Vec<u8>, Error> {
+        Self {
+            seed: Some(seed_index),
+            ..self.clone()
+        }
+        .forward(wasm)
+    }
+
+    /// Transform a WebAssembly module to compute derivatives in forward mode along `batch_size`
+    /// directions at once.
+    ///
+    /// This is like [`Autodiff::forward`], except that each float is followed by `batch_size`
+    /// tangents instead of just one, so a function with `n` float parameters and `m` float results
+    /// takes `n * (1 + batch_size)` floats and returns `m * (1 + batch_size)` floats. Seeding each
+    /// direction with a different basis vector computes that many columns of the Jacobian in one
+    /// call. Since [`Autodiff::import`] configures just one tangent for each imported memory or
+    /// float global, those can only be imported when `batch_size` is one.
+    pub fn batch_forward(&self, wasm: &[u8], batch_size: usize) -> Result<Vec<u8>, Error> {
+        let batch_size = u32::try_from(batch_size)
+            .map_err(|_| ErrorImpl::Transform("batch size does not fit in a u32"))?;
         self.transform
-            .forward(self, wasm)
-            .map_err(|inner| Error { inner })
+            .forward(self, wasm, batch_size)
+            .map_err(Error::from)
     }
 
     /// Transform a WebAssembly module to compute derivatives in reverse mode.
     pub fn reverse(&self, wasm: &[u8]) -> Result<Vec<u8>, Error> {
-        self.transform
-            .reverse(self, wasm)
-            .map_err(|inner| Error { inner })
+        self.transform.reverse(self, wasm).map_err(Error::from)
+    }
+
+    /// Names of the instructions that [`Autodiff::reverse`] can differentiate, spelled the same
+    /// way as in [`Error::unsupported_instruction`].
+    pub fn supported_instructions() -> Vec<&'static str> {
+        crate::reverse::SUPPORTED_INSTRUCTIONS.to_vec()
+    }
+
+    /// Check whether [`Autodiff::reverse`] supports every instruction in a WebAssembly module,
+    /// without transforming it.
+    ///
+    /// Instructions allowed by the [`UnsupportedInstructionPolicy`] and instructions in functions
+    /// marked via [`Autodiff::skip`] or [`Autodiff::custom_gradient`] count as supported. On
+    /// failure, this returns each distinct unsupported instruction once, in the order they first
+    /// appear; or if the module can't be parsed, just the parse error. This doesn't validate the
+    /// module, so it isn't a guarantee that [`Autodiff::reverse`] will succeed.
+    pub fn check_support(&self, wasm: &[u8]) -> Result<(), Vec<String>> {
+        match self.unsupported_instructions(wasm) {
+            Ok(found) if found.is_empty() => Ok(()),
+            Ok(found) => {
+                let mut unsupported = Vec::new();
+                for (_, name) in found {
+                    if !unsupported.iter().any(|found| found == name) {
+                        unsupported.push(name.to_string());
+                    }
+                }
+                Err(unsupported)
+            }
+            Err(err) => Err(vec![err.to_string()]),
+        }
+    }
+
+    /// List every instruction in a WebAssembly module that [`Autodiff::reverse`] doesn't support,
+    /// along with the index of the function containing it, without transforming the module.
+    ///
+    /// Instructions are spelled the same way as in [`Error::unsupported_instruction`], and listed
+    /// once per occurrence, in order. What counts as supported is the same as for
+    /// [`Autodiff::check_support`], and likewise this doesn't validate the module.
+    pub fn unsupported_instructions(&self, wasm: &[u8]) -> Result<Vec<(u32, &'static str)>, Error> {
+        crate::reverse::unsupported_instructions(self, wasm).map_err(Error::from)
+    }
+
+    /// Describe, in a human-readable multi-line string, what [`Autodiff::reverse`] would do to a
+    /// WebAssembly module: which functions get split into forward and backward passes, which
+    /// helpers get injected, which exports get added, and any configuration that doesn't match.
+    ///
+    /// This only does a light pass over the module, so it doesn't validate the input or check for
+    /// unsupported instructions.
+    pub fn explain(&self, wasm: &[u8]) -> Result<String, Error> {
+        crate::explain::explain(self, wasm).map_err(Error::from)
+    }
+
+    /// Apply [`Autodiff::reverse`] to a Wasm module and report how big the result is and how much
+    /// tape it uses.
+    pub fn stats(&self, wasm: &[u8]) -> Result<Stats, Error> {
+        crate::stats::stats(self, wasm).map_err(Error::from)
+    }
+
+    /// Transform a WebAssembly module to compute second derivatives via reverse mode over forward
+    /// mode.
+    ///
+    /// This first applies [`Autodiff::forward`], then applies [`Autodiff::reverse`] to the result,
+    /// additionally exporting the backward pass of the forward-mode `name` export as `name_rvf`.
+    ///
+    /// Calling `name` with the parameter tangents set to a vector `v`, then calling `name_rvf`
+    /// with a cotangent of zero for each primal result and one for each tangent result, gives the
+    /// Hessian-vector product `H v` as the cotangents of the primal parameters. So for a function
+    /// with `n` parameters, doing this once for each of the `n` basis vectors gives the full
+    /// Hessian.
+    pub fn reverse_of_forward(
+        &self,
+        wasm: &[u8],
+        name: impl Into<String>,
+        name_rvf: impl Into<String>,
+    ) -> Result<Vec<u8>, Error> {
+        crate::second_order::reverse_of_forward(self, wasm, name.into(), name_rvf.into())
+            .map_err(Error::from)
+    }
+
+    /// Transform a WebAssembly module to compute second derivatives via reverse mode over forward
+    /// mode, such as Hessian-vector products.
+    ///
+    /// This first applies [`Autodiff::forward`], then applies [`Autodiff::reverse`] to the result,
+    /// so the backward pass of each forward-mode function is exported as configured via
+    /// [`Autodiff::export`] or [`Autodiff::export_all`]. Unlike [`Autodiff::reverse_of_forward`],
+    /// this doesn't add an export of its own.
+    pub fn second_order_reverse_over_forward(&self, wasm: &[u8]) -> Result<Vec<u8>, Error> {
+        crate::second_order::reverse_over_forward(self, wasm).map_err(Error::from)
+    }
+
+    /// Transform a WebAssembly module to compute second derivatives via forward mode over reverse
+    /// mode, such as Hessian-vector products.
+    ///
+    /// This first applies [`Autodiff::reverse`], then applies [`Autodiff::forward`] to the result,
+    /// so every function and backward pass takes and returns a tangent alongside each float.
+    pub fn second_order_forward_over_reverse(&self, wasm: &[u8]) -> Result<Vec<u8>, Error> {
+        crate::second_order::forward_over_reverse(self, wasm).map_err(Error::from)
+    }
+
+    /// Transform a WebAssembly module in reverse mode, additionally exporting the backward pass of
+    /// the `name` export as `name_bwd`, and remember the types of both so that they can be checked
+    /// when instantiating the result with Wasmtime.
+    ///
+    /// The type parameters are the primal parameters `P`, primal results `R`, parameter adjoints
+    /// `DP`, and result adjoints `DR`, in the same order as [`wasmtime::TypedFunc`] expects them.
+    #[cfg(feature = "wasmtime")]
+    pub fn reverse_typed<P, R, DP, DR>(
+        &self,
+        wasm: &[u8],
+        name: impl Into<String>,
+        name_bwd: impl Into<String>,
+    ) -> Result<crate::ReverseTyped<P, R, DP, DR>, Error> {
+        let name = name.into();
+        let name_bwd = name_bwd.into();
+        let mut config = self.clone();
+        config.try_export(name.clone(), name_bwd.clone())?;
+        let wasm = config.reverse(wasm)?;
+        Ok(crate::ReverseTyped::new(
+            wasm,
+            name,
+            name_bwd,
+            config.tape_reset.clone(),
+        ))
     }
 }
@@ -0,0 +1,222 @@
+use std::fmt::Write;
+
+use wasmparser::{ExternalKind, Parser, Payload, TypeRef, ValType};
+
+use crate::{
+    helper::{OFFSET_FUNCTIONS, OFFSET_GLOBALS, OFFSET_MEMORIES},
+    util::TwoStrs,
+    Autodiff,
+};
+
+/// Describe what [`Autodiff::reverse`] would do to a Wasm module, without transforming it.
+pub fn explain(config: &Autodiff, wasm_module: &[u8]) -> crate::Result<String> {
+    let mut imports = Vec::new();
+    let mut num_functions = 0;
+    let mut num_memories = 0;
+    let mut num_globals = 0;
+    let mut num_global_adjoints = 0;
+    let mut adjoint_imports = Vec::new();
+    let mut exports = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm_module) {
+        match payload? {
+            Payload::ImportSection(section) => {
+                for import in section {
+                    let import = import?;
+                    match import.ty {
+                        TypeRef::Func(_) => imports.push((import.module, import.name)),
+                        TypeRef::Memory(_) => {
+                            num_memories += 1;
+                            adjoint_imports.push(("memory", import.module, import.name));
+                        }
+                        TypeRef::Global(ty) => {
+                            num_globals += 1;
+                            if ty.mutable && matches!(ty.content_type, ValType::F32 | ValType::F64)
+                            {
+                                num_global_adjoints += 1;
+                                adjoint_imports.push(("global", import.module, import.name));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Payload::FunctionSection(section) => num_functions += section.count(),
+            Payload::MemorySection(section) => num_memories += section.count(),
+            Payload::GlobalSection(section) => {
+                for global in section {
+                    let ty = global?.ty;
+                    num_globals += 1;
+                    if ty.mutable && matches!(ty.content_type, ValType::F32 | ValType::F64) {
+                        num_global_adjoints += 1;
+                    }
+                }
+            }
+            Payload::ExportSection(section) => {
+                for export in section {
+                    let export = export?;
+                    if export.kind == ExternalKind::Func {
+                        exports.push((export.name, export.index));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let num_imports: u32 = imports.len().try_into().unwrap();
+
+    // Writing to a `String` never fails, so we ignore the results of `writeln!` throughout.
+    let mut out = String::new();
+    let mut warnings = Vec::new();
+    for (index, &(module, name)) in (0..).zip(&imports) {
+        match config.imports.get(&TwoStrs(module, name)) {
+            Some((module_bwd, name_bwd)) => {
+                let _ = writeln!(
+                    out,
+                    "import {index} {module:?} {name:?}: backward pass imported as \
+                     {module_bwd:?} {name_bwd:?}",
+                );
+            }
+            None => warnings.push(format!("no import configured for {module:?} {name:?}")),
+        }
+    }
+    for &(kind, module, name) in &adjoint_imports {
+        match config.imports.get(&TwoStrs(module, name)) {
+            Some((module_bwd, name_bwd)) => {
+                let _ = writeln!(
+                    out,
+                    "import {kind} {module:?} {name:?}: adjoint imported as \
+                     {module_bwd:?} {name_bwd:?}",
+                );
+            }
+            None => warnings.push(format!("no import configured for {module:?} {name:?}")),
+        }
+    }
+    for index in num_imports..num_imports + num_functions {
+        let funcidx = OFFSET_FUNCTIONS + 2 * index;
+        let _ = writeln!(
+            out,
+            "function {index}: forward pass is function {funcidx}, backward pass is function {}",
+            funcidx + 1,
+        );
+    }
+    for &(name, index) in &exports {
+        if config.skip.contains(name) {
+            let _ = writeln!(
+                out,
+                "export {name:?}: function {index} skipped, so its backward pass returns zeros",
+            );
+        }
+        if let Some(derivative) = config.custom_gradients.get(name) {
+            let _ = writeln!(
+                out,
+                "export {name:?}: backward pass of function {index} calls {derivative:?}",
+            );
+        }
+        if let Some(name_bwd) = config.export_name(name) {
+            let _ = writeln!(
+                out,
+                "export {name:?}: backward pass of function {index} exported as {name_bwd:?}",
+            );
+        }
+    }
+    let mut missing: Vec<_> = config
+        .exports
+        .keys()
+        .chain(&config.skip)
+        .chain(config.custom_gradients.keys())
+        .chain(config.custom_gradients.values())
+        .filter(|&name| !exports.iter().any(|&(export, _)| export == name))
+        .collect();
+    missing.sort();
+    for name in missing {
+        warnings.push(format!("no function exported as {name:?}"));
+    }
+    let copies = if config.tape_per_function {
+        1 + num_functions
+    } else {
+        1
+    };
+    let _ = writeln!(
+        out,
+        "helpers: {} functions, {} memories, {} globals",
+        copies * OFFSET_FUNCTIONS,
+        copies * OFFSET_MEMORIES,
+        copies * OFFSET_GLOBALS,
+    );
+    if let Some(name) = &config.tape_reset {
+        let _ = writeln!(out, "tape reset exported as {name:?}");
+    }
+    if let Some(name) = &config.tape_pointer {
+        let _ = writeln!(out, "tape pointer exported as {name:?}");
+    }
+    let _ = writeln!(
+        out,
+        "memories: {num_memories} duplicated to hold adjoints; globals: {num_globals} kept, \
+         {num_global_adjoints} of which get adjoint globals",
+    );
+    for warning in warnings {
+        let _ = writeln!(out, "warning: {warning}");
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Autodiff;
+
+    #[test]
+    fn test_explain() {
+        let input = wat::parse_str(include_str!("wat/import_func.wat")).unwrap();
+        let mut ad = Autodiff::new();
+        ad.export("sigmoid", "backprop");
+        ad.export("missing", "missing_bwd");
+        assert_eq!(
+            ad.explain(&input).unwrap(),
+            "\
+function 1: forward pass is function 34, backward pass is function 35
+export \"sigmoid\": backward pass of function 1 exported as \"backprop\"
+helpers: 32 functions, 1 memories, 1 globals
+memories: 0 duplicated to hold adjoints; globals: 0 kept, 0 of which get adjoint globals
+warning: no import configured for \"f64\" \"exp\"
+warning: no function exported as \"missing\"
+",
+        );
+    }
+
+    #[test]
+    fn test_explain_import_memory_global() {
+        let input = wat::parse_str(include_str!("wat/import_memory_global_mut.wat")).unwrap();
+        let mut ad = Autodiff::new();
+        ad.import(("env", "memory"), ("env", "memory_bwd"));
+        ad.export_tape_reset("reset");
+        assert_eq!(
+            ad.explain(&input).unwrap(),
+            "\
+import memory \"env\" \"memory\": adjoint imported as \"env\" \"memory_bwd\"
+function 0: forward pass is function 32, backward pass is function 33
+helpers: 32 functions, 1 memories, 1 globals
+tape reset exported as \"reset\"
+memories: 1 duplicated to hold adjoints; globals: 3 kept, 1 of which get adjoint globals
+warning: no import configured for \"env\" \"offset\"
+",
+        );
+    }
+    #[test]
+    fn test_explain_skip() {
+        let input = wat::parse_str(include_str!("wat/skip.wat")).unwrap();
+        let mut ad = Autodiff::new();
+        ad.skip("scale");
+        ad.export("mul_scaled", "backprop");
+        assert_eq!(
+            ad.explain(&input).unwrap(),
+            "\
+function 0: forward pass is function 32, backward pass is function 33
+function 1: forward pass is function 34, backward pass is function 35
+export \"scale\": function 0 skipped, so its backward pass returns zeros
+export \"mul_scaled\": backward pass of function 1 exported as \"backprop\"
+helpers: 32 functions, 1 memories, 1 globals
+memories: 0 duplicated to hold adjoints; globals: 0 kept, 0 of which get adjoint globals
+",
+        );
+    }
+}
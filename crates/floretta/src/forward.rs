@@ -1,27 +1,63 @@
+//! Forward mode, where each float is followed by its tangents.
+//!
+//! Plain forward mode gives each float a single tangent, so it becomes a pair of primal and
+//! tangent; batch forward mode gives each float several tangents, one for each direction. Either
+//! way, we call the primal and its tangents the lanes of a float.
+
+use std::convert::Infallible;
+
 use wasm_encoder::{
     reencode::{Reencode, RoundtripReencoder},
-    CodeSection, ExportSection, Function, FunctionSection, InstructionSink, Module, TypeSection,
+    CodeSection, ConstExpr, CustomSection, DataCountSection, DataSection, ExportKind,
+    ExportSection, Function, FunctionSection, GlobalSection, ImportSection, Instruction,
+    InstructionSink, MemorySection, Module, StartSection, TypeSection,
+};
+use wasmparser::{
+    Data, DataKind, FunctionBody, Global, Import, KnownCustom, Operator, Parser, Payload, TypeRef,
 };
-use wasmparser::{FunctionBody, Operator, Parser, Payload};
 
 use crate::{
-    util::{u32_to_usize, FuncTypes, ValType},
+    util::{operator_name, u32_to_usize, BlockType, FuncTypes, TwoStrs, ValType},
     validate::{FunctionValidator, ModuleValidator},
-    Autodiff,
+    Autodiff, ErrorImpl,
 };
 
 pub fn transform(
     mut validator: impl ModuleValidator,
-    _: &Autodiff,
+    config: &Autodiff,
     wasm_module: &[u8],
+    tangents: u32,
 ) -> crate::Result<Vec<u8>> {
+    // Every function gets temporary locals for all the lanes of a few floats, so once we know that
+    // those fit, the number of lanes does too.
+    Tmp::count(tangents).ok_or(ErrorImpl::Transform("too many tangents"))?;
+    let width = 1 + tangents;
     let mut types = TypeSection::new();
+    let mut imports = ImportSection::new();
     let mut functions = FunctionSection::new();
+    let mut memories = MemorySection::new();
+    let mut globals = GlobalSection::new();
     let mut exports = ExportSection::new();
+    let mut start = None;
+    let mut data_count = None;
     let mut code = CodeSection::new();
+    let mut data = DataSection::new();
+    let mut customs = Vec::new();
     let mut type_sigs = FuncTypes::new();
     let mut func_types = Vec::new();
+    let mut global_types = Vec::new();
+    let mut global_indices = Vec::new();
+    let mut num_memories: u32 = 0;
+    let mut num_globals: u32 = 0;
+    let mut num_func_imports = 0;
     let mut num_bodies = 0;
+    let mut func_infos = Vec::new();
+    // With a seed, the index of the function that each seeded wrapper calls.
+    let mut wrapped = Vec::new();
+
+    #[cfg(feature = "names")]
+    let mut names = None;
+
     for payload in Parser::new(0).parse_all(wasm_module) {
         match payload? {
             Payload::TypeSection(section) => {
@@ -29,10 +65,73 @@ pub fn transform(
                 for ty in section.into_iter_err_on_gc_types() {
                     let typeidx = type_sigs.push(ty?)?;
                     types.ty().function(
-                        tuple(type_sigs.params(typeidx)),
-                        tuple(type_sigs.results(typeidx)),
+                        lanes(type_sigs.params(typeidx), width),
+                        lanes(type_sigs.results(typeidx), width),
                     );
                 }
+                // Block types with a single float result need a function type for all its lanes, so
+                // we add those at the end of the type section.
+                types.ty().function([], lanes(&[ValType::F32], width));
+                types.ty().function([], lanes(&[ValType::F64], width));
+                // With a seed, the parameter tangents of each exported function are constants
+                // instead of parameters, so its wrapper uses one of these copies instead.
+                if config.seed.is_some() {
+                    for (_, params, results) in type_sigs.iter() {
+                        types
+                            .ty()
+                            .function(params.iter().map(|&ty| ty.into()), lanes(results, width));
+                    }
+                }
+            }
+            Payload::ImportSection(section) => {
+                validator.import_section(&section)?;
+                for import in section {
+                    let Import { module, name, ty } = import?;
+                    match ty {
+                        // The host must provide the forward-mode version of an imported function,
+                        // which has the same type index because types are transformed in place.
+                        TypeRef::Func(typeidx) => {
+                            imports.import(
+                                module,
+                                name,
+                                wasm_encoder::EntityType::Function(typeidx),
+                            );
+                            func_types.push(typeidx);
+                            num_func_imports += 1;
+                        }
+                        // Like the memory section, each imported memory is followed by another
+                        // memory for its tangent, which is imported from the configured name.
+                        TypeRef::Memory(memory_ty) => {
+                            let (module_tan, name_tan) =
+                                import_tangent(config, tangents, module, name)?;
+                            let memory_type = RoundtripReencoder.memory_type(memory_ty);
+                            imports.import(module, name, memory_type);
+                            imports.import(module_tan, name_tan, memory_type);
+                            num_memories = num_memories
+                                .checked_add(2)
+                                .ok_or(ErrorImpl::Transform("too many memories"))?;
+                        }
+                        TypeRef::Global(global_ty) => {
+                            let val_type = ValType::try_from(global_ty.content_type)?;
+                            let global_type = RoundtripReencoder.global_type(global_ty)?;
+                            global_indices.push(num_globals);
+                            imports.import(module, name, global_type);
+                            num_globals += 1;
+                            if val_type.is_float() {
+                                let (module_tan, name_tan) =
+                                    import_tangent(config, tangents, module, name)?;
+                                imports.import(module_tan, name_tan, global_type);
+                                num_globals += 1;
+                            }
+                            global_types.push(val_type);
+                        }
+                        // Tables only hold references and tags only refer to types, neither of
+                        // which has a tangent, so these can be imported unchanged.
+                        TypeRef::Table(_) | TypeRef::Tag(_) => {
+                            imports.import(module, name, RoundtripReencoder.entity_type(ty)?);
+                        }
+                    }
+                }
             }
             Payload::FunctionSection(section) => {
                 validator.function_section(&section)?;
@@ -42,126 +141,1244 @@ pub fn transform(
                     func_types.push(t);
                 }
             }
+            Payload::MemorySection(section) => {
+                validator.memory_section(&section)?;
+                for memory_ty in section {
+                    let memory_type = RoundtripReencoder.memory_type(memory_ty?);
+                    num_memories = num_memories
+                        .checked_add(width)
+                        .ok_or(ErrorImpl::Transform("too many memories"))?;
+                    // Each memory is followed by one copy for each of its tangents.
+                    for _ in 0..width {
+                        memories.memory(memory_type);
+                    }
+                }
+            }
+            Payload::GlobalSection(section) => {
+                validator.global_section(&section)?;
+                for global in section {
+                    let Global { ty, init_expr } = global?;
+                    let val_type = ValType::try_from(ty.content_type)?;
+                    let global_type = RoundtripReencoder.global_type(ty)?;
+                    // Like locals, each float global is followed by more globals for its tangents.
+                    global_indices.push(num_globals);
+                    globals.global(global_type, &RoundtripReencoder.const_expr(init_expr)?);
+                    num_globals = num_globals
+                        .checked_add(1)
+                        .ok_or(ErrorImpl::Transform("too many globals"))?;
+                    let zero = match val_type {
+                        ValType::I32 | ValType::I64 => None,
+                        ValType::F32 => Some(ConstExpr::f32_const(0.)),
+                        ValType::F64 => Some(ConstExpr::f64_const(0.)),
+                    };
+                    if let Some(zero) = zero {
+                        for _ in 0..tangents {
+                            globals.global(global_type, &zero);
+                        }
+                        num_globals = num_globals
+                            .checked_add(tangents)
+                            .ok_or(ErrorImpl::Transform("too many globals"))?;
+                    }
+                    global_types.push(val_type);
+                }
+            }
             Payload::ExportSection(section) => {
                 validator.export_section(&section)?;
-                RoundtripReencoder.parse_export_section(&mut exports, section)?;
+                for export in section {
+                    let e = export?;
+                    let kind = RoundtripReencoder.export_kind(e.kind);
+                    let index = match kind {
+                        ExportKind::Func => match config.seed {
+                            Some(seed) => {
+                                let typeidx = func_types[u32_to_usize(e.index)];
+                                check_seed(type_sigs.params(typeidx), seed)?;
+                                // The wrappers go after all the other functions.
+                                let index = func_types.len() + wrapped.len();
+                                wrapped.push(e.index);
+                                index.try_into().unwrap()
+                            }
+                            None => e.index,
+                        },
+                        ExportKind::Memory => width * e.index,
+                        ExportKind::Global => global_indices[u32_to_usize(e.index)],
+                        _ => e.index,
+                    };
+                    exports.export(e.name, kind, index);
+                }
+            }
+            Payload::StartSection { func, range } => {
+                validator.start_section(func, &range)?;
+                // Function indices are unchanged, so this is the forward-mode start function.
+                start = Some(StartSection {
+                    function_index: func,
+                });
+            }
+            Payload::DataCountSection { count, range } => {
+                validator.data_count_section(count, &range)?;
+                data_count = Some(DataCountSection { count });
             }
             Payload::CodeSectionEntry(body) => {
                 let func = validator.code_section_entry(&body)?;
-                code.function(&function(func, &type_sigs, func_types[num_bodies], body)?);
+                let (info, body) = function(
+                    func,
+                    &type_sigs,
+                    &func_types,
+                    &global_types,
+                    &global_indices,
+                    tangents,
+                    (num_func_imports + num_bodies).try_into().unwrap(),
+                    body,
+                )?;
+                func_infos.push(info);
+                code.function(&body);
                 num_bodies += 1;
             }
+            Payload::DataSection(section) => {
+                validator.data_section(&section)?;
+                let mut reencoder = PrimalReencoder {
+                    width,
+                    global_indices: &global_indices,
+                };
+                for segment in section {
+                    let Data {
+                        kind, data: bytes, ..
+                    } = segment?;
+                    // Only the primal memories get initialized; their tangents start out zeroed.
+                    match kind {
+                        DataKind::Passive => data.passive(bytes.iter().copied()),
+                        DataKind::Active {
+                            memory_index,
+                            offset_expr,
+                        } => data.active(
+                            reencoder.memory_index(memory_index),
+                            &reencoder.const_expr(offset_expr)?,
+                            bytes.iter().copied(),
+                        ),
+                    };
+                }
+            }
+            Payload::CustomSection(section) => match section.as_known() {
+                #[cfg(feature = "names")]
+                KnownCustom::Name(reader) => names = Some(reader),
+                // The indices in the name section refer to the input module, so it can't be copied.
+                #[cfg(not(feature = "names"))]
+                KnownCustom::Name(_) => {}
+                _ => customs.push(CustomSection {
+                    name: section.name().into(),
+                    data: section.data().into(),
+                }),
+            },
+
             other => validator.payload(&other)?,
         }
     }
+    if let Some(seed) = config.seed {
+        let num_types: u32 = type_sigs.iter().len().try_into().unwrap();
+        for funcidx in wrapped {
+            let typeidx = func_types[u32_to_usize(funcidx)];
+            functions.function(num_types + 2 + typeidx);
+            code.function(&seeded_wrapper(
+                type_sigs.params(typeidx),
+                seed,
+                tangents,
+                funcidx,
+            ));
+        }
+    }
     let mut module = Module::new();
     module.section(&types);
+    module.section(&imports);
     module.section(&functions);
+    module.section(&memories);
+    module.section(&globals);
     module.section(&exports);
+    if let Some(start) = &start {
+        module.section(start);
+    }
+    if let Some(data_count) = &data_count {
+        module.section(data_count);
+    }
     module.section(&code);
+    module.section(&data);
+
+    #[cfg(feature = "names")]
+    if config.names {
+        let info = ModuleInfo {
+            tangents,
+            num_func_imports: num_func_imports.try_into().unwrap(),
+            num_types: type_sigs.iter().len().try_into().unwrap(),
+            global_types: &global_types,
+            global_indices: &global_indices,
+            functions: &func_infos,
+        };
+        module.section(&crate::name::forward_name_section(info, names)?);
+    }
+    for custom in &customs {
+        module.section(custom);
+    }
+
     Ok(module.finish())
 }
 
-/// Duplicate all floating-point types.
-fn tuple(val_types: &[ValType]) -> Vec<wasm_encoder::ValType> {
+// When the `names` feature is disabled, this gets marked as dead code.
+#[allow(dead_code)]
+pub struct ModuleInfo<'a> {
+    /// Number of tangents of each float.
+    pub tangents: u32,
+
+    /// Number of imported functions.
+    pub num_func_imports: u32,
+
+    /// Number of types in the input module, not counting the lane types added at the end.
+    pub num_types: u32,
+
+    /// Types of all the globals in the input module.
+    pub global_types: &'a [ValType],
+
+    /// Index in the output module of each global from the input module.
+    pub global_indices: &'a [u32],
+
+    /// Information about each function defined in the input module.
+    pub functions: &'a [FunctionInfo],
+}
+
+// When the `names` feature is disabled, this gets marked as dead code.
+#[allow(dead_code)]
+pub struct FunctionInfo {
+    /// Types of the locals of the input function, including parameters.
+    pub local_types: Vec<ValType>,
+
+    /// Index in the output function of each local from the input function.
+    pub local_indices: Vec<u32>,
+
+    /// Index of the first of the temporary locals at the end of the output function.
+    pub tmp: u32,
+
+    /// Number of temporary locals of each float type.
+    pub num_tmp: u32,
+}
+
+/// Look up the configured import for the tangent of an imported memory or global.
+fn import_tangent<'a>(
+    config: &'a Autodiff,
+    tangents: u32,
+    module: &str,
+    name: &str,
+) -> crate::Result<(&'a str, &'a str)> {
+    // Each import is configured with a single counterpart, so there is nowhere to get more.
+    if tangents != 1 {
+        return Err(ErrorImpl::Transform(
+            "batch forward mode cannot import memories or float globals",
+        ));
+    }
+    let (module_tan, name_tan) =
+        config
+            .imports
+            .get(&TwoStrs(module, name))
+            .ok_or_else(|| ErrorImpl::TangentImport {
+                module: module.to_string(),
+                name: name.to_string(),
+            })?;
+    Ok((module_tan, name_tan))
+}
+
+/// Check that `seed` is the index of a float in `params`.
+fn check_seed(params: &[ValType], seed: u32) -> crate::Result<()> {
+    match params.get(u32_to_usize(seed)) {
+        None => Err(ErrorImpl::Transform("seed index is out of range")),
+        Some(ty) if !ty.is_float() => Err(ErrorImpl::Transform("seed parameter is not a float")),
+        Some(_) => Ok(()),
+    }
+}
+
+/// A function that takes only the primal `params`, and calls the function at `funcidx` with them
+/// and their tangents, all of which are zero except that the first tangent of the parameter at
+/// index `seed` is one.
+fn seeded_wrapper(params: &[ValType], seed: u32, tangents: u32, funcidx: u32) -> Function {
+    let mut body = Function::new([]);
+    let mut insn = body.instructions();
+    for (i, &ty) in (0..).zip(params) {
+        insn.local_get(i);
+        for lane in 0..tangents {
+            // Only the first tangent gets seeded; the rest are zero.
+            let tangent: f32 = if i == seed && lane == 0 { 1. } else { 0. };
+            match ty {
+                ValType::I32 | ValType::I64 => break,
+                ValType::F32 => insn.f32_const(tangent),
+                ValType::F64 => insn.f64_const(tangent.into()),
+            };
+        }
+    }
+    insn.call(funcidx).end();
+    body
+}
+
+/// Reencodes instructions that don't touch floats, and the offsets of data segments, so each memory
+/// index refers to the primal memory instead of one of its tangents.
+struct PrimalReencoder<'a> {
+    width: u32,
+    global_indices: &'a [u32],
+}
+
+impl Reencode for PrimalReencoder<'_> {
+    type Error = Infallible;
+
+    fn global_index(&mut self, global: u32) -> u32 {
+        self.global_indices[u32_to_usize(global)]
+    }
+
+    fn memory_index(&mut self, memory: u32) -> u32 {
+        self.width * memory
+    }
+}
+
+/// Repeat every floating-point type to make room for all its lanes.
+fn lanes(val_types: &[ValType], width: u32) -> Vec<wasm_encoder::ValType> {
     let mut types = Vec::new();
     for &ty in val_types {
-        match ty {
-            ValType::I32 | ValType::I64 => types.push(ty.into()),
-            ValType::F32 | ValType::F64 => {
-                let reencoded = ty.into();
-                types.push(reencoded);
-                types.push(reencoded);
-            }
+        let count = if ty.is_float() { width } else { 1 };
+        for _ in 0..count {
+            types.push(ty.into());
         }
     }
     types
 }
 
+/// Indices of the temporary locals for one float type.
+#[derive(Clone, Copy)]
+struct Tmp {
+    x: u32,
+    y: u32,
+    z: u32,
+
+    /// The first of the tangents of `x`, which are consecutive.
+    dx: u32,
+
+    /// The first of the tangents of `y`, which are consecutive.
+    dy: u32,
+}
+
+impl Tmp {
+    fn new(first: u32, tangents: u32) -> Self {
+        Self {
+            x: first,
+            y: first + 1,
+            z: first + 2,
+            dx: first + 3,
+            dy: first + 3 + tangents,
+        }
+    }
+
+    /// Number of temporary locals for each float type, or `None` if all of them together with the
+    /// one for an `i32` wouldn't fit in a `u32`.
+    fn count(tangents: u32) -> Option<u32> {
+        let count = tangents.checked_mul(2)?.checked_add(3)?;
+        count.checked_mul(2)?.checked_add(1)?;
+        Some(count)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn function(
     mut validator: impl FunctionValidator,
     type_sigs: &FuncTypes,
-    typeidx: u32,
+    func_types: &[u32],
+    global_types: &[ValType],
+    global_indices: &[u32],
+    tangents: u32,
+    funcidx: u32,
     body: FunctionBody,
-) -> crate::Result<Function> {
+) -> crate::Result<(FunctionInfo, Function)> {
+    let width = 1 + tangents;
+    let typeidx = func_types[u32_to_usize(funcidx)];
+    let params = type_sigs.params(typeidx);
+    let too_many_locals = || ErrorImpl::Transform("too many locals");
+    let mut locals = Vec::new();
     let mut local_indices = Vec::new();
-    let mut local_index = 0;
-    for ty in type_sigs.params(typeidx) {
-        match ty {
-            ValType::I32 | ValType::I64 => {
-                local_indices.push(local_index);
-                local_index += 1;
-            }
-            ValType::F32 | ValType::F64 => {
-                local_indices.push(local_index);
-                local_index += 2;
-            }
+    let mut local_index: u32 = 0;
+    for &ty in params {
+        let count = if ty.is_float() { width } else { 1 };
+        local_indices.push(local_index);
+        local_index = local_index.checked_add(count).ok_or_else(too_many_locals)?;
+    }
+    let mut local_types = params.to_vec();
+    let mut locals_reader = body.get_locals_reader()?;
+    for _ in 0..locals_reader.get_count() {
+        let offset = locals_reader.original_position();
+        let (count, ty) = locals_reader.read()?;
+        validator.define_locals(offset, count, ty)?;
+        let ty = ValType::try_from(ty)?;
+        let lanes = if ty.is_float() { width } else { 1 };
+        for _ in 0..count {
+            local_types.push(ty);
+            local_indices.push(local_index);
+            local_index = local_index.checked_add(lanes).ok_or_else(too_many_locals)?;
         }
+        locals.push((lanes * count, ty.into()));
     }
-    assert_eq!(body.get_locals_reader()?.get_count(), 0); // TODO: Handle locals.
+    let num_tmp = Tmp::count(tangents).unwrap();
+    local_index
+        .checked_add(2 * num_tmp + 1)
+        .ok_or_else(too_many_locals)?;
+    locals.push((num_tmp, wasm_encoder::ValType::F64));
+    locals.push((num_tmp, wasm_encoder::ValType::F32));
+    locals.push((1, wasm_encoder::ValType::I32));
+    let body_fwd = Function::new(locals);
     let mut func = Func {
-        local_types: type_sigs.params(typeidx).to_vec(),
+        type_sigs,
+        func_types,
+        num_types: type_sigs.iter().len().try_into().unwrap(),
+        tangents,
+        funcidx,
+        offset: 0, // This initial value should be unused; to be set before each instruction.
+        operand_stack: Vec::new(),
+        control_stack: vec![(BlockType::Func(typeidx), 0, false)],
+        local_types,
         local_indices,
-        tmp_f64: (
-            local_index,
-            local_index + 1,
-            local_index + 2,
-            local_index + 3,
-        ),
-        body: Function::new([(4, wasm_encoder::ValType::F64)]),
+        global_types,
+        global_indices,
+        tmp_f64: Tmp::new(local_index, tangents),
+        tmp_f32: Tmp::new(local_index + num_tmp, tangents),
+        tmp_i32: local_index + 2 * num_tmp,
+        body: body_fwd,
     };
     let mut operators_reader = body.get_operators_reader()?;
     while !operators_reader.eof() {
         let (op, offset) = operators_reader.read_with_offset()?;
         validator.op(offset, &op)?;
+        func.offset = offset.try_into().unwrap();
         func.op(op)?;
     }
     validator.finish(operators_reader.original_position())?;
-    Ok(func.body)
+    let info = FunctionInfo {
+        local_types: func.local_types,
+        local_indices: func.local_indices,
+        tmp: local_index,
+        num_tmp,
+    };
+    Ok((info, func.body))
 }
 
-struct Func {
+struct Func<'a> {
+    type_sigs: &'a FuncTypes,
+    func_types: &'a [u32],
+    num_types: u32,
+
+    /// Number of tangents of each float.
+    tangents: u32,
+
+    /// Index of this function in the input module.
+    funcidx: u32,
+
+    /// Byte offset of the current instruction in the input module.
+    offset: u32,
+
+    /// The types of the values on the operand stack of the input function, not counting tangents.
+    operand_stack: Vec<ValType>,
+
+    /// The block type of each enclosing block, the operand stack height at its start, and whether
+    /// the rest of it is unreachable because of an unconditional branch.
+    control_stack: Vec<(BlockType, usize, bool)>,
+
     local_types: Vec<ValType>,
     local_indices: Vec<u32>,
-    tmp_f64: (u32, u32, u32, u32),
+    global_types: &'a [ValType],
+    global_indices: &'a [u32],
+    tmp_f64: Tmp,
+    tmp_f32: Tmp,
+    tmp_i32: u32,
     body: Function,
 }
 
-impl Func {
+impl Func<'_> {
     fn op(&mut self, op: Operator) -> crate::Result<()> {
+        // Code after an unconditional branch never runs, and the operand stack of the input function
+        // is polymorphic there, so we skip everything but the block structure until the block ends.
+        if !self.reachable() {
+            match op {
+                Operator::Block { .. }
+                | Operator::Loop { .. }
+                | Operator::If { .. }
+                | Operator::Else
+                | Operator::End => {}
+                _ => return Ok(()),
+            }
+        }
         match op {
             Operator::End => {
+                let (block_type, height, _) = self.control_stack.pop().unwrap();
+                self.operand_stack.truncate(height);
+                self.push_results(block_type);
                 self.instructions().end();
             }
+            Operator::Block { blockty } => {
+                let block_type = BlockType::try_from(blockty)?;
+                let reencoded = self.enter(block_type);
+                self.instructions().block(reencoded);
+            }
+            Operator::Loop { blockty } => {
+                let block_type = BlockType::try_from(blockty)?;
+                let reencoded = self.enter(block_type);
+                self.instructions().loop_(reencoded);
+            }
+            Operator::If { blockty } => {
+                if self.reachable() {
+                    self.pop();
+                }
+                let block_type = BlockType::try_from(blockty)?;
+                let reencoded = self.enter(block_type);
+                self.instructions().if_(reencoded);
+            }
+            Operator::Else => {
+                // The `else` branch is reachable unless the whole `if` is.
+                let n = self.control_stack.len();
+                let (_, _, unreachable) = self.control_stack[n - 2];
+                let (block_type, height, frame_unreachable) = &mut self.control_stack[n - 1];
+                *frame_unreachable = unreachable;
+                let (block_type, height) = (*block_type, *height);
+                self.operand_stack.truncate(height);
+                self.push_params(block_type);
+                self.instructions().else_();
+            }
+            // All the lanes of every float are already on the operand stack, so branches carry them
+            // without any changes once the block types have been widened.
+            Operator::Br { relative_depth } => {
+                self.unreachable();
+                self.instructions().br(relative_depth);
+            }
+            Operator::BrIf { relative_depth } => {
+                self.pop();
+                self.instructions().br_if(relative_depth);
+            }
+            Operator::BrTable { targets } => {
+                self.pop();
+                self.unreachable();
+                let labels = targets.targets().collect::<Result<Vec<_>, _>>()?;
+                self.instructions().br_table(labels, targets.default());
+            }
+            Operator::Unreachable => {
+                self.unreachable();
+                self.instructions().unreachable();
+            }
+            Operator::Nop => {
+                self.instructions().nop();
+            }
+            Operator::Return => {
+                // The forward-mode result type already widens every float result, so the lanes on
+                // the operand stack match it as long as the input function was valid.
+                let &(block_type, _, _) = self.control_stack.first().unwrap();
+                if let BlockType::Func(typeidx) = block_type {
+                    debug_assert!(self
+                        .operand_stack
+                        .ends_with(self.type_sigs.results(typeidx)));
+                }
+                self.unreachable();
+                self.instructions().return_();
+            }
+            Operator::Call { function_index } => {
+                let typeidx = self.func_types[u32_to_usize(function_index)];
+                for _ in self.type_sigs.params(typeidx) {
+                    self.pop();
+                }
+                self.push_results(BlockType::Func(typeidx));
+                // Function indices are unchanged, and the callee already takes and returns lanes.
+                self.instructions().call(function_index);
+            }
+            Operator::Drop => {
+                let ty = self.pop();
+                for _ in 0..self.lanes(ty) {
+                    self.instructions().drop();
+                }
+            }
+            Operator::Select => {
+                self.pop();
+                self.pop();
+                let ty = self.pop();
+                self.push(ty);
+                self.select(ty);
+            }
+            Operator::TypedSelect { ty } => {
+                self.pop();
+                self.pop();
+                self.pop();
+                let ty = ValType::try_from(ty)?;
+                self.push(ty);
+                self.select(ty);
+            }
             Operator::LocalGet { local_index } => {
+                let ty = self.local_type(local_index);
+                self.push(ty);
+                let i = self.local_index(local_index);
+                for lane in 0..self.lanes(ty) {
+                    self.instructions().local_get(i + lane);
+                }
+            }
+            Operator::LocalSet { local_index } => {
+                self.pop();
+                let ty = self.local_type(local_index);
+                let i = self.local_index(local_index);
+                for lane in (0..self.lanes(ty)).rev() {
+                    self.instructions().local_set(i + lane);
+                }
+            }
+            Operator::LocalTee { local_index } => {
+                let ty = self.local_type(local_index);
                 let i = self.local_index(local_index);
-                self.instructions().local_get(i);
-                if self.local_type(local_index).is_float() {
-                    self.instructions().local_get(i + 1);
-                }
-            }
-            Operator::F64Mul => {
-                let (x, dx, y, dy) = self.tmp_f64;
-                self.instructions()
-                    .local_set(dy)
-                    .local_set(y)
-                    .local_set(dx)
-                    .local_tee(x)
-                    .local_get(y)
-                    .f64_mul()
-                    .local_get(dx)
-                    .local_get(y)
-                    .f64_mul()
-                    .local_get(x)
-                    .local_get(dy)
-                    .f64_mul()
-                    .f64_add();
-            }
-            _ => unimplemented!("{op:?}"),
+                let lanes = self.lanes(ty);
+                for lane in (1..lanes).rev() {
+                    self.instructions().local_set(i + lane);
+                }
+                self.instructions().local_tee(i);
+                for lane in 1..lanes {
+                    self.instructions().local_get(i + lane);
+                }
+            }
+            Operator::GlobalGet { global_index } => {
+                let ty = self.global_type(global_index);
+                self.push(ty);
+                let i = self.global_index(global_index);
+                for lane in 0..self.lanes(ty) {
+                    self.instructions().global_get(i + lane);
+                }
+            }
+            Operator::GlobalSet { global_index } => {
+                self.pop();
+                let ty = self.global_type(global_index);
+                let i = self.global_index(global_index);
+                for lane in (0..self.lanes(ty)).rev() {
+                    self.instructions().global_set(i + lane);
+                }
+            }
+            Operator::I32Const { .. } => self.copy(&op, 0, Some(ValType::I32))?,
+            Operator::I64Const { .. } => self.copy(&op, 0, Some(ValType::I64))?,
+            // Constants have zero tangents.
+            Operator::F32Const { .. } => {
+                self.copy(&op, 0, Some(ValType::F32))?;
+                self.zeros(ValType::F32);
+            }
+            Operator::F64Const { .. } => {
+                self.copy(&op, 0, Some(ValType::F64))?;
+                self.zeros(ValType::F64);
+            }
+            Operator::I32Eqz
+            | Operator::I32Clz
+            | Operator::I32Ctz
+            | Operator::I32Popcnt
+            | Operator::I32Extend8S
+            | Operator::I32Extend16S
+            | Operator::I64Eqz
+            | Operator::I32WrapI64
+            | Operator::I32Load { .. }
+            | Operator::I32Load8S { .. }
+            | Operator::I32Load8U { .. }
+            | Operator::I32Load16S { .. }
+            | Operator::I32Load16U { .. } => self.copy(&op, 1, Some(ValType::I32))?,
+            Operator::I32Eq
+            | Operator::I32Ne
+            | Operator::I32LtS
+            | Operator::I32LtU
+            | Operator::I32GtS
+            | Operator::I32GtU
+            | Operator::I32LeS
+            | Operator::I32LeU
+            | Operator::I32GeS
+            | Operator::I32GeU
+            | Operator::I32Add
+            | Operator::I32Sub
+            | Operator::I32Mul
+            | Operator::I32DivS
+            | Operator::I32DivU
+            | Operator::I32RemS
+            | Operator::I32RemU
+            | Operator::I32And
+            | Operator::I32Or
+            | Operator::I32Xor
+            | Operator::I32Shl
+            | Operator::I32ShrS
+            | Operator::I32ShrU
+            | Operator::I32Rotl
+            | Operator::I32Rotr
+            | Operator::I64Eq
+            | Operator::I64Ne
+            | Operator::I64LtS
+            | Operator::I64LtU
+            | Operator::I64GtS
+            | Operator::I64GtU
+            | Operator::I64LeS
+            | Operator::I64LeU
+            | Operator::I64GeS
+            | Operator::I64GeU => self.copy(&op, 2, Some(ValType::I32))?,
+            Operator::I64Clz
+            | Operator::I64Ctz
+            | Operator::I64Popcnt
+            | Operator::I64Extend8S
+            | Operator::I64Extend16S
+            | Operator::I64Extend32S
+            | Operator::I64ExtendI32S
+            | Operator::I64ExtendI32U
+            | Operator::I64Load { .. }
+            | Operator::I64Load8S { .. }
+            | Operator::I64Load8U { .. }
+            | Operator::I64Load16S { .. }
+            | Operator::I64Load16U { .. }
+            | Operator::I64Load32S { .. }
+            | Operator::I64Load32U { .. } => self.copy(&op, 1, Some(ValType::I64))?,
+            Operator::I64Add
+            | Operator::I64Sub
+            | Operator::I64Mul
+            | Operator::I64DivS
+            | Operator::I64DivU
+            | Operator::I64RemS
+            | Operator::I64RemU
+            | Operator::I64And
+            | Operator::I64Or
+            | Operator::I64Xor
+            | Operator::I64Shl
+            | Operator::I64ShrS
+            | Operator::I64ShrU
+            | Operator::I64Rotl
+            | Operator::I64Rotr => self.copy(&op, 2, Some(ValType::I64))?,
+            Operator::I32Store { .. }
+            | Operator::I32Store8 { .. }
+            | Operator::I32Store16 { .. }
+            | Operator::I64Store { .. }
+            | Operator::I64Store8 { .. }
+            | Operator::I64Store16 { .. }
+            | Operator::I64Store32 { .. } => self.copy(&op, 2, None)?,
+            Operator::MemorySize { .. } => self.copy(&op, 0, Some(ValType::I32))?,
+            Operator::MemoryGrow { mem } => {
+                // Keep the tangent memories the same size as the primal memory.
+                let i = self.tmp_i32;
+                self.instructions().local_tee(i);
+                self.copy(&op, 1, Some(ValType::I32))?;
+                for lane in 1..=self.tangents {
+                    let memidx = (1 + self.tangents) * mem + lane;
+                    self.instructions().local_get(i).memory_grow(memidx).drop();
+                }
+            }
+            Operator::F32Load { memarg } | Operator::F64Load { memarg } => {
+                self.pop();
+                let ty = match op {
+                    Operator::F32Load { .. } => ValType::F32,
+                    _ => ValType::F64,
+                };
+                self.push(ty);
+                let i = self.tmp_i32;
+                self.instructions().local_set(i);
+                for lane in 0..=self.tangents {
+                    let memarg = self.memarg(memarg, lane);
+                    self.instructions().local_get(i);
+                    match ty {
+                        ValType::F32 => self.instructions().f32_load(memarg),
+                        _ => self.instructions().f64_load(memarg),
+                    };
+                }
+            }
+            Operator::F32Store { memarg } | Operator::F64Store { memarg } => {
+                self.pop2();
+                let ty = match op {
+                    Operator::F32Store { .. } => ValType::F32,
+                    _ => ValType::F64,
+                };
+                let tmp = self.unpack1(ty);
+                let i = self.tmp_i32;
+                self.instructions().local_set(i);
+                for lane in 0..=self.tangents {
+                    let memarg = self.memarg(memarg, lane);
+                    self.instructions().local_get(i);
+                    self.lane(tmp.x, tmp.dx, lane);
+                    match ty {
+                        ValType::F32 => self.instructions().f32_store(memarg),
+                        _ => self.instructions().f64_store(memarg),
+                    };
+                }
+            }
+            // Comparisons of floats only need the primals.
+            Operator::F32Eq
+            | Operator::F32Ne
+            | Operator::F32Lt
+            | Operator::F32Gt
+            | Operator::F32Le
+            | Operator::F32Ge
+            | Operator::F64Eq
+            | Operator::F64Ne
+            | Operator::F64Lt
+            | Operator::F64Gt
+            | Operator::F64Le
+            | Operator::F64Ge => {
+                let tmp = self.unpack2(self.float_type(&op));
+                self.instructions().local_get(tmp.x).local_get(tmp.y);
+                self.copy(&op, 2, Some(ValType::I32))?;
+            }
+            Operator::F32Neg | Operator::F64Neg => {
+                let ty = self.float_type(&op);
+                let tmp = self.unary(&op, ty, ty)?;
+                for lane in 1..=self.tangents {
+                    self.instructions().local_get(tmp.dx + lane - 1);
+                    self.arith(ty, Arith::Neg);
+                }
+            }
+            Operator::F32Sqrt | Operator::F64Sqrt => {
+                let ty = self.float_type(&op);
+                let tmp = self.unary(&op, ty, ty)?;
+                self.instructions().local_tee(tmp.z);
+                for lane in 1..=self.tangents {
+                    self.instructions()
+                        .local_get(tmp.dx + lane - 1)
+                        .local_get(tmp.z);
+                    self.constant(ty, 2.);
+                    self.arith(ty, Arith::Mul);
+                    self.arith(ty, Arith::Div);
+                }
+            }
+            Operator::F32Abs | Operator::F64Abs => {
+                // Multiply each tangent by the sign of the primal; `copysign(dx, x)` alone would be
+                // wrong whenever `dx` is negative.
+                let ty = self.float_type(&op);
+                let tmp = self.unary(&op, ty, ty)?;
+                for lane in 1..=self.tangents {
+                    self.instructions().local_get(tmp.dx + lane - 1);
+                    self.sign(ty, tmp.x);
+                    self.arith(ty, Arith::Mul);
+                }
+            }
+            Operator::F64PromoteF32 => {
+                let tmp = self.unary(&op, ValType::F32, ValType::F64)?;
+                for lane in 1..=self.tangents {
+                    self.instructions()
+                        .local_get(tmp.dx + lane - 1)
+                        .f64_promote_f32();
+                }
+            }
+            Operator::F32DemoteF64 => {
+                let tmp = self.unary(&op, ValType::F64, ValType::F32)?;
+                for lane in 1..=self.tangents {
+                    self.instructions()
+                        .local_get(tmp.dx + lane - 1)
+                        .f32_demote_f64();
+                }
+            }
+            Operator::F32Add | Operator::F64Add | Operator::F32Sub | Operator::F64Sub => {
+                let ty = self.float_type(&op);
+                let arith = match op {
+                    Operator::F32Add | Operator::F64Add => Arith::Add,
+                    _ => Arith::Sub,
+                };
+                let tmp = self.binary(&op, ty)?;
+                for lane in 1..=self.tangents {
+                    self.instructions()
+                        .local_get(tmp.dx + lane - 1)
+                        .local_get(tmp.dy + lane - 1);
+                    self.arith(ty, arith);
+                }
+            }
+            Operator::F32Mul | Operator::F64Mul => {
+                let ty = self.float_type(&op);
+                let tmp = self.binary(&op, ty)?;
+                for lane in 1..=self.tangents {
+                    self.instructions()
+                        .local_get(tmp.dx + lane - 1)
+                        .local_get(tmp.y);
+                    self.arith(ty, Arith::Mul);
+                    self.instructions()
+                        .local_get(tmp.x)
+                        .local_get(tmp.dy + lane - 1);
+                    self.arith(ty, Arith::Mul);
+                    self.arith(ty, Arith::Add);
+                }
+            }
+            Operator::F32Div | Operator::F64Div => {
+                let ty = self.float_type(&op);
+                // Use the quotient `z` to compute each tangent as `(dx - z * dy) / y`.
+                let tmp = self.binary(&op, ty)?;
+                self.instructions().local_tee(tmp.z);
+                for lane in 1..=self.tangents {
+                    self.instructions()
+                        .local_get(tmp.dx + lane - 1)
+                        .local_get(tmp.z)
+                        .local_get(tmp.dy + lane - 1);
+                    self.arith(ty, Arith::Mul);
+                    self.arith(ty, Arith::Sub);
+                    self.instructions().local_get(tmp.y);
+                    self.arith(ty, Arith::Div);
+                }
+            }
+            Operator::F32Min | Operator::F64Min | Operator::F32Max | Operator::F64Max => {
+                let ty = self.float_type(&op);
+                // Take the tangents of whichever operand was chosen, without needing a tape.
+                let arith = match op {
+                    Operator::F32Min | Operator::F64Min => Arith::Le,
+                    _ => Arith::Ge,
+                };
+                let tmp = self.binary(&op, ty)?;
+                for lane in 1..=self.tangents {
+                    self.instructions()
+                        .local_get(tmp.dx + lane - 1)
+                        .local_get(tmp.dy + lane - 1)
+                        .local_get(tmp.x)
+                        .local_get(tmp.y);
+                    self.arith(ty, arith);
+                    self.instructions().select();
+                }
+            }
+            Operator::F32Copysign | Operator::F64Copysign => {
+                let ty = self.float_type(&op);
+                // The sign of `y` doesn't depend continuously on `y`, so only the tangents of `x`
+                // matter, and they get flipped whenever the sign of `x` does.
+                let tmp = self.binary(&op, ty)?;
+                for lane in 1..=self.tangents {
+                    self.instructions().local_get(tmp.dx + lane - 1);
+                    self.sign(ty, tmp.x);
+                    self.arith(ty, Arith::Mul);
+                    self.sign(ty, tmp.y);
+                    self.arith(ty, Arith::Mul);
+                }
+            }
+            // Rounding is piecewise constant, so the tangents are zero.
+            Operator::F32Ceil
+            | Operator::F32Floor
+            | Operator::F32Trunc
+            | Operator::F32Nearest
+            | Operator::F64Ceil
+            | Operator::F64Floor
+            | Operator::F64Trunc
+            | Operator::F64Nearest => {
+                let ty = self.float_type(&op);
+                self.drop_tangents();
+                self.copy(&op, 1, Some(ty))?;
+                self.zeros(ty);
+            }
+            // Floats made from integers have zero tangents, including reinterpreted bits.
+            Operator::F32ConvertI32S
+            | Operator::F32ConvertI32U
+            | Operator::F32ConvertI64S
+            | Operator::F32ConvertI64U
+            | Operator::F32ReinterpretI32 => {
+                self.copy(&op, 1, Some(ValType::F32))?;
+                self.zeros(ValType::F32);
+            }
+            Operator::F64ConvertI32S
+            | Operator::F64ConvertI32U
+            | Operator::F64ConvertI64S
+            | Operator::F64ConvertI64U
+            | Operator::F64ReinterpretI64 => {
+                self.copy(&op, 1, Some(ValType::F64))?;
+                self.zeros(ValType::F64);
+            }
+            // Integers made from floats just drop the tangents.
+            Operator::I32TruncF32S
+            | Operator::I32TruncF32U
+            | Operator::I32TruncSatF32S
+            | Operator::I32TruncSatF32U
+            | Operator::I32TruncF64S
+            | Operator::I32TruncF64U
+            | Operator::I32TruncSatF64S
+            | Operator::I32TruncSatF64U
+            | Operator::I32ReinterpretF32 => {
+                self.drop_tangents();
+                self.copy(&op, 1, Some(ValType::I32))?;
+            }
+            Operator::I64TruncF32S
+            | Operator::I64TruncF32U
+            | Operator::I64TruncF64S
+            | Operator::I64TruncF64U
+            | Operator::I64TruncSatF32S
+            | Operator::I64TruncSatF32U
+            | Operator::I64TruncSatF64S
+            | Operator::I64TruncSatF64U
+            | Operator::I64ReinterpretF64 => {
+                self.drop_tangents();
+                self.copy(&op, 1, Some(ValType::I64))?;
+            }
+            Operator::AtomicFence => {
+                self.instructions().atomic_fence();
+            }
+            _ => return Err(self.unsupported(&op)),
+        }
+        Ok(())
+    }
+
+    fn unsupported(&self, op: &Operator) -> ErrorImpl {
+        ErrorImpl::Unsupported {
+            function_index: self.funcidx,
+            byte_offset: self.offset,
+            instruction: operator_name(op),
+        }
+    }
+
+    fn push(&mut self, ty: ValType) {
+        self.operand_stack.push(ty);
+    }
+
+    fn pop(&mut self) -> ValType {
+        self.operand_stack.pop().unwrap()
+    }
+
+    fn pop2(&mut self) {
+        self.pop();
+        self.pop();
+    }
+
+    /// Push the parameters of a block type onto the operand stack.
+    fn push_params(&mut self, block_type: BlockType) {
+        if let BlockType::Func(typeidx) = block_type {
+            let type_sigs = self.type_sigs;
+            self.operand_stack
+                .extend_from_slice(type_sigs.params(typeidx));
+        }
+    }
+
+    /// Push the results of a block type onto the operand stack.
+    fn push_results(&mut self, block_type: BlockType) {
+        match block_type {
+            BlockType::Empty => {}
+            BlockType::Result(ty) => self.push(ty),
+            BlockType::Func(typeidx) => {
+                let type_sigs = self.type_sigs;
+                self.operand_stack
+                    .extend_from_slice(type_sigs.results(typeidx));
+            }
+        }
+    }
+
+    /// Start a block, moving its parameters from the outer operand stack into the block, and return
+    /// its block type in the output function.
+    fn enter(&mut self, block_type: BlockType) -> wasm_encoder::BlockType {
+        if !self.reachable() {
+            // A block in dead code can take nothing and return nothing, since none of it runs.
+            self.control_stack
+                .push((BlockType::Empty, self.operand_stack.len(), true));
+            return wasm_encoder::BlockType::Empty;
+        }
+        if let BlockType::Func(typeidx) = block_type {
+            for _ in self.type_sigs.params(typeidx) {
+                self.pop();
+            }
+        }
+        self.control_stack
+            .push((block_type, self.operand_stack.len(), false));
+        self.push_params(block_type);
+        self.blockty(block_type)
+    }
+
+    /// Discard the operand stack of the current block, after an unconditional branch.
+    fn unreachable(&mut self) {
+        let (_, height, unreachable) = self.control_stack.last_mut().unwrap();
+        *unreachable = true;
+        let height = *height;
+        self.operand_stack.truncate(height);
+    }
+
+    /// Whether the current instruction can run, because no unconditional branch precedes it in its
+    /// block.
+    fn reachable(&self) -> bool {
+        let &(_, _, unreachable) = self.control_stack.last().unwrap();
+        !unreachable
+    }
+
+    /// Number of lanes taken by a value of type `ty`.
+    fn lanes(&self, ty: ValType) -> u32 {
+        if ty.is_float() {
+            1 + self.tangents
+        } else {
+            1
+        }
+    }
+
+    /// Type of the float operands of an instruction.
+    fn float_type(&self, op: &Operator) -> ValType {
+        match op {
+            Operator::F32Eq
+            | Operator::F32Ne
+            | Operator::F32Lt
+            | Operator::F32Gt
+            | Operator::F32Le
+            | Operator::F32Ge
+            | Operator::F32Neg
+            | Operator::F32Sqrt
+            | Operator::F32Abs
+            | Operator::F32Add
+            | Operator::F32Sub
+            | Operator::F32Mul
+            | Operator::F32Div
+            | Operator::F32Min
+            | Operator::F32Max
+            | Operator::F32Copysign
+            | Operator::F32Ceil
+            | Operator::F32Floor
+            | Operator::F32Trunc
+            | Operator::F32Nearest => ValType::F32,
+            _ => ValType::F64,
+        }
+    }
+
+    fn tmp(&self, ty: ValType) -> Tmp {
+        match ty {
+            ValType::F32 => self.tmp_f32,
+            _ => self.tmp_f64,
+        }
+    }
+
+    /// Move every lane of the float of type `ty` on top of the stack into `x` and `dx`.
+    fn unpack1(&mut self, ty: ValType) -> Tmp {
+        let tmp = self.tmp(ty);
+        for lane in (1..=self.tangents).rev() {
+            self.instructions().local_set(tmp.dx + lane - 1);
+        }
+        self.instructions().local_set(tmp.x);
+        tmp
+    }
+
+    /// Move every lane of the two floats of type `ty` on top of the stack into `x`, `dx`, `y`, and
+    /// `dy`.
+    fn unpack2(&mut self, ty: ValType) -> Tmp {
+        let tmp = self.tmp(ty);
+        for lane in (1..=self.tangents).rev() {
+            self.instructions().local_set(tmp.dy + lane - 1);
+        }
+        self.instructions().local_set(tmp.y);
+        self.unpack1(ty)
+    }
+
+    /// Get lane `lane` of the float whose primal is in local `x` and tangents start at `dx`.
+    fn lane(&mut self, x: u32, dx: u32, lane: u32) {
+        let i = if lane == 0 { x } else { dx + lane - 1 };
+        self.instructions().local_get(i);
+    }
+
+    /// Unpack the operand of a unary float instruction from type `from` to type `to`, and compute
+    /// its primal, leaving the tangents to the caller.
+    fn unary(&mut self, op: &Operator, from: ValType, to: ValType) -> crate::Result<Tmp> {
+        let tmp = self.unpack1(from);
+        self.pop();
+        self.push(to);
+        self.instructions().local_get(tmp.x);
+        self.primal(op)?;
+        Ok(tmp)
+    }
+
+    /// Unpack the operands of a binary float instruction of type `ty` and compute its primal,
+    /// leaving the tangents to the caller.
+    fn binary(&mut self, op: &Operator, ty: ValType) -> crate::Result<Tmp> {
+        let tmp = self.unpack2(ty);
+        self.pop2();
+        self.push(ty);
+        self.instructions().local_get(tmp.x).local_get(tmp.y);
+        self.primal(op)?;
+        Ok(tmp)
+    }
+
+    /// Drop the tangents of the float on top of the stack, leaving its primal.
+    fn drop_tangents(&mut self) {
+        for _ in 0..self.tangents {
+            self.instructions().drop();
+        }
+    }
+
+    /// Push a zero tangent for each lane after the primal of a float of type `ty`.
+    fn zeros(&mut self, ty: ValType) {
+        for _ in 0..self.tangents {
+            self.constant(ty, 0.);
         }
+    }
+
+    /// Copy an instruction as-is, without touching the operand stack.
+    fn primal(&mut self, op: &Operator) -> crate::Result<()> {
+        let mut reencoder = PrimalReencoder {
+            width: 1 + self.tangents,
+            global_indices: self.global_indices,
+        };
+        let instruction = reencoder.instruction(op.clone())?;
+        self.body.instruction(&instruction);
         Ok(())
     }
 
+    /// Copy an instruction that doesn't touch any tangents, after popping `arity` operands and then
+    /// pushing its `result` if any.
+    fn copy(&mut self, op: &Operator, arity: usize, result: Option<ValType>) -> crate::Result<()> {
+        for _ in 0..arity {
+            self.pop();
+        }
+        if let Some(ty) = result {
+            self.push(ty);
+        }
+        self.primal(op)
+    }
+
+    /// Push a constant of float type `ty`.
+    fn constant(&mut self, ty: ValType, value: f64) {
+        match ty {
+            ValType::F32 => self.instructions().f32_const(value as f32),
+            _ => self.instructions().f64_const(value),
+        };
+    }
+
+    /// Push one with the same sign as the float of type `ty` in local `x`.
+    fn sign(&mut self, ty: ValType, x: u32) {
+        self.constant(ty, 1.);
+        self.instructions().local_get(x);
+        self.arith(ty, Arith::Copysign);
+    }
+
+    /// Apply an arithmetic instruction to floats of type `ty`.
+    fn arith(&mut self, ty: ValType, arith: Arith) {
+        let instruction = match (ty, arith) {
+            (ValType::F32, Arith::Add) => Instruction::F32Add,
+            (ValType::F32, Arith::Sub) => Instruction::F32Sub,
+            (ValType::F32, Arith::Mul) => Instruction::F32Mul,
+            (ValType::F32, Arith::Div) => Instruction::F32Div,
+            (ValType::F32, Arith::Neg) => Instruction::F32Neg,
+            (ValType::F32, Arith::Copysign) => Instruction::F32Copysign,
+            (ValType::F32, Arith::Le) => Instruction::F32Le,
+            (ValType::F32, Arith::Ge) => Instruction::F32Ge,
+            (_, Arith::Add) => Instruction::F64Add,
+            (_, Arith::Sub) => Instruction::F64Sub,
+            (_, Arith::Mul) => Instruction::F64Mul,
+            (_, Arith::Div) => Instruction::F64Div,
+            (_, Arith::Neg) => Instruction::F64Neg,
+            (_, Arith::Copysign) => Instruction::F64Copysign,
+            (_, Arith::Le) => Instruction::F64Le,
+            (_, Arith::Ge) => Instruction::F64Ge,
+        };
+        self.body.instruction(&instruction);
+    }
+
+    /// Process a `select` instruction whose operands have type `ty`.
+    fn select(&mut self, ty: ValType) {
+        if !ty.is_float() {
+            self.instructions().select();
+            return;
+        }
+        // Use the same condition to choose every lane.
+        let c = self.tmp_i32;
+        self.instructions().local_set(c);
+        let tmp = self.unpack2(ty);
+        for lane in 0..=self.tangents {
+            self.lane(tmp.x, tmp.dx, lane);
+            self.lane(tmp.y, tmp.dy, lane);
+            self.instructions().local_get(c).select();
+        }
+    }
+
+    fn blockty(&self, block_type: BlockType) -> wasm_encoder::BlockType {
+        match block_type {
+            BlockType::Empty => wasm_encoder::BlockType::Empty,
+            BlockType::Result(val_type) => match val_type {
+                ValType::I32 | ValType::I64 => wasm_encoder::BlockType::Result(val_type.into()),
+                ValType::F32 => wasm_encoder::BlockType::FunctionType(self.num_types),
+                ValType::F64 => wasm_encoder::BlockType::FunctionType(self.num_types + 1),
+            },
+            BlockType::Func(typeidx) => wasm_encoder::BlockType::FunctionType(typeidx),
+        }
+    }
+
+    /// Reencode a memory argument to refer to lane `lane` of its memory.
+    fn memarg(&self, memarg: wasmparser::MemArg, lane: u32) -> wasm_encoder::MemArg {
+        let mut reencoded = RoundtripReencoder.mem_arg(memarg);
+        reencoded.memory_index = (1 + self.tangents) * reencoded.memory_index + lane;
+        reencoded
+    }
+
     fn local_type(&self, index: u32) -> ValType {
         self.local_types[u32_to_usize(index)]
     }
@@ -170,16 +1387,93 @@ impl Func {
         self.local_indices[u32_to_usize(index)]
     }
 
+    fn global_type(&self, index: u32) -> ValType {
+        self.global_types[u32_to_usize(index)]
+    }
+
+    fn global_index(&self, index: u32) -> u32 {
+        self.global_indices[u32_to_usize(index)]
+    }
+
     fn instructions(&mut self) -> InstructionSink<'_> {
         self.body.instructions()
     }
 }
 
+/// Float instructions used to compute tangents.
+#[derive(Clone, Copy)]
+enum Arith {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Neg,
+    Copysign,
+    Le,
+    Ge,
+}
+
 #[cfg(test)]
 mod tests {
-    use wasmtime::{Engine, Instance, Module, Store};
+    #[cfg(feature = "names")]
+    use std::io::Write;
+
+    #[cfg(feature = "names")]
+    use goldenfile::Mint;
+    use wasmtime::{
+        Engine, Global, GlobalType, Instance, Linker, Memory, MemoryType, Module, Mutability,
+        Store, TypedFunc, Val, WasmParams, WasmResults,
+    };
+
+    use crate::{Autodiff, ErrorKind};
+
+    fn compile<P: WasmParams, R: WasmResults>(
+        wat: &str,
+        name: &str,
+    ) -> (Store<()>, TypedFunc<P, R>) {
+        let input = wat::parse_str(wat).unwrap();
+        let output = Autodiff::new().forward(&input).unwrap();
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let function = instance.get_typed_func::<P, R>(&mut store, name).unwrap();
+        (store, function)
+    }
 
-    use crate::Autodiff;
+    #[test]
+    #[cfg(feature = "names")]
+    fn test_names() {
+        let input = wat::parse_str(include_str!("wat/names_forward.wat")).unwrap();
+        let mut ad = Autodiff::new();
+        ad.names();
+        let output = wasmprinter::print_bytes(ad.forward(&input).unwrap()).unwrap();
+        let mut mint = Mint::new("src/forward");
+        let mut file = mint.new_goldenfile("names.wat").unwrap();
+        file.write_all(output.as_bytes()).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "names")]
+    fn test_names_seeded() {
+        let input = wat::parse_str(
+            r#"
+(module
+  (func (export "mul") (param $x f64) (param $y f64) (result f64)
+    (f64.mul
+      (local.get $x)
+      (local.get $y))))
+"#,
+        )
+        .unwrap();
+        let mut ad = Autodiff::new();
+        ad.names();
+        let output = wasmprinter::print_bytes(ad.forward_seeded(&input, 0).unwrap()).unwrap();
+        // The exported wrapper seeds the tangents, so the original function keeps its parameters.
+        assert!(output.contains(
+            "(param $x f64) (param $x_tangent f64) (param $y f64) (param $y_tangent f64)"
+        ));
+    }
 
     #[test]
     fn test_square() {
@@ -197,4 +1491,813 @@ mod tests {
 
         assert_eq!(square.call(&mut store, (3., 1.)).unwrap(), (9., 6.));
     }
+
+    #[test]
+    fn test_forward_seeded() {
+        let input = wat::parse_str(include_str!("wat/f64_mul.wat")).unwrap();
+
+        let output = Autodiff::new().forward_seeded(&input, 1).unwrap();
+
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let mul = instance
+            .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, "mul")
+            .unwrap();
+
+        assert_eq!(mul.call(&mut store, (3., 5.)).unwrap(), (15., 3.));
+    }
+
+    #[test]
+    fn test_forward_seeded_out_of_range() {
+        let input = wat::parse_str(include_str!("wat/f64_mul.wat")).unwrap();
+        let err = Autodiff::new().forward_seeded(&input, 2).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Transform);
+        assert_eq!(err.description(), "seed index is out of range");
+    }
+
+    #[test]
+    fn test_forward_seeded_not_float() {
+        let input = wat::parse_str(include_str!("wat/select_f64.wat")).unwrap();
+        let err = Autodiff::new().forward_seeded(&input, 2).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Transform);
+        assert_eq!(err.description(), "seed parameter is not a float");
+    }
+
+    #[test]
+    fn test_forward_seeded_block_type() {
+        let input = wat::parse_str(include_str!("wat/block_type_seeded.wat")).unwrap();
+
+        let output = Autodiff::new().forward_seeded(&input, 0).unwrap();
+
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let square = instance
+            .get_typed_func::<f64, (f64, f64)>(&mut store, "square")
+            .unwrap();
+
+        assert_eq!(square.call(&mut store, 3.).unwrap(), (9., 6.));
+    }
+
+    #[test]
+    fn test_global_set() {
+        let input = wat::parse_str(include_str!("wat/global_set.wat")).unwrap();
+
+        let output = Autodiff::new().forward(&input).unwrap();
+
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let set = instance
+            .get_typed_func::<(i32, f64, f64), ()>(&mut store, "set")
+            .unwrap();
+        let get_n = instance
+            .get_typed_func::<(), i32>(&mut store, "get_n")
+            .unwrap();
+        let get_x = instance
+            .get_typed_func::<(), (f64, f64)>(&mut store, "get_x")
+            .unwrap();
+
+        set.call(&mut store, (1, 3., 2.)).unwrap();
+        assert_eq!(get_n.call(&mut store, ()).unwrap(), 1);
+        assert_eq!(get_x.call(&mut store, ()).unwrap(), (3., 2.));
+    }
+
+    #[test]
+    fn test_start() {
+        let (mut store, scale) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/start.wat"), "scale");
+        assert_eq!(scale.call(&mut store, (2., 1.)).unwrap(), (6., 3.));
+    }
+
+    #[test]
+    fn test_custom_section() {
+        let input = wat::parse_str(include_str!("wat/custom.wat")).unwrap();
+        let output = Autodiff::new().forward(&input).unwrap();
+        let mut customs = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(&output) {
+            if let wasmparser::Payload::CustomSection(section) = payload.unwrap() {
+                customs.push((section.name().to_owned(), section.data().to_vec()));
+            }
+        }
+        // The name section is dropped because names weren't requested.
+        assert_eq!(customs, [("my_section".to_owned(), b"hello".to_vec())]);
+    }
+
+    #[test]
+    fn test_f64_const() {
+        let (mut store, function) =
+            compile::<(), (f64, f64)>(include_str!("wat/f64_const.wat"), "const");
+        assert_eq!(function.call(&mut store, ()).unwrap(), (42., 0.));
+    }
+
+    #[test]
+    fn test_f64_copysign() {
+        let (mut store, function) = compile::<(f64, f64, f64, f64), (f64, f64)>(
+            include_str!("wat/f64_copysign.wat"),
+            "copysign",
+        );
+        assert_eq!(
+            function.call(&mut store, (3., 1., -2., 5.)).unwrap(),
+            (-3., -1.)
+        );
+        assert_eq!(
+            function.call(&mut store, (-3., 1., -2., 5.)).unwrap(),
+            (-3., 1.)
+        );
+    }
+
+    #[test]
+    fn test_i32_store_load() {
+        let (mut store, function) =
+            compile::<i32, (i32, i32, i32)>(include_str!("wat/i32_store_load.wat"), "roundtrip");
+        assert_eq!(
+            function.call(&mut store, 0x180).unwrap(),
+            (0x180, 0x80, -0x80)
+        );
+    }
+
+    #[test]
+    fn test_memory_grow() {
+        let (mut store, function) = compile::<(f64, f64), (i32, i32, f64, f64)>(
+            include_str!("wat/memory_grow.wat"),
+            "grow",
+        );
+        assert_eq!(function.call(&mut store, (3., 1.)).unwrap(), (1, 2, 9., 6.));
+    }
+
+    #[test]
+    fn test_br_table() {
+        let (mut store, function) =
+            compile::<(i32, f64, f64), (f64, f64)>(include_str!("wat/br_table.wat"), "switch");
+        assert_eq!(function.call(&mut store, (0, 2., 1.)).unwrap(), (2., 1.));
+        assert_eq!(function.call(&mut store, (1, 2., 1.)).unwrap(), (8., 12.));
+        assert_eq!(function.call(&mut store, (2, 2., 1.)).unwrap(), (4., 4.));
+    }
+
+    #[test]
+    fn test_atomic_fence() {
+        let input = wat::parse_str(include_str!("wat/atomic_fence.wat")).unwrap();
+
+        let output = Autodiff::new().forward(&input).unwrap();
+
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let square = instance
+            .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, "square")
+            .unwrap();
+
+        assert_eq!(square.call(&mut store, (3., 1.)).unwrap(), (9., 6.));
+    }
+
+    #[test]
+    fn test_local_tee_f64() {
+        let (mut store, double_square) = compile::<(f64, f64, f64, f64), (f64, f64)>(
+            include_str!("wat/local_tee_f64.wat"),
+            "double_square",
+        );
+        assert_eq!(
+            double_square.call(&mut store, (3., 1., 0., 0.)).unwrap(),
+            (18., 12.),
+        );
+    }
+
+    #[test]
+    fn test_f64_local() {
+        let (mut store, cube) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/f64_local.wat"), "cube");
+        assert_eq!(cube.call(&mut store, (3., 1.)).unwrap(), (27., 27.));
+    }
+
+    #[test]
+    fn test_if() {
+        let (mut store, select) =
+            compile::<(i32, f64, f64, f64, f64), (f64, f64)>(include_str!("wat/if.wat"), "select");
+        assert_eq!(
+            select.call(&mut store, (1, 3., 1., 5., 2.)).unwrap(),
+            (3., 1.)
+        );
+        assert_eq!(
+            select.call(&mut store, (0, 3., 1., 5., 2.)).unwrap(),
+            (5., 2.)
+        );
+    }
+
+    #[test]
+    fn test_else() {
+        let (mut store, select) = compile::<(i32, f64, f64, f64, f64), (f64, f64)>(
+            include_str!("wat/else.wat"),
+            "select",
+        );
+        assert_eq!(
+            select.call(&mut store, (1, 3., 1., 5., 2.)).unwrap(),
+            (3., 1.)
+        );
+        assert_eq!(
+            select.call(&mut store, (0, 3., 1., 5., 2.)).unwrap(),
+            (5., 2.)
+        );
+    }
+
+    #[test]
+    fn test_br() {
+        let (mut store, select) =
+            compile::<(i32, f64, f64, f64, f64), (f64, f64)>(include_str!("wat/br.wat"), "select");
+        assert_eq!(
+            select.call(&mut store, (1, 3., 1., 5., 2.)).unwrap(),
+            (3., 1.)
+        );
+        assert_eq!(
+            select.call(&mut store, (0, 3., 1., 5., 2.)).unwrap(),
+            (5., 2.)
+        );
+    }
+
+    #[test]
+    fn test_block_br_if() {
+        let (mut store, maybe_mul) = compile::<(i32, f64, f64, f64, f64), (f64, f64)>(
+            include_str!("wat/block_br_if.wat"),
+            "maybe_mul",
+        );
+        assert_eq!(
+            maybe_mul.call(&mut store, (1, 3., 1., 5., 2.)).unwrap(),
+            (3., 1.),
+        );
+        assert_eq!(
+            maybe_mul.call(&mut store, (0, 3., 1., 5., 2.)).unwrap(),
+            (15., 11.),
+        );
+    }
+
+    #[test]
+    fn test_loop_br_if() {
+        let (mut store, square_twice) = compile::<(f64, f64, i32, i32), (f64, f64)>(
+            include_str!("wat/loop_br_if.wat"),
+            "square_twice",
+        );
+        assert_eq!(
+            square_twice.call(&mut store, (3., 1., 1, 0)).unwrap(),
+            (81., 108.),
+        );
+        assert_eq!(
+            square_twice.call(&mut store, (3., 1., 0, 0)).unwrap(),
+            (9., 6.),
+        );
+    }
+
+    #[test]
+    fn test_call() {
+        let (mut store, fourth) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/call_f64.wat"), "fourth");
+        assert_eq!(fourth.call(&mut store, (2., 1.)).unwrap(), (16., 32.));
+    }
+
+    #[test]
+    fn test_call_seeded() {
+        let input = wat::parse_str(include_str!("wat/call_f64.wat")).unwrap();
+        let output = Autodiff::new().forward_seeded(&input, 0).unwrap();
+
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let fourth = instance
+            .get_typed_func::<f64, (f64, f64)>(&mut store, "fourth")
+            .unwrap();
+
+        assert_eq!(fourth.call(&mut store, 2.).unwrap(), (16., 32.));
+    }
+
+    #[test]
+    fn test_f64_store_load() {
+        let (mut store, roundtrip) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/f64_store_load.wat"), "roundtrip");
+        assert_eq!(roundtrip.call(&mut store, (42., 2.)).unwrap(), (42., 2.));
+    }
+
+    #[test]
+    fn test_import_memory_global() {
+        let input = wat::parse_str(include_str!("wat/import_memory_global.wat")).unwrap();
+        let mut ad = Autodiff::new();
+        ad.import(("env", "memory"), ("env", "memory_tangent"));
+        ad.import(("env", "scale"), ("env", "scale_tangent"));
+        let output = ad.forward(&input).unwrap();
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap("env", "square", |x: f64, dx: f64| (x * x, 2. * x * dx))
+            .unwrap();
+        for name in ["memory", "memory_tangent"] {
+            let memory = Memory::new(&mut store, MemoryType::new(1, None)).unwrap();
+            linker.define(&store, "env", name, memory).unwrap();
+        }
+        for (name, value) in [("scale", 2.), ("scale_tangent", 0.5)] {
+            let ty = GlobalType::new(wasmtime::ValType::F64, Mutability::Const);
+            let global = Global::new(&mut store, ty, Val::F64(f64::to_bits(value))).unwrap();
+            linker.define(&store, "env", name, global).unwrap();
+        }
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let scaled_square = instance
+            .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, "scaled_square")
+            .unwrap();
+        assert_eq!(
+            scaled_square.call(&mut store, (3., 1.)).unwrap(),
+            (18., 16.5),
+        );
+    }
+
+    #[test]
+    fn test_import_tangent_missing() {
+        let input = wat::parse_str(include_str!("wat/import_memory_global.wat")).unwrap();
+        let err = Autodiff::new().forward(&input).unwrap_err();
+        assert_eq!(err.description(), "no tangent import configured");
+        assert_eq!(err.kind(), ErrorKind::Import);
+        assert_eq!(
+            err.to_string(),
+            "no tangent import registered for \"env::memory\"",
+        );
+    }
+
+    #[test]
+    fn test_import_table() {
+        let input = wat::parse_str(r#"(module (import "env" "table" (table 1 funcref)))"#).unwrap();
+        let output = Autodiff::new().forward(&input).unwrap();
+        let module = Module::new(&Engine::default(), &output).unwrap();
+        let import = module.imports().next().unwrap();
+        assert_eq!((import.module(), import.name()), ("env", "table"));
+        assert!(import.ty().table().is_some());
+    }
+
+    #[test]
+    fn test_select_f64() {
+        let (mut store, select) = compile::<(f64, f64, f64, f64, i32), (f64, f64)>(
+            include_str!("wat/select_f64.wat"),
+            "select",
+        );
+        assert_eq!(
+            select.call(&mut store, (3., 1., 5., 2., 1)).unwrap(),
+            (3., 1.)
+        );
+        assert_eq!(
+            select.call(&mut store, (3., 1., 5., 2., 0)).unwrap(),
+            (5., 2.)
+        );
+    }
+
+    #[test]
+    fn test_select_i32() {
+        let (mut store, select) =
+            compile::<(i32, i32, i32), i32>(include_str!("wat/select_i32.wat"), "select");
+        assert_eq!(select.call(&mut store, (3, 5, 1)).unwrap(), 3);
+        assert_eq!(select.call(&mut store, (3, 5, 0)).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_drop_f64() {
+        let (mut store, drop) = compile::<(f64, f64), ()>(include_str!("wat/drop_f64.wat"), "drop");
+        drop.call(&mut store, (3., 1.)).unwrap();
+    }
+
+    #[test]
+    fn test_unreachable_dead_code() {
+        let (mut store, square) = compile::<(f64, f64), (f64, f64)>(
+            include_str!("wat/unreachable_dead_code.wat"),
+            "square",
+        );
+        assert_eq!(square.call(&mut store, (3., 1.)).unwrap(), (9., 6.));
+        assert!(square.call(&mut store, (-1., 1.)).is_err());
+    }
+
+    #[test]
+    fn test_return() {
+        let (mut store, maybe_square) = compile::<(i32, f64, f64), (f64, f64)>(
+            include_str!("wat/return_f64.wat"),
+            "maybe_square",
+        );
+        assert_eq!(
+            maybe_square.call(&mut store, (1, 3., 1.)).unwrap(),
+            (3., 1.),
+        );
+        assert_eq!(
+            maybe_square.call(&mut store, (0, 3., 1.)).unwrap(),
+            (9., 6.),
+        );
+    }
+
+    #[test]
+    fn test_br_dead_code() {
+        let (mut store, abs) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/br_dead_code.wat"), "abs");
+        assert_eq!(abs.call(&mut store, (3., 1.)).unwrap(), (3., 1.));
+        assert_eq!(abs.call(&mut store, (-2., 1.)).unwrap(), (2., -1.));
+    }
+
+    #[test]
+    fn test_f32_min() {
+        let (mut store, min) =
+            compile::<(f32, f32, f32, f32), (f32, f32)>(include_str!("wat/f32_min.wat"), "min");
+        assert_eq!(min.call(&mut store, (3., 1., 2., 5.)).unwrap(), (2., 5.));
+    }
+
+    #[test]
+    fn test_f32_max() {
+        let (mut store, max) =
+            compile::<(f32, f32, f32, f32), (f32, f32)>(include_str!("wat/f32_max.wat"), "max");
+        assert_eq!(max.call(&mut store, (3., 1., 2., 5.)).unwrap(), (3., 1.));
+    }
+
+    #[test]
+    fn test_f64_min() {
+        let (mut store, min) =
+            compile::<(f64, f64, f64, f64), (f64, f64)>(include_str!("wat/f64_min.wat"), "min");
+        assert_eq!(min.call(&mut store, (3., 1., 2., 5.)).unwrap(), (2., 5.));
+    }
+
+    #[test]
+    fn test_f64_max() {
+        let (mut store, max) =
+            compile::<(f64, f64, f64, f64), (f64, f64)>(include_str!("wat/f64_max.wat"), "max");
+        assert_eq!(max.call(&mut store, (3., 1., 2., 5.)).unwrap(), (3., 1.));
+    }
+
+    #[test]
+    fn test_f32_abs() {
+        let (mut store, abs) =
+            compile::<(f32, f32), (f32, f32)>(include_str!("wat/f32_abs.wat"), "abs");
+        assert_eq!(abs.call(&mut store, (3., -2.)).unwrap(), (3., -2.));
+        assert_eq!(abs.call(&mut store, (-3., -2.)).unwrap(), (3., 2.));
+    }
+
+    #[test]
+    fn test_f64_abs() {
+        let (mut store, abs) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/f64_abs.wat"), "abs");
+        assert_eq!(abs.call(&mut store, (3., -2.)).unwrap(), (3., -2.));
+        assert_eq!(abs.call(&mut store, (-3., -2.)).unwrap(), (3., 2.));
+    }
+
+    #[test]
+    fn test_f64_promote_f32() {
+        let (mut store, promote) =
+            compile::<(f32, f32), (f64, f64)>(include_str!("wat/f64_promote_f32.wat"), "promote");
+        assert_eq!(promote.call(&mut store, (1.5, 0.25)).unwrap(), (1.5, 0.25));
+    }
+
+    #[test]
+    fn test_f32_demote_f64() {
+        let (mut store, demote) =
+            compile::<(f64, f64), (f32, f32)>(include_str!("wat/f32_demote_f64.wat"), "demote");
+        assert_eq!(demote.call(&mut store, (1.5, 0.25)).unwrap(), (1.5, 0.25));
+    }
+
+    #[test]
+    fn test_f64_add() {
+        let (mut store, add) =
+            compile::<(f64, f64, f64, f64), (f64, f64)>(include_str!("wat/f64_add.wat"), "add");
+        assert_eq!(add.call(&mut store, (3., 1., 5., 2.)).unwrap(), (8., 3.));
+    }
+
+    #[test]
+    fn test_f64_sub() {
+        let (mut store, sub) =
+            compile::<(f64, f64, f64, f64), (f64, f64)>(include_str!("wat/f64_sub.wat"), "sub");
+        assert_eq!(sub.call(&mut store, (3., 1., 5., 2.)).unwrap(), (-2., -1.));
+    }
+
+    #[test]
+    fn test_f64_neg() {
+        let (mut store, neg) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/f64_neg.wat"), "neg");
+        assert_eq!(neg.call(&mut store, (3., 1.)).unwrap(), (-3., -1.));
+    }
+
+    #[test]
+    fn test_f64_sqrt() {
+        let (mut store, sqrt) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/f64_sqrt.wat"), "sqrt");
+        assert_eq!(sqrt.call(&mut store, (4., 1.)).unwrap(), (2., 0.25));
+    }
+
+    #[test]
+    fn test_f64_div() {
+        let (mut store, div) =
+            compile::<(f64, f64, f64, f64), (f64, f64)>(include_str!("wat/f64_div.wat"), "div");
+        assert_eq!(div.call(&mut store, (6., 1., 2., 1.)).unwrap(), (3., -1.));
+    }
+
+    #[test]
+    fn test_f32_add() {
+        let (mut store, add) =
+            compile::<(f32, f32, f32, f32), (f32, f32)>(include_str!("wat/f32_add.wat"), "add");
+        assert_eq!(add.call(&mut store, (3., 1., 2., 2.)).unwrap(), (5., 3.));
+    }
+
+    #[test]
+    fn test_f32_sub() {
+        let (mut store, sub) =
+            compile::<(f32, f32, f32, f32), (f32, f32)>(include_str!("wat/f32_sub.wat"), "sub");
+        assert_eq!(sub.call(&mut store, (3., 1., 2., 2.)).unwrap(), (1., -1.));
+    }
+
+    #[test]
+    fn test_f32_mul() {
+        let (mut store, mul) =
+            compile::<(f32, f32, f32, f32), (f32, f32)>(include_str!("wat/f32_mul.wat"), "mul");
+        assert_eq!(mul.call(&mut store, (3., 1., 2., 2.)).unwrap(), (6., 8.));
+    }
+
+    #[test]
+    fn test_f32_div() {
+        let (mut store, div) =
+            compile::<(f32, f32, f32, f32), (f32, f32)>(include_str!("wat/f32_div.wat"), "div");
+        assert_eq!(div.call(&mut store, (6., 1., 2., 1.)).unwrap(), (3., -1.));
+    }
+
+    #[test]
+    fn test_f32_neg() {
+        let (mut store, neg) =
+            compile::<(f32, f32), (f32, f32)>(include_str!("wat/f32_neg.wat"), "neg");
+        assert_eq!(neg.call(&mut store, (3., 2.)).unwrap(), (-3., -2.));
+    }
+
+    #[test]
+    fn test_f32_sqrt() {
+        let (mut store, sqrt) =
+            compile::<(f32, f32), (f32, f32)>(include_str!("wat/f32_sqrt.wat"), "sqrt");
+        assert_eq!(sqrt.call(&mut store, (16., 2.)).unwrap(), (4., 0.25));
+    }
+
+    #[test]
+    fn test_data() {
+        let (mut store, function) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/data.wat"), "scale");
+        assert_eq!(function.call(&mut store, (2., 1.)).unwrap(), (30., 15.));
+    }
+
+    #[test]
+    fn test_data_import_global() {
+        let input = wat::parse_str(include_str!("wat/data_import_global.wat")).unwrap();
+        let mut ad = Autodiff::new();
+        ad.import(("env", "scale"), ("env", "scale_tangent"));
+        let output = ad.forward(&input).unwrap();
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let mut linker = Linker::new(&engine);
+        for (name, value) in [("scale", 2.), ("scale_tangent", 0.)] {
+            let ty = GlobalType::new(wasmtime::ValType::F64, Mutability::Const);
+            let global = Global::new(&mut store, ty, Val::F64(f64::to_bits(value))).unwrap();
+            linker.define(&store, "env", name, global).unwrap();
+        }
+        let ty = GlobalType::new(wasmtime::ValType::I32, Mutability::Const);
+        let base = Global::new(&mut store, ty, Val::I32(16)).unwrap();
+        linker.define(&store, "env", "base", base).unwrap();
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let load = instance
+            .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, "load")
+            .unwrap();
+        // The data offset has to refer to the imported `i32` global, not to the tangent of the
+        // imported `f64` global that comes before it in the output.
+        assert_eq!(load.call(&mut store, (5., 1.)).unwrap(), (30., 6.));
+    }
+
+    #[test]
+    fn test_f64_floor() {
+        let (mut store, function) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/f64_floor.wat"), "floor");
+        assert_eq!(function.call(&mut store, (2.5, 1.)).unwrap(), (2., 0.));
+    }
+
+    #[test]
+    fn test_f32_nearest() {
+        let (mut store, function) =
+            compile::<(f32, f32), (f32, f32)>(include_str!("wat/f32_nearest.wat"), "nearest");
+        assert_eq!(function.call(&mut store, (2.7, 1.)).unwrap(), (3., 0.));
+    }
+
+    #[test]
+    fn test_f64_convert_i32_s() {
+        let (mut store, function) =
+            compile::<i32, (f64, f64)>(include_str!("wat/f64_convert_i32_s.wat"), "convert");
+        assert_eq!(function.call(&mut store, -3).unwrap(), (-3., 0.));
+    }
+
+    #[test]
+    fn test_i32_trunc_f64_s() {
+        let (mut store, function) =
+            compile::<(f64, f64), i32>(include_str!("wat/i32_trunc_f64_s.wat"), "trunc");
+        assert_eq!(function.call(&mut store, (-2.7, 1.)).unwrap(), -2);
+    }
+
+    #[test]
+    fn test_f64_reinterpret() {
+        let (mut store, function) =
+            compile::<(f64, f64), (f64, f64)>(include_str!("wat/f64_reinterpret.wat"), "bits");
+        assert_eq!(function.call(&mut store, (3., 1.)).unwrap(), (3., 0.));
+    }
+
+    #[test]
+    fn test_i32_trunc_sat_f64_s() {
+        let (mut store, function) =
+            compile::<(f64, f64), i32>(include_str!("wat/i32_trunc_sat_f64_s.wat"), "trunc");
+        assert_eq!(function.call(&mut store, (1e10, 1.)).unwrap(), i32::MAX);
+        assert_eq!(function.call(&mut store, (-2.7, 1.)).unwrap(), -2);
+    }
+
+    fn compile_batch<P: WasmParams, R: WasmResults>(
+        wat: &str,
+        name: &str,
+        batch_size: usize,
+    ) -> (Store<()>, TypedFunc<P, R>) {
+        let input = wat::parse_str(wat).unwrap();
+        let output = Autodiff::new().batch_forward(&input, batch_size).unwrap();
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let function = instance.get_typed_func::<P, R>(&mut store, name).unwrap();
+        (store, function)
+    }
+
+    #[test]
+    fn test_batch_empty() {
+        let (mut store, function) =
+            compile_batch::<(f64, f64), f64>(include_str!("wat/f64_mul.wat"), "mul", 0);
+        assert_eq!(function.call(&mut store, (3., 5.)).unwrap(), 15.);
+    }
+
+    #[test]
+    fn test_batch_jacobian() {
+        let (mut store, function) = compile_batch::<(f64, f64, f64, f64, f64, f64), (f64, f64, f64)>(
+            include_str!("wat/f64_mul.wat"),
+            "mul",
+            2,
+        );
+        // Seeding one direction per parameter gives the whole gradient at once.
+        assert_eq!(
+            function.call(&mut store, (3., 1., 0., 5., 0., 1.)).unwrap(),
+            (15., 5., 3.),
+        );
+    }
+
+    #[test]
+    fn test_batch_cube() {
+        let (mut store, function) = compile_batch::<(f64, f64, f64, f64), (f64, f64, f64, f64)>(
+            include_str!("wat/cube.wat"),
+            "cube",
+            3,
+        );
+        assert_eq!(
+            function.call(&mut store, (2., 1., 2., 3.)).unwrap(),
+            (8., 12., 24., 36.),
+        );
+    }
+
+    #[test]
+    fn test_batch_f64_div() {
+        let (mut store, function) = compile_batch::<(f64, f64, f64, f64, f64, f64), (f64, f64, f64)>(
+            include_str!("wat/f64_div.wat"),
+            "div",
+            2,
+        );
+        assert_eq!(
+            function.call(&mut store, (3., 1., 0., 2., 0., 1.)).unwrap(),
+            (1.5, 0.5, -0.75),
+        );
+    }
+
+    #[test]
+    fn test_batch_f32_sqrt() {
+        let (mut store, function) = compile_batch::<(f32, f32, f32), (f32, f32, f32)>(
+            include_str!("wat/f32_sqrt.wat"),
+            "sqrt",
+            2,
+        );
+        assert_eq!(
+            function.call(&mut store, (16., 1., 2.)).unwrap(),
+            (4., 0.125, 0.25),
+        );
+    }
+
+    #[test]
+    fn test_batch_select_f64() {
+        let (mut store, function) = compile_batch::<
+            (f64, f64, f64, f64, f64, f64, i32),
+            (f64, f64, f64),
+        >(include_str!("wat/select_f64.wat"), "select", 2);
+        assert_eq!(
+            function
+                .call(&mut store, (3., 1., 2., 5., 3., 4., 1))
+                .unwrap(),
+            (3., 1., 2.),
+        );
+        assert_eq!(
+            function
+                .call(&mut store, (3., 1., 2., 5., 3., 4., 0))
+                .unwrap(),
+            (5., 3., 4.),
+        );
+    }
+
+    #[test]
+    fn test_batch_br_table() {
+        let (mut store, function) = compile_batch::<(i32, f64, f64, f64), (f64, f64, f64)>(
+            include_str!("wat/br_table.wat"),
+            "switch",
+            2,
+        );
+        assert_eq!(
+            function.call(&mut store, (1, 2., 1., 2.)).unwrap(),
+            (8., 12., 24.),
+        );
+        assert_eq!(
+            function.call(&mut store, (2, 2., 1., 2.)).unwrap(),
+            (4., 4., 8.),
+        );
+    }
+
+    #[test]
+    fn test_batch_br_dead_code() {
+        let (mut store, function) = compile_batch::<(f64, f64, f64), (f64, f64, f64)>(
+            include_str!("wat/br_dead_code.wat"),
+            "abs",
+            2,
+        );
+        assert_eq!(
+            function.call(&mut store, (-2., 1., 3.)).unwrap(),
+            (2., -1., -3.),
+        );
+    }
+
+    #[test]
+    fn test_batch_memory_grow() {
+        let (mut store, function) = compile_batch::<(f64, f64, f64), (i32, i32, f64, f64, f64)>(
+            include_str!("wat/memory_grow.wat"),
+            "grow",
+            2,
+        );
+        assert_eq!(
+            function.call(&mut store, (3., 1., 2.)).unwrap(),
+            (1, 2, 9., 6., 12.),
+        );
+    }
+
+    #[test]
+    fn test_batch_data() {
+        let (mut store, function) = compile_batch::<(f64, f64, f64), (f64, f64, f64)>(
+            include_str!("wat/data.wat"),
+            "scale",
+            2,
+        );
+        assert_eq!(
+            function.call(&mut store, (2., 1., 3.)).unwrap(),
+            (30., 15., 45.),
+        );
+    }
+
+    #[test]
+    fn test_batch_global_set() {
+        let input = wat::parse_str(include_str!("wat/global_set.wat")).unwrap();
+        let output = Autodiff::new().batch_forward(&input, 2).unwrap();
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let module = Module::new(&engine, &output).unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let set = instance
+            .get_typed_func::<(i32, f64, f64, f64), ()>(&mut store, "set")
+            .unwrap();
+        let get_x = instance
+            .get_typed_func::<(), (f64, f64, f64)>(&mut store, "get_x")
+            .unwrap();
+        set.call(&mut store, (1, 3., 2., 1.)).unwrap();
+        assert_eq!(get_x.call(&mut store, ()).unwrap(), (3., 2., 1.));
+    }
+
+    #[test]
+    fn test_batch_import_memory() {
+        let input = wat::parse_str(include_str!("wat/import_memory_global.wat")).unwrap();
+        let mut ad = Autodiff::new();
+        ad.import(("env", "memory"), ("env", "memory_tangent"));
+        let err = ad.batch_forward(&input, 2).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Transform);
+    }
+
+    #[test]
+    fn test_batch_too_large() {
+        let input = wat::parse_str(include_str!("wat/f64_mul.wat")).unwrap();
+        for batch_size in [u32::MAX as usize, usize::MAX] {
+            let err = Autodiff::new()
+                .batch_forward(&input, batch_size)
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Transform);
+        }
+    }
 }
@@ -0,0 +1,29 @@
+(module $my_module
+  (type $my_type (;0;) (func (param i32 f64 f64) (result f64 f64)))
+  (type $pair_f32 (;1;) (func (result f32 f32)))
+  (type $pair_f64 (;2;) (func (result f64 f64)))
+  (import "foo" "bar" (func $my_imported_func (;0;) (type $my_type)))
+  (memory $my_memory (;0;) 0)
+  (memory $my_memory_tangent (;1;) 0)
+  (global $my_global (;0;) (mut f64) f64.const 0x0p+0 (;=0;))
+  (global $my_global_tangent (;1;) (mut f64) f64.const 0x0p+0 (;=0;))
+  (export "my_exported_memory" (memory $my_memory))
+  (export "my_exported_func" (func $my_func))
+  (func $my_func (;1;) (type $my_type) (param $my_int_param i32) (param $my_float_param f64) (param $my_float_param_tangent f64) (result f64 f64)
+    (local $my_local f64) (local $my_local_tangent f64) (local $tmp_f64 f64) (local $tmp_f64_1 f64) (local $tmp_f64_2 f64) (local $tmp_f64_3 f64) (local $tmp_f64_4 f64) (local $tmp_f32 f32) (local $tmp_f32_1 f32) (local $tmp_f32_2 f32) (local $tmp_f32_3 f32) (local $tmp_f32_4 f32) (local $tmp_i32 i32)
+    block $my_block
+    end
+    local.get $my_float_param
+    local.get $my_float_param_tangent
+    local.set $my_local_tangent
+    local.set $my_local
+    local.get $my_local
+    local.get $my_local_tangent
+    global.set $my_global_tangent
+    global.set $my_global
+    local.get $my_int_param
+    local.get $my_local
+    local.get $my_local_tangent
+    call $my_imported_func
+  )
+)
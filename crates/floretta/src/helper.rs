@@ -4,10 +4,10 @@ use wasm_encoder::{
 
 use crate::util::NumImports;
 
-pub const OFFSET_TYPES: u32 = 11;
+pub const OFFSET_TYPES: u32 = 12;
 pub const TYPE_DISPATCH: u32 = 0;
 const TYPE_TAPE_I32: u32 = 1;
-const TYPE_TAPE_I32_BWD: u32 = 2;
+pub const TYPE_TAPE_I32_BWD: u32 = 2;
 const TYPE_F32_PAIR: u32 = 3;
 const TYPE_F32_UNARY: u32 = 4;
 const TYPE_F32_BIN_FWD: u32 = 5;
@@ -16,30 +16,86 @@ const TYPE_F64_PAIR: u32 = 7;
 const TYPE_F64_UNARY: u32 = 8;
 const TYPE_F64_BIN_FWD: u32 = 9;
 const TYPE_F64_BIN_BWD: u32 = 10;
+pub const TYPE_NULLARY: u32 = 11;
 
-pub const OFFSET_MEMORIES: u32 = 3;
-const MEM_TAPE_ALIGN_1: u32 = 0;
-const MEM_TAPE_ALIGN_4: u32 = 1;
-const MEM_TAPE_ALIGN_8: u32 = 2;
+pub const OFFSET_MEMORIES: u32 = 1;
+const MEM_TAPE: u32 = 0;
 
-pub const OFFSET_GLOBALS: u32 = 3;
-const GLOBAL_TAPE_ALIGN_1: u32 = 0;
-const GLOBAL_TAPE_ALIGN_4: u32 = 1;
-const GLOBAL_TAPE_ALIGN_8: u32 = 2;
+pub const OFFSET_GLOBALS: u32 = 1;
+const GLOBAL_TAPE: u32 = 0;
 
-pub const OFFSET_FUNCTIONS: u32 = 26;
+/// Minimum number of pages to grow the tape by whenever it runs out of space, so that a forward
+/// pass pushing lots of small values doesn't call `memory.grow` once for every page.
+const TAPE_GROWTH_FACTOR: i32 = 16;
 
+pub const OFFSET_FUNCTIONS: u32 = 32;
+
+/// The indices of the memories and globals used by a set of helper functions for the tape.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tapes {
+    /// Index of the first of the [`OFFSET_MEMORIES`] tape memories.
+    pub memories: u32,
+
+    /// Index of the first of the [`OFFSET_GLOBALS`] tape globals.
+    pub globals: u32,
+}
+
+impl Tapes {
+    /// Tape memories and globals shared by the whole module, placed right after the imported
+    /// memories and globals.
+    pub fn new(num_imports: NumImports) -> Self {
+        Self {
+            memories: 2 * num_imports.memory,
+            globals: num_imports.global + num_imports.global_adjoint,
+        }
+    }
+}
+
+/// The index in the output of the primal counterpart of the memory at `index` in the input; its
+/// adjoint counterpart comes right after it.
+pub fn memidx(num_imports: NumImports, index: u32) -> u32 {
+    let mut memidx = 2 * index;
+    if index >= num_imports.memory {
+        memidx += OFFSET_MEMORIES;
+    }
+    memidx
+}
+
+/// The index in the output of the global at `index` in the input.
+pub fn globalidx(num_imports: NumImports, index: u32) -> u32 {
+    if index < num_imports.global {
+        index
+    } else {
+        index + num_imports.global_adjoint + OFFSET_GLOBALS
+    }
+}
+
+#[derive(Clone, Copy)]
 pub struct FuncOffsets {
-    num_imports: NumImports,
+    offset: u32,
 }
 
 impl FuncOffsets {
+    /// Helper functions shared by the whole module, placed right after the imported functions.
     pub fn new(num_imports: NumImports) -> Self {
-        Self { num_imports }
+        Self {
+            offset: 2 * num_imports.func,
+        }
+    }
+
+    /// Helper functions for the tape partition with the given `index`, placed after all the
+    /// `num_functions` functions defined in the module.
+    pub fn partition(num_imports: NumImports, num_functions: u32, index: u32) -> Self {
+        Self {
+            offset: 2 * num_imports.func
+                + OFFSET_FUNCTIONS
+                + 2 * num_functions
+                + OFFSET_FUNCTIONS * index,
+        }
     }
 
     fn offset(&self) -> u32 {
-        2 * self.num_imports.func
+        self.offset
     }
 
     pub fn tape_i32(&self) -> u32 {
@@ -98,53 +154,107 @@ impl FuncOffsets {
         self.offset() + 13
     }
 
-    pub fn f64_sqrt_fwd(&self) -> u32 {
+    pub fn f32_abs_fwd(&self) -> u32 {
         self.offset() + 14
     }
 
-    pub fn f64_sqrt_bwd(&self) -> u32 {
+    pub fn f32_abs_bwd(&self) -> u32 {
         self.offset() + 15
     }
 
-    pub fn f64_mul_fwd(&self) -> u32 {
+    pub fn f64_sqrt_fwd(&self) -> u32 {
         self.offset() + 16
     }
 
-    pub fn f64_mul_bwd(&self) -> u32 {
+    pub fn f64_sqrt_bwd(&self) -> u32 {
         self.offset() + 17
     }
 
-    pub fn f64_div_fwd(&self) -> u32 {
+    pub fn f64_mul_fwd(&self) -> u32 {
         self.offset() + 18
     }
 
-    pub fn f64_div_bwd(&self) -> u32 {
+    pub fn f64_mul_bwd(&self) -> u32 {
         self.offset() + 19
     }
 
-    pub fn f64_min_fwd(&self) -> u32 {
+    pub fn f64_div_fwd(&self) -> u32 {
         self.offset() + 20
     }
 
-    pub fn f64_min_bwd(&self) -> u32 {
+    pub fn f64_div_bwd(&self) -> u32 {
         self.offset() + 21
     }
 
-    pub fn f64_max_fwd(&self) -> u32 {
+    pub fn f64_min_fwd(&self) -> u32 {
         self.offset() + 22
     }
 
-    pub fn f64_max_bwd(&self) -> u32 {
+    pub fn f64_min_bwd(&self) -> u32 {
         self.offset() + 23
     }
 
-    pub fn f64_copysign_fwd(&self) -> u32 {
+    pub fn f64_max_fwd(&self) -> u32 {
         self.offset() + 24
     }
 
-    pub fn f64_copysign_bwd(&self) -> u32 {
+    pub fn f64_max_bwd(&self) -> u32 {
         self.offset() + 25
     }
+
+    pub fn f64_copysign_fwd(&self) -> u32 {
+        self.offset() + 26
+    }
+
+    pub fn f64_copysign_bwd(&self) -> u32 {
+        self.offset() + 27
+    }
+
+    pub fn f64_abs_fwd(&self) -> u32 {
+        self.offset() + 28
+    }
+
+    pub fn f64_abs_bwd(&self) -> u32 {
+        self.offset() + 29
+    }
+
+    pub fn tape_reset(&self) -> u32 {
+        self.offset() + 30
+    }
+
+    pub fn tape_ptr(&self) -> u32 {
+        self.offset() + 31
+    }
+
+    /// Whether `funcidx` is one of these helper functions.
+    pub fn contains(&self, funcidx: u32) -> bool {
+        (self.offset()..self.offset() + OFFSET_FUNCTIONS).contains(&funcidx)
+    }
+
+    /// Number of bytes that one call to the helper function at `funcidx` pushes onto the tape.
+    pub fn tape_bytes(&self, funcidx: u32) -> u32 {
+        let bytes = [
+            (self.tape_i32(), 4),
+            (self.f32_sqrt_fwd(), 4),
+            (self.f32_mul_fwd(), 8),
+            (self.f32_div_fwd(), 8),
+            (self.f32_min_fwd(), 1),
+            (self.f32_max_fwd(), 1),
+            (self.f32_copysign_fwd(), 1),
+            (self.f32_abs_fwd(), 1),
+            (self.f64_sqrt_fwd(), 8),
+            (self.f64_mul_fwd(), 16),
+            (self.f64_div_fwd(), 16),
+            (self.f64_min_fwd(), 1),
+            (self.f64_max_fwd(), 1),
+            (self.f64_copysign_fwd(), 1),
+            (self.f64_abs_fwd(), 1),
+        ];
+        bytes
+            .into_iter()
+            .find(|&(index, _)| index == funcidx)
+            .map_or(0, |(_, bytes)| bytes)
+    }
 }
 
 pub fn helper_types() -> impl Iterator<Item = (&'static str, FuncType)> {
@@ -196,6 +306,7 @@ pub fn helper_types() -> impl Iterator<Item = (&'static str, FuncType)> {
             "f64_bin_bwd",
             FuncType::new([ValType::F64], [ValType::F64, ValType::F64]),
         ),
+        (TYPE_NULLARY, "nullary", FuncType::new([], [])),
     ]
     .into_iter()
     .zip(0..)
@@ -205,25 +316,21 @@ pub fn helper_types() -> impl Iterator<Item = (&'static str, FuncType)> {
     })
 }
 
-pub fn helper_memories() -> impl Iterator<Item = (&'static str, MemoryType)> {
+pub fn helper_memories(initial_pages: u32) -> impl Iterator<Item = (&'static str, MemoryType)> {
     let memory = MemoryType {
-        minimum: 0,
+        minimum: initial_pages.into(),
         maximum: None,
         memory64: false,
         shared: false,
         page_size_log2: None,
     };
-    [
-        (MEM_TAPE_ALIGN_1, "tape_align_1"),
-        (MEM_TAPE_ALIGN_4, "tape_align_4"),
-        (MEM_TAPE_ALIGN_8, "tape_align_8"),
-    ]
-    .into_iter()
-    .zip(0..)
-    .map(move |((i, name), j)| {
-        assert_eq!(i, j);
-        (name, memory)
-    })
+    [(MEM_TAPE, "tape")]
+        .into_iter()
+        .zip(0..)
+        .map(move |((i, name), j)| {
+            assert_eq!(i, j);
+            (name, memory)
+        })
 }
 
 pub fn helper_globals() -> impl Iterator<Item = (&'static str, GlobalType, ConstExpr)> {
@@ -232,177 +339,209 @@ pub fn helper_globals() -> impl Iterator<Item = (&'static str, GlobalType, Const
         mutable: true,
         shared: false,
     };
-    [
-        (GLOBAL_TAPE_ALIGN_1, "tape_align_1", ConstExpr::i32_const(0)),
-        (GLOBAL_TAPE_ALIGN_4, "tape_align_4", ConstExpr::i32_const(0)),
-        (GLOBAL_TAPE_ALIGN_8, "tape_align_8", ConstExpr::i32_const(0)),
-    ]
-    .into_iter()
-    .zip(0..)
-    .map(move |((i, name, init), j)| {
-        assert_eq!(i, j);
-        (name, ty, init)
-    })
+    [(GLOBAL_TAPE, "tape", ConstExpr::i32_const(0))]
+        .into_iter()
+        .zip(0..)
+        .map(move |((i, name, init), j)| {
+            assert_eq!(i, j);
+            (name, ty, init)
+        })
 }
 
-pub fn helper_functions() -> impl Iterator<Item = (&'static str, u32, Function)> {
+pub fn helper_functions(tapes: Tapes) -> impl Iterator<Item = (&'static str, u32, Function)> {
     let offsets = FuncOffsets::new(NumImports::default());
     [
         (
             offsets.tape_i32(),
             "tape_i32",
             TYPE_TAPE_I32,
-            func_tape_i32(),
+            func_tape_i32(tapes),
         ),
         (
             offsets.tape_i32_bwd(),
             "tape_i32_bwd",
             TYPE_TAPE_I32_BWD,
-            func_tape_i32_bwd(),
+            func_tape_i32_bwd(tapes),
         ),
         (
             offsets.f32_sqrt_fwd(),
             "f32_sqrt",
             TYPE_F32_UNARY,
-            func_f32_sqrt_fwd(),
+            func_f32_sqrt_fwd(tapes),
         ),
         (
             offsets.f32_sqrt_bwd(),
             "f32_sqrt_bwd",
             TYPE_F32_UNARY,
-            func_f32_sqrt_bwd(),
+            func_f32_sqrt_bwd(tapes),
         ),
         (
             offsets.f32_mul_fwd(),
             "f32_mul",
             TYPE_F32_BIN_FWD,
-            func_f32_mul_fwd(),
+            func_f32_mul_fwd(tapes),
         ),
         (
             offsets.f32_mul_bwd(),
             "f32_mul_bwd",
             TYPE_F32_BIN_BWD,
-            func_f32_mul_bwd(),
+            func_f32_mul_bwd(tapes),
         ),
         (
             offsets.f32_div_fwd(),
             "f32_div",
             TYPE_F32_BIN_FWD,
-            func_f32_div_fwd(),
+            func_f32_div_fwd(tapes),
         ),
         (
             offsets.f32_div_bwd(),
             "f32_div_bwd",
             TYPE_F32_BIN_BWD,
-            func_f32_div_bwd(),
+            func_f32_div_bwd(tapes),
         ),
         (
             offsets.f32_min_fwd(),
             "f32_min",
             TYPE_F32_BIN_FWD,
-            func_f32_min_fwd(),
+            func_f32_min_fwd(tapes),
         ),
         (
             offsets.f32_min_bwd(),
             "f32_min_bwd",
             TYPE_F32_BIN_BWD,
-            func_f32_min_bwd(),
+            func_f32_min_bwd(tapes),
         ),
         (
             offsets.f32_max_fwd(),
             "f32_max",
             TYPE_F32_BIN_FWD,
-            func_f32_max_fwd(),
+            func_f32_max_fwd(tapes),
         ),
         (
             offsets.f32_max_bwd(),
             "f32_max_bwd",
             TYPE_F32_BIN_BWD,
-            func_f32_max_bwd(),
+            func_f32_max_bwd(tapes),
         ),
         (
             offsets.f32_copysign_fwd(),
             "f32_copysign",
             TYPE_F32_BIN_FWD,
-            func_f32_copysign_fwd(),
+            func_f32_copysign_fwd(tapes),
         ),
         (
             offsets.f32_copysign_bwd(),
             "f32_copysign_bwd",
             TYPE_F32_BIN_BWD,
-            func_f32_copysign_bwd(),
+            func_f32_copysign_bwd(tapes),
+        ),
+        (
+            offsets.f32_abs_fwd(),
+            "f32_abs",
+            TYPE_F32_UNARY,
+            func_f32_abs_fwd(tapes),
+        ),
+        (
+            offsets.f32_abs_bwd(),
+            "f32_abs_bwd",
+            TYPE_F32_UNARY,
+            func_f32_abs_bwd(tapes),
         ),
         (
             offsets.f64_sqrt_fwd(),
             "f64_sqrt",
             TYPE_F64_UNARY,
-            func_f64_sqrt_fwd(),
+            func_f64_sqrt_fwd(tapes),
         ),
         (
             offsets.f64_sqrt_bwd(),
             "f64_sqrt_bwd",
             TYPE_F64_UNARY,
-            func_f64_sqrt_bwd(),
+            func_f64_sqrt_bwd(tapes),
         ),
         (
             offsets.f64_mul_fwd(),
             "f64_mul",
             TYPE_F64_BIN_FWD,
-            func_f64_mul_fwd(),
+            func_f64_mul_fwd(tapes),
         ),
         (
             offsets.f64_mul_bwd(),
             "f64_mul_bwd",
             TYPE_F64_BIN_BWD,
-            func_f64_mul_bwd(),
+            func_f64_mul_bwd(tapes),
         ),
         (
             offsets.f64_div_fwd(),
             "f64_div",
             TYPE_F64_BIN_FWD,
-            func_f64_div_fwd(),
+            func_f64_div_fwd(tapes),
         ),
         (
             offsets.f64_div_bwd(),
             "f64_div_bwd",
             TYPE_F64_BIN_BWD,
-            func_f64_div_bwd(),
+            func_f64_div_bwd(tapes),
         ),
         (
             offsets.f64_min_fwd(),
             "f64_min",
             TYPE_F64_BIN_FWD,
-            func_f64_min_fwd(),
+            func_f64_min_fwd(tapes),
         ),
         (
             offsets.f64_min_bwd(),
             "f64_min_bwd",
             TYPE_F64_BIN_BWD,
-            func_f64_min_bwd(),
+            func_f64_min_bwd(tapes),
         ),
         (
             offsets.f64_max_fwd(),
             "f64_max",
             TYPE_F64_BIN_FWD,
-            func_f64_max_fwd(),
+            func_f64_max_fwd(tapes),
         ),
         (
             offsets.f64_max_bwd(),
             "f64_max_bwd",
             TYPE_F64_BIN_BWD,
-            func_f64_max_bwd(),
+            func_f64_max_bwd(tapes),
         ),
         (
             offsets.f64_copysign_fwd(),
             "f64_copysign",
             TYPE_F64_BIN_FWD,
-            func_f64_copysign_fwd(),
+            func_f64_copysign_fwd(tapes),
         ),
         (
             offsets.f64_copysign_bwd(),
             "f64_copysign_bwd",
             TYPE_F64_BIN_BWD,
-            func_f64_copysign_bwd(),
+            func_f64_copysign_bwd(tapes),
+        ),
+        (
+            offsets.f64_abs_fwd(),
+            "f64_abs",
+            TYPE_F64_UNARY,
+            func_f64_abs_fwd(tapes),
+        ),
+        (
+            offsets.f64_abs_bwd(),
+            "f64_abs_bwd",
+            TYPE_F64_UNARY,
+            func_f64_abs_bwd(tapes),
+        ),
+        (
+            offsets.tape_reset(),
+            "tape_reset",
+            TYPE_NULLARY,
+            func_tape_reset(tapes),
+        ),
+        (
+            offsets.tape_ptr(),
+            "tape_ptr",
+            TYPE_TAPE_I32_BWD,
+            func_tape_ptr(tapes),
         ),
     ]
     .into_iter()
@@ -413,6 +552,9 @@ pub fn helper_functions() -> impl Iterator<Item = (&'static str, u32, Function)>
     })
 }
 
+/// The tape is a single stack of bytes. Values of different sizes are packed right next to each
+/// other with no padding, because otherwise popping a value would not tell us how much padding to
+/// pop along with it; so loads and stores on the tape are not necessarily aligned.
 struct Tape {
     memory: u32,
     global: u32,
@@ -420,6 +562,14 @@ struct Tape {
 }
 
 impl Tape {
+    fn new(tapes: Tapes, local: u32) -> Self {
+        Self {
+            memory: tapes.memories + MEM_TAPE,
+            global: tapes.globals + GLOBAL_TAPE,
+            local,
+        }
+    }
+
     fn grow(self, f: &mut Function, local: u32, bytes: i32) {
         f.instructions()
             .global_get(self.global)
@@ -431,8 +581,16 @@ impl Tape {
             .memory_size(self.memory)
             .i32_sub()
             .local_tee(local)
+            // The memory may already be bigger than we need if it started with some pages.
+            .i32_const(0)
+            .i32_gt_s()
             .if_(BlockType::Empty)
             .local_get(local)
+            .i32_const(TAPE_GROWTH_FACTOR)
+            .local_get(local)
+            .i32_const(TAPE_GROWTH_FACTOR)
+            .i32_gt_s()
+            .select()
             .memory_grow(self.memory)
             .drop()
             .end()
@@ -452,56 +610,41 @@ impl Tape {
     }
 }
 
-fn func_tape_i32() -> Function {
+fn func_tape_i32(tapes: Tapes) -> Function {
     let [k, i, n] = [0, 1, 2];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_4,
-        global: GLOBAL_TAPE_ALIGN_4,
-        local: i,
-    }
-    .grow(&mut f, n, 4);
+    Tape::new(tapes, i).grow(&mut f, n, 4);
     f.instructions()
         .local_get(i)
         .local_get(k)
         .i32_store(MemArg {
             offset: 0,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .end();
     f
 }
 
-fn func_tape_i32_bwd() -> Function {
+fn func_tape_i32_bwd(tapes: Tapes) -> Function {
     let [i] = [0];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_4,
-        global: GLOBAL_TAPE_ALIGN_4,
-        local: i,
-    }
-    .shrink(&mut f, 4);
+    Tape::new(tapes, i).shrink(&mut f, 4);
     f.instructions()
         .local_get(i)
         .i32_load(MemArg {
             offset: 0,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .end();
     f
 }
 
-fn func_f32_sqrt_fwd() -> Function {
+fn func_f32_sqrt_fwd(tapes: Tapes) -> Function {
     let [x, y, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(1, ValType::F32), (2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_4,
-        global: GLOBAL_TAPE_ALIGN_4,
-        local: i,
-    }
-    .grow(&mut f, n, 4);
+    Tape::new(tapes, i).grow(&mut f, n, 4);
     f.instructions()
         .local_get(i)
         .local_get(x)
@@ -509,30 +652,25 @@ fn func_f32_sqrt_fwd() -> Function {
         .local_tee(y)
         .f32_store(MemArg {
             offset: 0,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(y)
         .end();
     f
 }
 
-fn func_f32_sqrt_bwd() -> Function {
+fn func_f32_sqrt_bwd(tapes: Tapes) -> Function {
     let [dy, y, i] = [0, 1, 2];
     let mut f = Function::new([(1, ValType::F32), (1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_4,
-        global: GLOBAL_TAPE_ALIGN_4,
-        local: i,
-    }
-    .shrink(&mut f, 4);
+    Tape::new(tapes, i).shrink(&mut f, 4);
     f.instructions()
         .local_get(dy)
         .local_get(i)
         .f32_load(MemArg {
             offset: 0,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_tee(y)
         .local_get(y)
@@ -542,29 +680,24 @@ fn func_f32_sqrt_bwd() -> Function {
     f
 }
 
-fn func_f32_mul_fwd() -> Function {
+fn func_f32_mul_fwd(tapes: Tapes) -> Function {
     let [x, y, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_4,
-        global: GLOBAL_TAPE_ALIGN_4,
-        local: i,
-    }
-    .grow(&mut f, n, 8);
+    Tape::new(tapes, i).grow(&mut f, n, 8);
     f.instructions()
         .local_get(i)
         .local_get(x)
         .f32_store(MemArg {
             offset: 0,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(i)
         .local_get(y)
         .f32_store(MemArg {
             offset: 4,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(x)
         .local_get(y)
@@ -573,52 +706,42 @@ fn func_f32_mul_fwd() -> Function {
     f
 }
 
-fn func_f32_mul_bwd() -> Function {
+fn func_f32_mul_bwd(tapes: Tapes) -> Function {
     let [dz, i] = [0, 1];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_4,
-        global: GLOBAL_TAPE_ALIGN_4,
-        local: i,
-    }
-    .shrink(&mut f, 8);
+    Tape::new(tapes, i).shrink(&mut f, 8);
     f.instructions()
         .local_get(dz)
         .local_get(i)
         .f32_load(MemArg {
             offset: 4,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .f32_mul()
         .local_get(dz)
         .local_get(i)
         .f32_load(MemArg {
             offset: 0,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .f32_mul()
         .end();
     f
 }
 
-fn func_f32_div_fwd() -> Function {
+fn func_f32_div_fwd(tapes: Tapes) -> Function {
     let [x, y, z, i, n] = [0, 1, 2, 3, 4];
     let mut f = Function::new([(1, ValType::F32), (2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_4,
-        global: GLOBAL_TAPE_ALIGN_4,
-        local: i,
-    }
-    .grow(&mut f, n, 8);
+    Tape::new(tapes, i).grow(&mut f, n, 8);
     f.instructions()
         .local_get(i)
         .local_get(y)
         .f32_store(MemArg {
             offset: 0,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(i)
         .local_get(x)
@@ -627,30 +750,25 @@ fn func_f32_div_fwd() -> Function {
         .local_tee(z)
         .f32_store(MemArg {
             offset: 4,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(z)
         .end();
     f
 }
 
-fn func_f32_div_bwd() -> Function {
+fn func_f32_div_bwd(tapes: Tapes) -> Function {
     let [dz, dx, i] = [0, 1, 2];
     let mut f = Function::new([(1, ValType::F32), (1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_4,
-        global: GLOBAL_TAPE_ALIGN_4,
-        local: i,
-    }
-    .shrink(&mut f, 8);
+    Tape::new(tapes, i).shrink(&mut f, 8);
     f.instructions()
         .local_get(dz)
         .local_get(i)
         .f32_load(MemArg {
             offset: 0,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .f32_div()
         .local_tee(dx)
@@ -658,8 +776,8 @@ fn func_f32_div_bwd() -> Function {
         .local_get(i)
         .f32_load(MemArg {
             offset: 4,
-            align: 2,
-            memory_index: MEM_TAPE_ALIGN_4,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .f32_neg()
         .f32_mul()
@@ -667,15 +785,10 @@ fn func_f32_div_bwd() -> Function {
     f
 }
 
-fn func_f32_min_fwd() -> Function {
+fn func_f32_min_fwd(tapes: Tapes) -> Function {
     let [x, y, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .grow(&mut f, n, 1);
+    Tape::new(tapes, i).grow(&mut f, n, 1);
     f.instructions()
         .local_get(i)
         .local_get(x)
@@ -684,7 +797,7 @@ fn func_f32_min_fwd() -> Function {
         .i32_store8(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(x)
         .local_get(y)
@@ -693,21 +806,16 @@ fn func_f32_min_fwd() -> Function {
     f
 }
 
-fn func_f32_min_bwd() -> Function {
+fn func_f32_min_bwd(tapes: Tapes) -> Function {
     let [dz, i] = [0, 1];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .shrink(&mut f, 1);
+    Tape::new(tapes, i).shrink(&mut f, 1);
     f.instructions()
         .local_get(i)
         .i32_load8_u(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .if_(BlockType::FunctionType(TYPE_F32_PAIR))
         .f32_const(0.)
@@ -720,15 +828,10 @@ fn func_f32_min_bwd() -> Function {
     f
 }
 
-fn func_f32_max_fwd() -> Function {
+fn func_f32_max_fwd(tapes: Tapes) -> Function {
     let [x, y, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .grow(&mut f, n, 1);
+    Tape::new(tapes, i).grow(&mut f, n, 1);
     f.instructions()
         .local_get(i)
         .local_get(x)
@@ -737,7 +840,7 @@ fn func_f32_max_fwd() -> Function {
         .i32_store8(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(x)
         .local_get(y)
@@ -746,21 +849,16 @@ fn func_f32_max_fwd() -> Function {
     f
 }
 
-fn func_f32_max_bwd() -> Function {
+fn func_f32_max_bwd(tapes: Tapes) -> Function {
     let [dz, i] = [0, 1];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .shrink(&mut f, 1);
+    Tape::new(tapes, i).shrink(&mut f, 1);
     f.instructions()
         .local_get(i)
         .i32_load8_u(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .if_(BlockType::FunctionType(TYPE_F32_PAIR))
         .f32_const(0.)
@@ -773,15 +871,10 @@ fn func_f32_max_bwd() -> Function {
     f
 }
 
-fn func_f32_copysign_fwd() -> Function {
+fn func_f32_copysign_fwd(tapes: Tapes) -> Function {
     let [to, from, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .grow(&mut f, n, 1);
+    Tape::new(tapes, i).grow(&mut f, n, 1);
     f.instructions()
         .local_get(i)
         .local_get(to)
@@ -792,7 +885,7 @@ fn func_f32_copysign_fwd() -> Function {
         .i32_store8(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(to)
         .local_get(from)
@@ -801,15 +894,10 @@ fn func_f32_copysign_fwd() -> Function {
     f
 }
 
-fn func_f32_copysign_bwd() -> Function {
+fn func_f32_copysign_bwd(tapes: Tapes) -> Function {
     let [grad, i] = [0, 1];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .shrink(&mut f, 1);
+    Tape::new(tapes, i).shrink(&mut f, 1);
     f.instructions()
         .local_get(grad)
         .local_get(grad)
@@ -819,7 +907,7 @@ fn func_f32_copysign_bwd() -> Function {
         .i32_load8_u(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .select()
         .f32_const(0.)
@@ -827,15 +915,49 @@ fn func_f32_copysign_bwd() -> Function {
     f
 }
 
-fn func_f64_sqrt_fwd() -> Function {
+fn func_f32_abs_fwd(tapes: Tapes) -> Function {
+    let [x, i, n] = [0, 1, 2];
+    let mut f = Function::new([(2, ValType::I32)]);
+    Tape::new(tapes, i).grow(&mut f, n, 1);
+    f.instructions()
+        .local_get(i)
+        .local_get(x)
+        .f32_const(0.)
+        .f32_lt()
+        .i32_store8(MemArg {
+            offset: 0,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
+        })
+        .local_get(x)
+        .f32_abs()
+        .end();
+    f
+}
+
+fn func_f32_abs_bwd(tapes: Tapes) -> Function {
+    let [dy, i] = [0, 1];
+    let mut f = Function::new([(1, ValType::I32)]);
+    Tape::new(tapes, i).shrink(&mut f, 1);
+    f.instructions()
+        .local_get(dy)
+        .f32_neg()
+        .local_get(dy)
+        .local_get(i)
+        .i32_load8_u(MemArg {
+            offset: 0,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
+        })
+        .select()
+        .end();
+    f
+}
+
+fn func_f64_sqrt_fwd(tapes: Tapes) -> Function {
     let [x, y, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(1, ValType::F64), (2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_8,
-        global: GLOBAL_TAPE_ALIGN_8,
-        local: i,
-    }
-    .grow(&mut f, n, 8);
+    Tape::new(tapes, i).grow(&mut f, n, 8);
     f.instructions()
         .local_get(i)
         .local_get(x)
@@ -843,30 +965,25 @@ fn func_f64_sqrt_fwd() -> Function {
         .local_tee(y)
         .f64_store(MemArg {
             offset: 0,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(y)
         .end();
     f
 }
 
-fn func_f64_sqrt_bwd() -> Function {
+fn func_f64_sqrt_bwd(tapes: Tapes) -> Function {
     let [dy, y, i] = [0, 1, 2];
     let mut f = Function::new([(1, ValType::F64), (1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_8,
-        global: GLOBAL_TAPE_ALIGN_8,
-        local: i,
-    }
-    .shrink(&mut f, 8);
+    Tape::new(tapes, i).shrink(&mut f, 8);
     f.instructions()
         .local_get(dy)
         .local_get(i)
         .f64_load(MemArg {
             offset: 0,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_tee(y)
         .local_get(y)
@@ -876,29 +993,24 @@ fn func_f64_sqrt_bwd() -> Function {
     f
 }
 
-fn func_f64_mul_fwd() -> Function {
+fn func_f64_mul_fwd(tapes: Tapes) -> Function {
     let [x, y, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_8,
-        global: GLOBAL_TAPE_ALIGN_8,
-        local: i,
-    }
-    .grow(&mut f, n, 16);
+    Tape::new(tapes, i).grow(&mut f, n, 16);
     f.instructions()
         .local_get(i)
         .local_get(x)
         .f64_store(MemArg {
             offset: 0,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(i)
         .local_get(y)
         .f64_store(MemArg {
             offset: 8,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(x)
         .local_get(y)
@@ -907,52 +1019,42 @@ fn func_f64_mul_fwd() -> Function {
     f
 }
 
-fn func_f64_mul_bwd() -> Function {
+fn func_f64_mul_bwd(tapes: Tapes) -> Function {
     let [dz, i] = [0, 1];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_8,
-        global: GLOBAL_TAPE_ALIGN_8,
-        local: i,
-    }
-    .shrink(&mut f, 16);
+    Tape::new(tapes, i).shrink(&mut f, 16);
     f.instructions()
         .local_get(dz)
         .local_get(i)
         .f64_load(MemArg {
             offset: 8,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .f64_mul()
         .local_get(dz)
         .local_get(i)
         .f64_load(MemArg {
             offset: 0,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .f64_mul()
         .end();
     f
 }
 
-fn func_f64_div_fwd() -> Function {
+fn func_f64_div_fwd(tapes: Tapes) -> Function {
     let [x, y, z, i, n] = [0, 1, 2, 3, 4];
     let mut f = Function::new([(1, ValType::F64), (2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_8,
-        global: GLOBAL_TAPE_ALIGN_8,
-        local: i,
-    }
-    .grow(&mut f, n, 16);
+    Tape::new(tapes, i).grow(&mut f, n, 16);
     f.instructions()
         .local_get(i)
         .local_get(y)
         .f64_store(MemArg {
             offset: 0,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(i)
         .local_get(x)
@@ -961,30 +1063,25 @@ fn func_f64_div_fwd() -> Function {
         .local_tee(z)
         .f64_store(MemArg {
             offset: 8,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(z)
         .end();
     f
 }
 
-fn func_f64_div_bwd() -> Function {
+fn func_f64_div_bwd(tapes: Tapes) -> Function {
     let [dz, dx, i] = [0, 1, 2];
     let mut f = Function::new([(1, ValType::F64), (1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_8,
-        global: GLOBAL_TAPE_ALIGN_8,
-        local: i,
-    }
-    .shrink(&mut f, 16);
+    Tape::new(tapes, i).shrink(&mut f, 16);
     f.instructions()
         .local_get(dz)
         .local_get(i)
         .f64_load(MemArg {
             offset: 0,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .f64_div()
         .local_tee(dx)
@@ -992,8 +1089,8 @@ fn func_f64_div_bwd() -> Function {
         .local_get(i)
         .f64_load(MemArg {
             offset: 8,
-            align: 3,
-            memory_index: MEM_TAPE_ALIGN_8,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .f64_neg()
         .f64_mul()
@@ -1001,15 +1098,10 @@ fn func_f64_div_bwd() -> Function {
     f
 }
 
-fn func_f64_min_fwd() -> Function {
+fn func_f64_min_fwd(tapes: Tapes) -> Function {
     let [x, y, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .grow(&mut f, n, 1);
+    Tape::new(tapes, i).grow(&mut f, n, 1);
     f.instructions()
         .local_get(i)
         .local_get(x)
@@ -1018,7 +1110,7 @@ fn func_f64_min_fwd() -> Function {
         .i32_store8(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(x)
         .local_get(y)
@@ -1027,21 +1119,16 @@ fn func_f64_min_fwd() -> Function {
     f
 }
 
-fn func_f64_min_bwd() -> Function {
+fn func_f64_min_bwd(tapes: Tapes) -> Function {
     let [dz, i] = [0, 1];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .shrink(&mut f, 1);
+    Tape::new(tapes, i).shrink(&mut f, 1);
     f.instructions()
         .local_get(i)
         .i32_load8_u(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .if_(BlockType::FunctionType(TYPE_F64_PAIR))
         .f64_const(0.)
@@ -1054,15 +1141,10 @@ fn func_f64_min_bwd() -> Function {
     f
 }
 
-fn func_f64_max_fwd() -> Function {
+fn func_f64_max_fwd(tapes: Tapes) -> Function {
     let [x, y, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .grow(&mut f, n, 1);
+    Tape::new(tapes, i).grow(&mut f, n, 1);
     f.instructions()
         .local_get(i)
         .local_get(x)
@@ -1071,7 +1153,7 @@ fn func_f64_max_fwd() -> Function {
         .i32_store8(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(x)
         .local_get(y)
@@ -1080,21 +1162,16 @@ fn func_f64_max_fwd() -> Function {
     f
 }
 
-fn func_f64_max_bwd() -> Function {
+fn func_f64_max_bwd(tapes: Tapes) -> Function {
     let [dz, i] = [0, 1];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .shrink(&mut f, 1);
+    Tape::new(tapes, i).shrink(&mut f, 1);
     f.instructions()
         .local_get(i)
         .i32_load8_u(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .if_(BlockType::FunctionType(TYPE_F64_PAIR))
         .f64_const(0.)
@@ -1107,15 +1184,10 @@ fn func_f64_max_bwd() -> Function {
     f
 }
 
-fn func_f64_copysign_fwd() -> Function {
+fn func_f64_copysign_fwd(tapes: Tapes) -> Function {
     let [to, from, i, n] = [0, 1, 2, 3];
     let mut f = Function::new([(2, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .grow(&mut f, n, 1);
+    Tape::new(tapes, i).grow(&mut f, n, 1);
     f.instructions()
         .local_get(i)
         .local_get(to)
@@ -1126,7 +1198,7 @@ fn func_f64_copysign_fwd() -> Function {
         .i32_store8(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .local_get(to)
         .local_get(from)
@@ -1135,15 +1207,10 @@ fn func_f64_copysign_fwd() -> Function {
     f
 }
 
-fn func_f64_copysign_bwd() -> Function {
+fn func_f64_copysign_bwd(tapes: Tapes) -> Function {
     let [grad, i] = [0, 1];
     let mut f = Function::new([(1, ValType::I32)]);
-    Tape {
-        memory: MEM_TAPE_ALIGN_1,
-        global: GLOBAL_TAPE_ALIGN_1,
-        local: i,
-    }
-    .shrink(&mut f, 1);
+    Tape::new(tapes, i).shrink(&mut f, 1);
     f.instructions()
         .local_get(grad)
         .local_get(grad)
@@ -1153,10 +1220,68 @@ fn func_f64_copysign_bwd() -> Function {
         .i32_load8_u(MemArg {
             offset: 0,
             align: 0,
-            memory_index: MEM_TAPE_ALIGN_1,
+            memory_index: tapes.memories + MEM_TAPE,
         })
         .select()
         .f64_const(0.)
         .end();
     f
 }
+
+fn func_f64_abs_fwd(tapes: Tapes) -> Function {
+    let [x, i, n] = [0, 1, 2];
+    let mut f = Function::new([(2, ValType::I32)]);
+    Tape::new(tapes, i).grow(&mut f, n, 1);
+    f.instructions()
+        .local_get(i)
+        .local_get(x)
+        .f64_const(0.)
+        .f64_lt()
+        .i32_store8(MemArg {
+            offset: 0,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
+        })
+        .local_get(x)
+        .f64_abs()
+        .end();
+    f
+}
+
+fn func_f64_abs_bwd(tapes: Tapes) -> Function {
+    let [dy, i] = [0, 1];
+    let mut f = Function::new([(1, ValType::I32)]);
+    Tape::new(tapes, i).shrink(&mut f, 1);
+    f.instructions()
+        .local_get(dy)
+        .f64_neg()
+        .local_get(dy)
+        .local_get(i)
+        .i32_load8_u(MemArg {
+            offset: 0,
+            align: 0,
+            memory_index: tapes.memories + MEM_TAPE,
+        })
+        .select()
+        .end();
+    f
+}
+
+/// Empty the tape, discarding whatever the forward passes pushed that no backward pass popped.
+fn func_tape_reset(tapes: Tapes) -> Function {
+    let mut f = Function::new([]);
+    f.instructions()
+        .i32_const(0)
+        .global_set(tapes.globals + GLOBAL_TAPE)
+        .end();
+    f
+}
+
+/// Number of bytes currently on the tape.
+fn func_tape_ptr(tapes: Tapes) -> Function {
+    let mut f = Function::new([]);
+    f.instructions()
+        .global_get(tapes.globals + GLOBAL_TAPE)
+        .end();
+    f
+}
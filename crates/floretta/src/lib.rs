@@ -66,20 +66,34 @@
 //! [webassembly]: https://webassembly.org/
 
 mod api;
+mod explain;
 mod forward;
 mod helper;
 mod reverse;
+mod second_order;
+mod stats;
 mod util;
 mod validate;
 
 #[cfg(feature = "names")]
 mod name;
 
+#[cfg(feature = "names")]
+pub mod names;
+
+#[cfg(feature = "wasmtime")]
+mod typed;
+
+use std::borrow::Cow;
+
 use wasm_encoder::reencode;
 use wasmparser::{BinaryReaderError, Validator, WasmFeatures};
 
 pub use api::*;
 
+#[cfg(feature = "wasmtime")]
+pub use typed::ReverseTyped;
+
 #[derive(Debug, thiserror::Error)]
 enum ErrorImpl {
     #[error("Wasm parsing or validation error: {0}")]
@@ -88,17 +102,62 @@ enum ErrorImpl {
     #[error("code transformation error: {0}")]
     Transform(&'static str),
 
-    #[error("no import configured: {0:?} {1:?}")]
-    Import(String, String),
+    #[error("no backward import registered for \"{module}::{name}\"")]
+    Import { module: String, name: String },
+
+    #[error("no tangent import registered for \"{module}::{name}\"")]
+    TangentImport { module: String, name: String },
+
+    #[error("mapping already exists for export {name:?}")]
+    Export { name: String },
 
     #[error("Wasm reencoding error: {0}")]
     Reencode(#[from] reencode::Error),
+
+    #[error("unsupported instruction in function {function_index} at byte offset {byte_offset}: {instruction}")]
+    Unsupported {
+        function_index: u32,
+        byte_offset: u32,
+        instruction: &'static str,
+    },
+}
+
+impl ErrorImpl {
+    fn description(&self) -> Cow<'_, str> {
+        match self {
+            ErrorImpl::Parse(_) => "Wasm parsing or validation error".into(),
+            ErrorImpl::Transform(message) => (*message).into(),
+            ErrorImpl::Import { .. } => "no import configured".into(),
+            ErrorImpl::TangentImport { .. } => "no tangent import configured".into(),
+            ErrorImpl::Export { .. } => "export already configured".into(),
+            ErrorImpl::Reencode(_) => "Wasm reencoding error".into(),
+            ErrorImpl::Unsupported {
+                byte_offset,
+                instruction,
+                ..
+            } => format!("unsupported Wasm instruction: {instruction} at offset {byte_offset}")
+                .into(),
+        }
+    }
+
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ErrorImpl::Parse(_) => ErrorKind::Parse,
+            ErrorImpl::Transform(_) => ErrorKind::Transform,
+            ErrorImpl::Export { .. } => ErrorKind::Export,
+            ErrorImpl::Import { .. } | ErrorImpl::TangentImport { .. } => ErrorKind::Import,
+            ErrorImpl::Reencode(_) => ErrorKind::Reencode,
+            ErrorImpl::Unsupported { .. } => ErrorKind::UnsupportedInstruction,
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, ErrorImpl>;
 
 trait Transform {
-    fn forward(&self, config: &Autodiff, wasm_module: &[u8]) -> Result<Vec<u8>>;
+    fn clone_box(&self) -> Box<dyn Transform>;
+
+    fn forward(&self, config: &Autodiff, wasm_module: &[u8], tangents: u32) -> Result<Vec<u8>>;
 
     fn reverse(&self, config: &Autodiff, wasm_module: &[u8]) -> Result<Vec<u8>>;
 }
@@ -111,22 +170,47 @@ struct Validate;
 struct NoValidate;
 
 impl Transform for Validate {
-    fn forward(&self, config: &Autodiff, wasm_module: &[u8]) -> Result<Vec<u8>> {
-        let features = WasmFeatures::empty() | WasmFeatures::FLOATS;
-        let validator = Validator::new_with_features(features);
-        forward::transform(validator, config, wasm_module)
+    fn clone_box(&self) -> Box<dyn Transform> {
+        Box::new(Validate)
+    }
+
+    fn forward(&self, config: &Autodiff, wasm_module: &[u8], tangents: u32) -> Result<Vec<u8>> {
+        let validator = Validator::new_with_features(forward_features());
+        forward::transform(validator, config, wasm_module, tangents)
     }
 
     fn reverse(&self, config: &Autodiff, wasm_module: &[u8]) -> Result<Vec<u8>> {
-        let features = WasmFeatures::empty() | WasmFeatures::MULTI_VALUE | WasmFeatures::FLOATS;
+        let features = WasmFeatures::empty()
+            | WasmFeatures::MUTABLE_GLOBAL
+            | WasmFeatures::MULTI_VALUE
+            | WasmFeatures::BULK_MEMORY
+            | WasmFeatures::FLOATS
+            | WasmFeatures::SATURATING_FLOAT_TO_INT
+            | WasmFeatures::SIGN_EXTENSION
+            | WasmFeatures::THREADS;
         let validator = Validator::new_with_features(features);
         reverse::transform(validator, config, wasm_module)
     }
 }
 
+/// The features that forward mode supports.
+fn forward_features() -> WasmFeatures {
+    WasmFeatures::empty()
+        | WasmFeatures::MUTABLE_GLOBAL
+        | WasmFeatures::MULTI_VALUE
+        | WasmFeatures::FLOATS
+        | WasmFeatures::SATURATING_FLOAT_TO_INT
+        | WasmFeatures::SIGN_EXTENSION
+        | WasmFeatures::THREADS
+}
+
 impl Transform for NoValidate {
-    fn forward(&self, config: &Autodiff, wasm_module: &[u8]) -> Result<Vec<u8>> {
-        forward::transform((), config, wasm_module)
+    fn clone_box(&self) -> Box<dyn Transform> {
+        Box::new(NoValidate)
+    }
+
+    fn forward(&self, config: &Autodiff, wasm_module: &[u8], tangents: u32) -> Result<Vec<u8>> {
+        forward::transform((), config, wasm_module, tangents)
     }
 
     fn reverse(&self, config: &Autodiff, wasm_module: &[u8]) -> Result<Vec<u8>> {
@@ -1,163 +1,19 @@
-use std::{
-    borrow::Cow,
-    collections::{HashMap, HashSet},
-};
+use std::{borrow::Cow, collections::HashMap};
 
-use regex::Regex;
 use wasm_encoder::NameSection;
 use wasmparser::{IndirectNaming, Name, NameSectionReader, Naming};
 
 use crate::{
+    forward::ModuleInfo,
     helper::{
-        helper_functions, helper_globals, helper_memories, helper_types, OFFSET_FUNCTIONS,
-        OFFSET_GLOBALS, OFFSET_MEMORIES, OFFSET_TYPES,
+        globalidx, helper_functions, helper_globals, helper_memories, helper_types, memidx, Tapes,
+        OFFSET_FUNCTIONS, OFFSET_TYPES,
     },
+    names::{NameGen, NameSet},
     reverse::StackHeight,
-    util::{LocalMap, NumImports},
+    util::{u32_to_usize, LocalMap, NumImports},
 };
 
-struct NameNumbers {
-    base_available: bool,
-    taken: HashSet<u32>,
-    mex: u32,
-}
-
-impl NameNumbers {
-    fn new() -> Self {
-        Self {
-            base_available: true,
-            taken: HashSet::new(),
-            mex: 2,
-        }
-    }
-
-    fn insert_base(&mut self) -> Option<u32> {
-        if self.base_available {
-            self.base_available = false;
-            None
-        } else {
-            Some(self.insert_number(self.mex))
-        }
-    }
-
-    fn insert_number(&mut self, number: u32) -> u32 {
-        if self.taken.insert(number) {
-            number
-        } else {
-            while self.taken.contains(&self.mex) {
-                self.mex += 1;
-            }
-            self.mex
-        }
-    }
-
-    fn insert(&mut self, number: Option<u32>) -> Option<u32> {
-        match number {
-            Some(n) => Some(self.insert_number(n)),
-            None => self.insert_base(),
-        }
-    }
-}
-
-struct Decomposition<'a> {
-    name: &'a str,
-    base: &'a str,
-    number: Option<u32>,
-}
-
-impl<'a> Decomposition<'a> {
-    fn recompose(&self, numbers: &mut NameNumbers) -> Cow<'a, str> {
-        let number = numbers.insert(self.number);
-        if number == self.number {
-            Cow::Borrowed(self.name)
-        } else {
-            let n = number.unwrap();
-            Cow::Owned(format!("{}_{n}", self.base))
-        }
-    }
-}
-
-/// A set of names that can efficiently give a name not in the set but similar to a name in the set.
-pub struct NameSet<'a> {
-    re: Regex,
-    names: HashMap<&'a str, NameNumbers>,
-}
-
-impl Default for NameSet<'_> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl<'a> NameSet<'a> {
-    /// Empty set of names.
-    pub fn new() -> Self {
-        Self {
-            re: Regex::new(r"^(.*)_(\d+)$").unwrap(),
-            names: HashMap::new(),
-        }
-    }
-
-    fn decompose<'b>(&self, name: &'b str) -> Decomposition<'b> {
-        if let Some(caps) = self.re.captures(name) {
-            if let Ok(n) = caps[2].parse() {
-                return Decomposition {
-                    name,
-                    base: caps.get(1).unwrap().as_str(),
-                    number: Some(n),
-                };
-            }
-        }
-        Decomposition {
-            name,
-            base: name,
-            number: None,
-        }
-    }
-
-    /// Insert a name into the set.
-    ///
-    /// If `name` was not already in the set, it is returned as [`Cow::Borrowed`]. Otherwise, a
-    /// similar name that wasn't previously in the set is returned as [`Cow::Owned`].
-    pub fn insert(&mut self, name: &'a str) -> Cow<'a, str> {
-        let decomp = self.decompose(name);
-        let numbers = self
-            .names
-            .entry(decomp.base)
-            .or_insert_with(NameNumbers::new);
-        decomp.recompose(numbers)
-    }
-
-    /// Done adding names from the original source; transition to the next phase.
-    pub fn done(self) -> NameGen<'a> {
-        NameGen { inner: self }
-    }
-}
-
-#[derive(Default)]
-pub struct NameGen<'a> {
-    inner: NameSet<'a>,
-}
-
-impl NameGen<'_> {
-    /// Insert a name into the set if possible.
-    ///
-    /// If `name` was not already in the set, it is returned as [`Cow::Borrowed`]. Otherwise, a
-    /// similar name that wasn't previously in the set is returned as [`Cow::Owned`].
-    ///
-    /// If `name` is composed of some base string followed by an underscore and then a nonnegative
-    /// integer less than [`u32::MAX`], the returned name is inserted if and only if some other name
-    /// with the same base string was already present. Otherwise, the returned name is inserted if
-    /// and only if `name` was already present.
-    pub fn insert<'b>(&mut self, name: &'b str) -> Cow<'b, str> {
-        let decomp = self.inner.decompose(name);
-        match self.inner.names.get_mut(decomp.base) {
-            Some(numbers) => decomp.recompose(numbers),
-            None => Cow::Borrowed(name),
-        }
-    }
-}
-
 pub trait FuncInfo {
     fn num_imports(&self) -> NumImports;
 
@@ -170,6 +26,8 @@ pub trait FuncInfo {
     fn stack_locals(&self, funcidx: u32) -> StackHeight;
 
     fn branch_locals(&self, funcidx: u32) -> StackHeight;
+
+    fn basic_blocks(&self, funcidx: u32) -> u32;
 }
 
 #[derive(Default)]
@@ -177,33 +35,41 @@ pub struct Names<'a> {
     section: NameSection,
     function_map: wasm_encoder::NameMap,
     function_gen: NameGen<'a>,
-    locals_map: wasm_encoder::IndirectNameMap,
-    locals_maps: HashMap<u32, (wasm_encoder::NameMap, NameGen<'a>)>,
+    locals_maps: HashMap<u32, (wasm_encoder::NameMap, wasm_encoder::NameMap, NameGen<'a>)>,
+    labels_maps: HashMap<u32, wasm_encoder::NameMap>,
     types_map: wasm_encoder::NameMap,
     types_gen: NameGen<'a>,
+    tables_map: wasm_encoder::NameMap,
     memories_map: wasm_encoder::NameMap,
     memories_gen: NameGen<'a>,
     globals_map: wasm_encoder::NameMap,
     globals_gen: NameGen<'a>,
+    elements_map: wasm_encoder::NameMap,
 }
 
 impl<'a> Names<'a> {
-    pub fn new(functions: impl FuncInfo, reader: NameSectionReader<'a>) -> crate::Result<Self> {
+    pub fn new(
+        functions: impl FuncInfo,
+        reader: NameSectionReader<'a>,
+        suffix: &str,
+    ) -> crate::Result<Self> {
         let mut section = NameSection::new();
         let mut function_map = wasm_encoder::NameMap::new();
         let mut function_set = Some(NameSet::new());
         let mut function_gen = None;
-        let mut locals_map = wasm_encoder::IndirectNameMap::new();
         let mut locals_maps = HashMap::new();
+        let mut labels_maps = HashMap::new();
         let mut types_map = wasm_encoder::NameMap::new();
         let mut types_set = Some(NameSet::new());
         let mut types_gen = None;
+        let mut tables_map = wasm_encoder::NameMap::new();
         let mut memories_map = wasm_encoder::NameMap::new();
         let mut memories_set = Some(NameSet::new());
         let mut memories_gen = None;
         let mut globals_map = wasm_encoder::NameMap::new();
         let mut globals_set = Some(NameSet::new());
         let mut globals_gen = None;
+        let mut elements_map = wasm_encoder::NameMap::new();
         for entry in reader {
             match entry? {
                 Name::Module {
@@ -229,7 +95,7 @@ impl<'a> Names<'a> {
                             funcidx += OFFSET_FUNCTIONS;
                         }
                         function_map
-                            .append(funcidx, &function_names.insert(&format!("{name}_bwd")));
+                            .append(funcidx, &function_names.insert(&format!("{name}{suffix}")));
                     }
                     function_gen = Some(function_names);
                 }
@@ -252,12 +118,23 @@ impl<'a> Names<'a> {
                             }
                             local_names.insert(name);
                         }
-                        let mut funcidx = 2 * index;
-                        if index >= functions.num_imports().func {
-                            funcidx += OFFSET_FUNCTIONS;
+                        locals_maps.insert(index, (locals_fwd, locals_bwd, local_names.done()));
+                    }
+                }
+                Name::Label(functions_in) => {
+                    // The forward pass has the same blocks as the original function, but the
+                    // backward pass has a different structure altogether.
+                    for function in functions_in {
+                        let mut labels = wasm_encoder::NameMap::new();
+                        let IndirectNaming {
+                            index,
+                            names: labels_in,
+                        } = function?;
+                        for label in labels_in {
+                            let Naming { index, name } = label?;
+                            labels.append(index, name);
                         }
-                        locals_map.append(funcidx, &locals_fwd);
-                        locals_maps.insert(index, (locals_bwd, local_names.done()));
+                        labels_maps.insert(index, labels);
                     }
                 }
                 Name::Type(types_in) => {
@@ -272,24 +149,41 @@ impl<'a> Names<'a> {
                         let Naming { index, name } = ty?;
                         types_map.append(
                             OFFSET_TYPES + 2 * index + 1,
-                            &type_names.insert(&format!("{name}_bwd")),
+                            &type_names.insert(&format!("{name}{suffix}")),
                         );
                     }
                     types_gen = Some(type_names);
                 }
+                Name::Table(tables_in) => {
+                    // Like memories, each table is followed by another for the backward passes.
+                    let mut table_names = NameSet::new();
+                    for table in tables_in.clone() {
+                        let Naming { index, name } = table?;
+                        tables_map.append(2 * index, name);
+                        table_names.insert(name);
+                    }
+                    let mut table_names = table_names.done();
+                    for table in tables_in {
+                        let Naming { index, name } = table?;
+                        tables_map.append(
+                            2 * index + 1,
+                            &table_names.insert(&format!("{name}{suffix}")),
+                        );
+                    }
+                }
                 Name::Memory(memories_in) => {
                     let mut memory_names = memories_set.take().unwrap();
                     for memory in memories_in.clone() {
                         let Naming { index, name } = memory?;
-                        memories_map.append(OFFSET_MEMORIES + 2 * index, name);
+                        memories_map.append(memidx(functions.num_imports(), index), name);
                         memory_names.insert(name);
                     }
                     let mut memory_names = memory_names.done();
                     for memory in memories_in {
                         let Naming { index, name } = memory?;
                         memories_map.append(
-                            OFFSET_MEMORIES + 2 * index + 1,
-                            &memory_names.insert(&format!("{name}_bwd")),
+                            memidx(functions.num_imports(), index) + 1,
+                            &memory_names.insert(&format!("{name}{suffix}")),
                         );
                     }
                     memories_gen = Some(memory_names);
@@ -298,11 +192,29 @@ impl<'a> Names<'a> {
                     let mut global_names = globals_set.take().unwrap();
                     for global in globals_in {
                         let Naming { index, name } = global?;
-                        globals_map.append(OFFSET_GLOBALS + index, name);
+                        globals_map.append(globalidx(functions.num_imports(), index), name);
                         global_names.insert(name);
                     }
                     globals_gen = Some(global_names.done());
                 }
+                Name::Element(elements_in) => {
+                    // Each element segment is split into one for the forward passes and another
+                    // for the backward passes.
+                    let mut element_names = NameSet::new();
+                    for element in elements_in.clone() {
+                        let Naming { index, name } = element?;
+                        elements_map.append(2 * index, name);
+                        element_names.insert(name);
+                    }
+                    let mut element_names = element_names.done();
+                    for element in elements_in {
+                        let Naming { index, name } = element?;
+                        elements_map.append(
+                            2 * index + 1,
+                            &element_names.insert(&format!("{name}{suffix}")),
+                        );
+                    }
+                }
                 _ => {} // TODO
             }
         }
@@ -310,14 +222,16 @@ impl<'a> Names<'a> {
             section,
             function_map,
             function_gen: function_gen.unwrap_or_default(),
-            locals_map,
             locals_maps,
+            labels_maps,
             types_map,
             types_gen: types_gen.unwrap_or_default(),
+            tables_map,
             memories_map,
             memories_gen: memories_gen.unwrap_or_default(),
             globals_map,
             globals_gen: globals_gen.unwrap_or_default(),
+            elements_map,
         })
     }
 }
@@ -327,17 +241,19 @@ pub fn name_section(functions: impl FuncInfo, names: Option<Names>) -> NameSecti
         mut section,
         mut function_map,
         mut function_gen,
-        mut locals_map,
         mut locals_maps,
+        mut labels_maps,
         mut types_map,
         mut types_gen,
+        tables_map,
         mut memories_map,
         mut memories_gen,
         mut globals_map,
         mut globals_gen,
+        elements_map,
     } = names.unwrap_or_default();
 
-    for (index, (name, ..)) in (0..).zip(helper_functions()) {
+    for (index, (name, ..)) in (0..).zip(helper_functions(Tapes::default())) {
         function_map.append(
             2 * functions.num_imports().func + index,
             &function_gen.insert(name),
@@ -345,18 +261,53 @@ pub fn name_section(functions: impl FuncInfo, names: Option<Names>) -> NameSecti
     }
     section.functions(&function_map);
 
+    let mut locals_map = wasm_encoder::IndirectNameMap::new();
     for index in 0..functions.num_functions() {
-        let (locals, local_names) = locals_maps
-            .entry(index)
-            .or_insert_with(|| (wasm_encoder::NameMap::new(), NameGen::default()));
+        let (locals_fwd, locals, local_names) = locals_maps.entry(index).or_insert_with(|| {
+            (
+                wasm_encoder::NameMap::new(),
+                wasm_encoder::NameMap::new(),
+                NameGen::default(),
+            )
+        });
+        let local_map = functions.locals(index);
+        let is_import = index < functions.num_imports().func;
+        let mut funcidx = 2 * index;
+        if !is_import {
+            funcidx += OFFSET_FUNCTIONS;
+            // The forward pass ends with `tmp_f32`, `tmp_f64`, and three `i32` locals, in that
+            // order.
+            let mut fwd_names = local_names.clone();
+            let tmps = local_map
+                .keys_flat()
+                .skip(u32_to_usize(local_map.count_keys() - 5));
+            let names = ["tmp_f32", "tmp_f64", "tmp_i32", "tmp_i32_1", "tmp_i32_2"];
+            for ((local_index, _), name) in tmps.zip(names) {
+                locals_fwd.append(local_index, &fwd_names.insert(name));
+            }
+            locals_map.append(funcidx, locals_fwd);
+        }
         let num_float_results = functions.num_float_results(index);
         for i in 0..num_float_results {
             locals.append(i, &local_names.insert(&format!("result_{i}")));
         }
-        let mut local_index = num_float_results + functions.locals(index).count_vals();
+        let mut local_index = num_float_results + local_map.count_vals();
+        if !is_import {
+            // Only the float temporaries have backward-pass counterparts in the local map.
+            locals.append(local_index - 2, &local_names.insert("tmp_f32"));
+            locals.append(local_index - 1, &local_names.insert("tmp_f64"));
+        }
         locals.append(local_index, &local_names.insert("tmp_i32"));
-        // TODO: Name `tmp_f32` and `tmp_f64` for both the forward pass and the backward pass.
         local_index += 1;
+        if !is_import {
+            // The rest of the `i32` temporaries and the `i64` one are only used by `memory.copy`.
+            for i in 1..7 {
+                locals.append(local_index, &local_names.insert(&format!("tmp_i32_{i}")));
+                local_index += 1;
+            }
+            locals.append(local_index, &local_names.insert("tmp_i64"));
+            local_index += 1;
+        }
         let stack_locals = functions.stack_locals(index);
         for i in 0..stack_locals.f32 {
             locals.append(local_index, &local_names.insert(&format!("stack_f32_{i}")));
@@ -375,98 +326,252 @@ pub fn name_section(functions: impl FuncInfo, names: Option<Names>) -> NameSecti
             locals.append(local_index, &local_names.insert(&format!("branch_f64_{i}")));
             local_index += 1;
         }
-        let mut funcidx = 2 * index + 1;
-        if index >= functions.num_imports().func {
-            funcidx += OFFSET_FUNCTIONS;
-        }
-        // This particular `if` statement should always be triggered, because there should never be
-        // local names for imported functions, but keep the conditional anyway for consistency.
-        locals_map.append(funcidx, locals);
+        locals_map.append(funcidx + 1, locals);
     }
     section.locals(&locals_map);
 
+    let mut labels_map = wasm_encoder::IndirectNameMap::new();
+    for index in functions.num_imports().func..functions.num_functions() {
+        let funcidx = OFFSET_FUNCTIONS + 2 * index;
+        if let Some(labels_fwd) = labels_maps.remove(&index) {
+            labels_map.append(funcidx, &labels_fwd);
+        }
+        // The backward pass starts with a `loop` containing one `block` per basic block plus one
+        // more for invalid basic block indices; branching out of the `block` with label `k + 1`
+        // runs basic block `k`.
+        let n = functions.basic_blocks(index);
+        if n > 0 {
+            let mut labels_bwd = wasm_encoder::NameMap::new();
+            labels_bwd.append(0, "dispatch");
+            for k in 0..n {
+                labels_bwd.append(k + 1, &format!("bb_{k}"));
+            }
+            labels_bwd.append(n + 1, "invalid");
+            labels_map.append(funcidx + 1, &labels_bwd);
+        }
+    }
+    section.labels(&labels_map);
+
     for (index, (name, ..)) in (0..).zip(helper_types()) {
         types_map.append(index, &types_gen.insert(name));
     }
     section.types(&types_map);
 
-    for (index, (name, ..)) in (0..).zip(helper_memories()) {
+    section.tables(&tables_map);
+
+    let tapes = Tapes::new(functions.num_imports());
+    for (index, (name, ..)) in (tapes.memories..).zip(helper_memories(0)) {
         memories_map.append(index, &memories_gen.insert(name));
     }
     section.memories(&memories_map);
 
-    for (index, (name, ..)) in (0..).zip(helper_globals()) {
+    for (index, (name, ..)) in (tapes.globals..).zip(helper_globals()) {
         globals_map.append(index, &globals_gen.insert(name));
     }
     section.globals(&globals_map);
 
+    section.elements(&elements_map);
+
     section
 }
 
-#[cfg(test)]
-mod tests {
-    use std::borrow::Cow;
-
-    use super::NameSet;
+/// Suffix for names of tangent counterparts in the forward-mode name section.
+const TANGENT_SUFFIX: &str = "_tangent";
 
-    #[test]
-    fn test_no_number() {
-        let mut names = NameSet::new();
-        let output = names.insert("foo");
-        assert_eq!(output, "foo");
+/// Name of the tangent in lane `lane` of a float named `name`, numbering all but the first tangent
+/// like duplicate names.
+fn tangent_name(name: &str, lane: u32) -> String {
+    match lane {
+        1 => format!("{name}{TANGENT_SUFFIX}"),
+        _ => format!("{name}{TANGENT_SUFFIX}_{lane}"),
     }
+}
 
-    #[test]
-    fn test_duplicates() {
-        let mut names = NameSet::new();
-        let output1 = names.insert("foo");
-        let output2 = names.insert("foo");
-        let output3 = names.insert("foo");
-        assert_eq!(output1, "foo");
-        assert_eq!(output2, "foo_2");
-        assert_eq!(output3, "foo_3");
+/// Sort a list of names by index and collect them into a name map.
+fn name_map(mut names: Vec<(u32, Cow<str>)>) -> wasm_encoder::NameMap {
+    names.sort_by_key(|&(index, _)| index);
+    let mut map = wasm_encoder::NameMap::new();
+    for (index, name) in names {
+        map.append(index, &name);
     }
+    map
+}
 
-    #[test]
-    fn test_base() {
-        let mut names = NameSet::new();
-        let output1 = names.insert("foo_1");
-        let output2 = names.insert("foo");
-        assert_eq!(output1, "foo_1");
-        assert_eq!(output2, "foo");
+/// Name a forward-mode output module: everything keeps its name from the input, and each tangent
+/// gets the name of its primal with [`TANGENT_SUFFIX`] appended, numbered if there are several.
+pub fn forward_name_section(
+    module: ModuleInfo,
+    reader: Option<NameSectionReader>,
+) -> crate::Result<NameSection> {
+    let mut section = NameSection::new();
+    let mut functions_in = Vec::new();
+    let mut locals_in = HashMap::new();
+    let mut labels_in = Vec::new();
+    let mut types_in = Vec::new();
+    let mut memories_in = Vec::new();
+    let mut globals_in = Vec::new();
+    for entry in reader.into_iter().flatten() {
+        match entry? {
+            Name::Module {
+                name,
+                name_range: _,
+            } => section.module(name),
+            Name::Function(names) => {
+                for naming in names {
+                    let Naming { index, name } = naming?;
+                    functions_in.push((index, name));
+                }
+            }
+            Name::Local(functions) => {
+                for function in functions {
+                    let IndirectNaming { index, names } = function?;
+                    let mut locals = Vec::new();
+                    for naming in names {
+                        let Naming { index, name } = naming?;
+                        locals.push((index, name));
+                    }
+                    locals_in.insert(index, locals);
+                }
+            }
+            Name::Label(functions) => {
+                for function in functions {
+                    let IndirectNaming { index, names } = function?;
+                    let mut labels = wasm_encoder::NameMap::new();
+                    for naming in names {
+                        let Naming { index, name } = naming?;
+                        labels.append(index, name);
+                    }
+                    labels_in.push((index, labels));
+                }
+            }
+            Name::Type(names) => {
+                for naming in names {
+                    let Naming { index, name } = naming?;
+                    types_in.push((index, name));
+                }
+            }
+            Name::Memory(names) => {
+                for naming in names {
+                    let Naming { index, name } = naming?;
+                    memories_in.push((index, name));
+                }
+            }
+            Name::Global(names) => {
+                for naming in names {
+                    let Naming { index, name } = naming?;
+                    globals_in.push((index, name));
+                }
+            }
+            _ => {} // TODO
+        }
     }
 
-    #[test]
-    fn test_gap() {
-        let mut names = NameSet::new();
-        let output1 = names.insert("foo");
-        let output2 = names.insert("foo_3");
-        let output3 = names.insert("foo_5");
-        let output4 = names.insert("foo");
-        let output5 = names.insert("foo");
-        assert_eq!(output1, "foo");
-        assert_eq!(output2, "foo_3");
-        assert_eq!(output3, "foo_5");
-        assert_eq!(output4, "foo_2");
-        assert_eq!(output5, "foo_4");
+    // Functions keep their indices, and there are no helper functions.
+    section.functions(&name_map(
+        functions_in
+            .into_iter()
+            .map(|(index, name)| (index, Cow::Borrowed(name)))
+            .collect(),
+    ));
+
+    let mut locals_map = wasm_encoder::IndirectNameMap::new();
+    for (index, info) in (module.num_func_imports..).zip(module.functions) {
+        let mut names = Vec::new();
+        let mut local_names = NameSet::new();
+        let locals = locals_in.remove(&index).unwrap_or_default();
+        for &(local, name) in &locals {
+            names.push((info.local_indices[u32_to_usize(local)], Cow::Borrowed(name)));
+            local_names.insert(name);
+        }
+        let mut local_names = local_names.done();
+        for &(local, name) in &locals {
+            let i = info.local_indices[u32_to_usize(local)];
+            if info.local_types[u32_to_usize(local)].is_float() {
+                for lane in 1..=module.tangents {
+                    let tangent = tangent_name(name, lane);
+                    names.push((
+                        i + lane,
+                        Cow::Owned(local_names.insert(&tangent).into_owned()),
+                    ));
+                }
+            }
+        }
+        let tmps = ["tmp_f64", "tmp_f32"].into_iter().flat_map(|base| {
+            (0..info.num_tmp).map(move |j| match j {
+                0 => base.to_string(),
+                _ => format!("{base}_{j}"),
+            })
+        });
+        for (i, name) in (info.tmp..).zip(tmps.chain(["tmp_i32".to_string()])) {
+            names.push((i, Cow::Owned(local_names.insert(&name).into_owned())));
+        }
+        locals_map.append(index, &name_map(names));
     }
+    section.locals(&locals_map);
 
-    #[test]
-    fn test_big_number() {
-        let mut names = NameSet::new();
-        let input = format!("foo_{}", u64::from(u32::MAX) + 1);
-        let output1 = names.insert(&input);
-        let output2 = names.insert(&input);
-        assert_eq!(output1, "foo_4294967296");
-        assert_eq!(output2, "foo_4294967296_2");
+    // Blocks keep their labels because the forward-mode function has the same structure.
+    let mut labels_map = wasm_encoder::IndirectNameMap::new();
+    for (index, labels) in labels_in {
+        labels_map.append(index, &labels);
     }
+    section.labels(&labels_map);
 
-    #[test]
-    fn test_number_borrowed() {
-        let mut names = NameSet::new();
-        match names.insert("foo_1") {
-            Cow::Borrowed(_) => {}
-            Cow::Owned(_) => panic!(),
+    let mut type_names = NameSet::new();
+    let mut types = Vec::new();
+    for &(index, name) in &types_in {
+        types.push((index, Cow::Borrowed(name)));
+        type_names.insert(name);
+    }
+    let mut type_names = type_names.done();
+    let lane_types = match module.tangents {
+        1 => ["pair_f32", "pair_f64"],
+        _ => ["lanes_f32", "lanes_f64"],
+    };
+    for (index, name) in (module.num_types..).zip(lane_types) {
+        types.push((index, type_names.insert(name)));
+    }
+    section.types(&name_map(types));
+
+    let mut memory_names = NameSet::new();
+    let mut memories = Vec::new();
+    let width = 1 + module.tangents;
+    for &(index, name) in &memories_in {
+        memories.push((width * index, Cow::Borrowed(name)));
+        memory_names.insert(name);
+    }
+    let mut memory_names = memory_names.done();
+    for &(index, name) in &memories_in {
+        for lane in 1..=module.tangents {
+            let tangent = tangent_name(name, lane);
+            memories.push((
+                width * index + lane,
+                Cow::Owned(memory_names.insert(&tangent).into_owned()),
+            ));
         }
     }
+    section.memories(&name_map(memories));
+
+    let mut global_names = NameSet::new();
+    let mut globals = Vec::new();
+    for &(index, name) in &globals_in {
+        let i = module.global_indices[u32_to_usize(index)];
+        globals.push((i, Cow::Borrowed(name)));
+        global_names.insert(name);
+    }
+    let mut global_names = global_names.done();
+    for &(index, name) in &globals_in {
+        if module.global_types[u32_to_usize(index)].is_float() {
+            let i = module.global_indices[u32_to_usize(index)];
+            for lane in 1..=module.tangents {
+                let tangent = tangent_name(name, lane);
+                globals.push((
+                    i + lane,
+                    Cow::Owned(global_names.insert(&tangent).into_owned()),
+                ));
+            }
+        }
+    }
+    section.globals(&name_map(globals));
+
+    Ok(section)
 }
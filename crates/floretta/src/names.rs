@@ -0,0 +1,255 @@
+//! Generate unique names that stay close to the names they're based on.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use regex::Regex;
+
+#[derive(Clone)]
+struct NameNumbers {
+    base_available: bool,
+    taken: HashSet<u32>,
+    mex: u32,
+}
+
+impl NameNumbers {
+    fn new() -> Self {
+        Self {
+            base_available: true,
+            taken: HashSet::new(),
+            mex: 2,
+        }
+    }
+
+    fn insert_base(&mut self) -> Option<u32> {
+        if self.base_available {
+            self.base_available = false;
+            None
+        } else {
+            Some(self.insert_number(self.mex))
+        }
+    }
+
+    fn insert_number(&mut self, number: u32) -> u32 {
+        if self.taken.insert(number) {
+            number
+        } else {
+            while self.taken.contains(&self.mex) {
+                self.mex += 1;
+            }
+            self.mex
+        }
+    }
+
+    fn contains(&self, number: Option<u32>) -> bool {
+        match number {
+            Some(n) => self.taken.contains(&n),
+            None => !self.base_available,
+        }
+    }
+
+    fn insert(&mut self, number: Option<u32>) -> Option<u32> {
+        match number {
+            Some(n) => Some(self.insert_number(n)),
+            None => self.insert_base(),
+        }
+    }
+}
+
+struct Decomposition<'a> {
+    name: &'a str,
+    base: &'a str,
+    number: Option<u32>,
+}
+
+impl<'a> Decomposition<'a> {
+    fn recompose(&self, numbers: &mut NameNumbers) -> Cow<'a, str> {
+        let number = numbers.insert(self.number);
+        if number == self.number {
+            Cow::Borrowed(self.name)
+        } else {
+            let n = number.unwrap();
+            Cow::Owned(format!("{}_{n}", self.base))
+        }
+    }
+}
+
+/// A set of names that can efficiently give a name not in the set but similar to a name in the set.
+#[derive(Clone)]
+pub struct NameSet<'a> {
+    re: Regex,
+    names: HashMap<&'a str, NameNumbers>,
+}
+
+impl Default for NameSet<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> NameSet<'a> {
+    /// Empty set of names.
+    pub fn new() -> Self {
+        Self {
+            re: Regex::new(r"^(.*)_(\d+)$").unwrap(),
+            names: HashMap::new(),
+        }
+    }
+
+    fn decompose<'b>(&self, name: &'b str) -> Decomposition<'b> {
+        if let Some(caps) = self.re.captures(name) {
+            if let Ok(n) = caps[2].parse() {
+                return Decomposition {
+                    name,
+                    base: caps.get(1).unwrap().as_str(),
+                    number: Some(n),
+                };
+            }
+        }
+        Decomposition {
+            name,
+            base: name,
+            number: None,
+        }
+    }
+
+    /// Whether `name` is already in the set.
+    pub fn contains(&self, name: &str) -> bool {
+        let decomp = self.decompose(name);
+        self.names
+            .get(decomp.base)
+            .is_some_and(|numbers| numbers.contains(decomp.number))
+    }
+
+    /// Insert a name into the set.
+    ///
+    /// If `name` was not already in the set, it is returned as [`Cow::Borrowed`]. Otherwise, a
+    /// similar name that wasn't previously in the set is returned as [`Cow::Owned`].
+    pub fn insert(&mut self, name: &'a str) -> Cow<'a, str> {
+        let decomp = self.decompose(name);
+        let numbers = self
+            .names
+            .entry(decomp.base)
+            .or_insert_with(NameNumbers::new);
+        decomp.recompose(numbers)
+    }
+
+    /// Done adding names from the original source; transition to the next phase.
+    pub fn done(self) -> NameGen<'a> {
+        NameGen { inner: self }
+    }
+}
+
+/// A [`NameSet`] that no longer accepts names from the original source, only generated names.
+#[derive(Clone, Default)]
+pub struct NameGen<'a> {
+    inner: NameSet<'a>,
+}
+
+impl NameGen<'_> {
+    /// Whether `name` is already in the set.
+    pub fn contains(&self, name: &str) -> bool {
+        self.inner.contains(name)
+    }
+
+    /// Insert a name into the set if possible.
+    ///
+    /// If `name` was not already in the set, it is returned as [`Cow::Borrowed`]. Otherwise, a
+    /// similar name that wasn't previously in the set is returned as [`Cow::Owned`].
+    ///
+    /// If `name` is composed of some base string followed by an underscore and then a nonnegative
+    /// integer less than [`u32::MAX`], the returned name is inserted if and only if some other name
+    /// with the same base string was already present. Otherwise, the returned name is inserted if
+    /// and only if `name` was already present.
+    pub fn insert<'b>(&mut self, name: &'b str) -> Cow<'b, str> {
+        let decomp = self.inner.decompose(name);
+        match self.inner.names.get_mut(decomp.base) {
+            Some(numbers) => decomp.recompose(numbers),
+            None => Cow::Borrowed(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::NameSet;
+
+    #[test]
+    fn test_no_number() {
+        let mut names = NameSet::new();
+        let output = names.insert("foo");
+        assert_eq!(output, "foo");
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut names = NameSet::new();
+        let output1 = names.insert("foo");
+        let output2 = names.insert("foo");
+        let output3 = names.insert("foo");
+        assert_eq!(output1, "foo");
+        assert_eq!(output2, "foo_2");
+        assert_eq!(output3, "foo_3");
+    }
+
+    #[test]
+    fn test_base() {
+        let mut names = NameSet::new();
+        let output1 = names.insert("foo_1");
+        let output2 = names.insert("foo");
+        assert_eq!(output1, "foo_1");
+        assert_eq!(output2, "foo");
+    }
+
+    #[test]
+    fn test_gap() {
+        let mut names = NameSet::new();
+        let output1 = names.insert("foo");
+        let output2 = names.insert("foo_3");
+        let output3 = names.insert("foo_5");
+        let output4 = names.insert("foo");
+        let output5 = names.insert("foo");
+        assert_eq!(output1, "foo");
+        assert_eq!(output2, "foo_3");
+        assert_eq!(output3, "foo_5");
+        assert_eq!(output4, "foo_2");
+        assert_eq!(output5, "foo_4");
+    }
+
+    #[test]
+    fn test_big_number() {
+        let mut names = NameSet::new();
+        let input = format!("foo_{}", u64::from(u32::MAX) + 1);
+        let output1 = names.insert(&input);
+        let output2 = names.insert(&input);
+        assert_eq!(output1, "foo_4294967296");
+        assert_eq!(output2, "foo_4294967296_2");
+    }
+
+    #[test]
+    fn test_contains() {
+        let mut names = NameSet::new();
+        names.insert("foo");
+        names.insert("foo");
+        names.insert("bar_3");
+        assert!(names.contains("foo"));
+        assert!(names.contains("foo_2"));
+        assert!(!names.contains("foo_3"));
+        assert!(names.contains("bar_3"));
+        assert!(!names.contains("bar"));
+        assert!(!names.contains("baz"));
+    }
+
+    #[test]
+    fn test_number_borrowed() {
+        let mut names = NameSet::new();
+        match names.insert("foo_1") {
+            Cow::Borrowed(_) => {}
+            Cow::Owned(_) => panic!(),
+        }
+    }
+}
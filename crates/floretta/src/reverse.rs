@@ -1,23 +1,32 @@
 #[cfg(test)]
 mod tests;
 
-use std::ops::Sub;
+use std::{convert::Infallible, ops::Sub};
 
+use hashbrown::{HashMap, HashSet};
 use wasm_encoder::{
     reencode::{Reencode, RoundtripReencoder},
-    CodeSection, Encode, ExportKind, ExportSection, Function, FunctionSection, GlobalSection,
-    ImportSection, InstructionSink, MemorySection, Module, TypeSection,
+    CodeSection, ConstExpr, CustomSection, DataCountSection, DataSection, ElementSection, Elements,
+    Encode, ExportKind, ExportSection, Function, FunctionSection, GlobalSection, ImportSection,
+    InstructionSink, MemorySection, Module, StartSection, TableSection, TypeSection,
+};
+use wasmparser::{
+    Data, DataKind, Element, ElementItems, ElementKind, FunctionBody, Global, Import, KnownCustom,
+    Operator, Parser, Payload, Table, TableInit, TypeRef,
 };
-use wasmparser::{FunctionBody, Global, Import, Operator, Parser, Payload, TypeRef};
 
 use crate::{
     helper::{
-        helper_functions, helper_globals, helper_memories, helper_types, FuncOffsets,
-        OFFSET_FUNCTIONS, OFFSET_GLOBALS, OFFSET_MEMORIES, OFFSET_TYPES, TYPE_DISPATCH,
+        globalidx, helper_functions, helper_globals, helper_memories, helper_types, memidx,
+        FuncOffsets, Tapes, OFFSET_FUNCTIONS, OFFSET_GLOBALS, OFFSET_MEMORIES, OFFSET_TYPES,
+        TYPE_DISPATCH, TYPE_NULLARY, TYPE_TAPE_I32_BWD,
+    },
+    util::{
+        numeric_signature, operator_name, u32_to_usize, BlockType, FuncTypes, LocalMap, NumImports,
+        TwoStrs, TypeMap, ValType,
     },
-    util::{u32_to_usize, BlockType, FuncTypes, LocalMap, NumImports, TwoStrs, TypeMap, ValType},
     validate::{FunctionValidator, ModuleValidator},
-    Autodiff, ErrorImpl,
+    Autodiff, ErrorImpl, UnsupportedInstructionPolicy,
 };
 
 pub fn transform(
@@ -28,32 +37,39 @@ pub fn transform(
     let mut types = TypeSection::new();
     let mut imports = ImportSection::new();
     let mut functions = FunctionSection::new();
+    let mut tables = TableSection::new();
     let mut memories = MemorySection::new();
     let mut globals = GlobalSection::new();
     let mut exports = ExportSection::new();
+    let mut start = None;
+    let mut elements = ElementSection::new();
+    let mut data_count = None;
     let mut code = CodeSection::new();
+    let mut data = DataSection::new();
+    let mut customs = Vec::new();
+    // Exported backward passes that need a wrapper to drop some of their results.
+    let mut partials = Vec::new();
     for (_, ty) in helper_types() {
         types.ty().func_type(&ty);
     }
-    for (_, memory) in helper_memories() {
+    for (_, memory) in helper_memories(config.tape_initial_pages) {
         memories.memory(memory);
     }
     for (_, ty, init) in helper_globals() {
         globals.global(ty, &init);
     }
-    for (_, i, f) in helper_functions() {
-        functions.function(i);
-        code.function(&f);
-    }
     assert_eq!(types.len(), OFFSET_TYPES);
     assert_eq!(memories.len(), OFFSET_MEMORIES);
     assert_eq!(globals.len(), OFFSET_GLOBALS);
-    assert_eq!(functions.len(), OFFSET_FUNCTIONS);
-    assert_eq!(code.len(), OFFSET_FUNCTIONS);
     let mut type_sigs = FuncTypes::new();
     let mut num_imports = NumImports::default();
+    let mut num_memories = 0;
+    let mut global_types = Vec::new();
+    let mut global_adjoints = Vec::new();
     let mut func_types = Vec::new();
     let mut func_infos = Vec::new();
+    // Functions whose bodies are copied as-is instead of differentiated.
+    let mut backwards = HashMap::new();
 
     #[cfg(feature = "names")]
     let mut names = None;
@@ -63,31 +79,30 @@ pub fn transform(
             Payload::TypeSection(section) => {
                 validator.type_section(&section)?;
                 for ty in section.into_iter_err_on_gc_types() {
-                    let typeidx = type_sigs.push(ty?)?;
+                    type_sigs.push(ty?)?;
+                }
+                for (_, params, results) in type_sigs.iter() {
                     // Forward pass: same type as the original function. All the adjoint values are
                     // assumed to be zero.
                     types.ty().function(
-                        type_sigs.params(typeidx).iter().map(|&ty| ty.into()),
-                        type_sigs.results(typeidx).iter().map(|&ty| ty.into()),
+                        params.iter().map(|&ty| ty.into()),
+                        results.iter().map(|&ty| ty.into()),
                     );
                     // Backward pass: results become parameters, and parameters become results.
                     // Also, integers disappear from function types in the backward pass.
-                    types.ty().function(
-                        tuple(type_sigs.results(typeidx)),
-                        tuple(type_sigs.params(typeidx)),
-                    );
+                    types.ty().function(tuple(results), tuple(params));
                 }
             }
             Payload::ImportSection(section) => {
                 validator.import_section(&section)?;
+                // The adjoints of imported globals go after all the imported globals, so that the
+                // imported globals themselves keep their indices.
+                let mut adjoints = Vec::new();
                 for import in section {
                     let Import { module, name, ty } = import?;
-                    let (module_bwd, name_bwd) = config
-                        .imports
-                        .get(&TwoStrs(module, name))
-                        .ok_or_else(|| ErrorImpl::Import(module.to_string(), name.to_string()))?;
                     match ty {
                         TypeRef::Func(typeidx) => {
+                            let (module_bwd, name_bwd) = import_bwd(config, module, name)?;
                             num_imports.func += 1;
                             let mapped = OFFSET_TYPES + 2 * typeidx;
                             let fwd = wasm_encoder::EntityType::Function(mapped);
@@ -100,17 +115,67 @@ pub fn transform(
                                 locals: LocalMap::new(type_map()),
                                 stack_locals: StackHeight::new(),
                                 branch_locals: StackHeight::new(),
+                                basic_blocks: 0,
                             });
                         }
-                        TypeRef::Table(_) => unimplemented!(),
-                        TypeRef::Memory(_) => unimplemented!(),
-                        TypeRef::Global(_) => unimplemented!(),
-                        TypeRef::Tag(_) => unimplemented!(),
+                        TypeRef::Memory(memory_ty) => {
+                            let (module_bwd, name_bwd) = import_bwd(config, module, name)?;
+                            num_imports.memory += 1;
+                            let memory_type = RoundtripReencoder.memory_type(memory_ty);
+                            imports.import(module, name, memory_type);
+                            imports.import(module_bwd, name_bwd, memory_type);
+                        }
+                        TypeRef::Global(global_ty) => {
+                            if global_ty.shared {
+                                return Err(ErrorImpl::Transform("shared globals are unsupported"));
+                            }
+                            num_imports.global += 1;
+                            let val_type = ValType::try_from(global_ty.content_type)?;
+                            imports.import(
+                                module,
+                                name,
+                                RoundtripReencoder.global_type(global_ty)?,
+                            );
+                            global_types.push(val_type);
+                            // Just like for globals defined in the module, only mutable float
+                            // globals need adjoints.
+                            if global_ty.mutable && val_type.is_float() {
+                                let (module_bwd, name_bwd) = import_bwd(config, module, name)?;
+                                global_adjoints.push(Some(u32::try_from(adjoints.len()).unwrap()));
+                                adjoints.push((module_bwd, name_bwd, val_type));
+                            } else {
+                                global_adjoints.push(None);
+                            }
+                        }
+                        TypeRef::Table(table_ty) => {
+                            // Like a table defined in the module, an imported table gets a
+                            // counterpart to hold the backward passes of its functions.
+                            let (module_bwd, name_bwd) = import_bwd(config, module, name)?;
+                            let table_type = RoundtripReencoder.table_type(table_ty)?;
+                            imports.import(module, name, table_type);
+                            imports.import(module_bwd, name_bwd, table_type);
+                        }
+                        TypeRef::Tag(_) => {
+                            return Err(ErrorImpl::Transform("exception handling is unsupported"))
+                        }
                     }
                 }
+                for index in global_adjoints.iter_mut().flatten() {
+                    *index += num_imports.global;
+                }
+                num_imports.global_adjoint = u32::try_from(adjoints.len()).unwrap();
+                for (module_bwd, name_bwd, val_type) in adjoints {
+                    let global_type = wasm_encoder::GlobalType {
+                        val_type: val_type.into(),
+                        mutable: true,
+                        shared: false,
+                    };
+                    imports.import(module_bwd, name_bwd, global_type);
+                }
             }
             Payload::FunctionSection(section) => {
                 validator.function_section(&section)?;
+                shared_helper_functions(&mut functions, &mut code, num_imports);
                 for type_index in section {
                     let typeidx = type_index?;
                     // Index arithmetic to account for the fact that we split each original
@@ -121,6 +186,26 @@ pub fn transform(
                     func_types.push(typeidx);
                 }
             }
+            Payload::TableSection(section) => {
+                validator.table_section(&section)?;
+                let mut reencoder = IndexReencoder { num_imports };
+                for table in section {
+                    let Table { ty, init } = table?;
+                    let table_type = RoundtripReencoder.table_type(ty)?;
+                    // Duplicate the table to hold the backward passes of its functions.
+                    match init {
+                        TableInit::RefNull => {
+                            tables.table(table_type);
+                            tables.table(table_type);
+                        }
+                        TableInit::Expr(expr) => {
+                            let (fwd, bwd) = element_exprs(&mut reencoder, expr)?;
+                            tables.table_with_init(table_type, &fwd);
+                            tables.table_with_init(table_type, &bwd);
+                        }
+                    }
+                }
+            }
             Payload::MemorySection(section) => {
                 validator.memory_section(&section)?;
                 for memory_ty in section {
@@ -128,41 +213,57 @@ pub fn transform(
                     memories.memory(memory_type);
                     // Duplicate the memory to store adjoint values.
                     memories.memory(memory_type);
+                    num_memories += 1;
                 }
             }
             Payload::GlobalSection(section) => {
                 validator.global_section(&section)?;
+                let num_globals = section.count();
+                let mut adjoints = Vec::new();
+                let mut reencoder = IndexReencoder { num_imports };
                 for global in section {
                     let Global { ty, init_expr } = global?;
-                    if ty.mutable {
-                        unimplemented!("mutable globals");
-                    }
                     if ty.shared {
-                        unimplemented!("shared globals");
-                    }
-                    let mut ce = wasm_encoder::ConstExpr::empty();
-                    let mut reader = init_expr.get_operators_reader();
-                    while !reader.is_end_then_eof() {
-                        match reader.read()? {
-                            Operator::I32Const { value } => ce = ce.with_i32_const(value),
-                            Operator::I64Const { value } => ce = ce.with_i64_const(value),
-                            Operator::F32Const { value } => ce = ce.with_f32_const(value.into()),
-                            Operator::F64Const { value } => ce = ce.with_f64_const(value.into()),
-                            op => unimplemented!("{op:?}"),
-                        };
+                        return Err(ErrorImpl::Transform("shared globals are unsupported"));
                     }
+                    let ce = reencoder.const_expr(init_expr)?;
+                    let val_type = ValType::try_from(ty.content_type)?;
                     globals.global(
                         wasm_encoder::GlobalType {
-                            val_type: ValType::try_from(ty.content_type)?.into(),
-                            mutable: false,
+                            val_type: val_type.into(),
+                            mutable: ty.mutable,
                             shared: false,
                         },
                         &ce,
                     );
+                    global_types.push(val_type);
+                    // Immutable globals are constants, so only mutable float globals need adjoints.
+                    if ty.mutable && val_type.is_float() {
+                        let index = globalidx(num_imports, num_imports.global + num_globals)
+                            + u32::try_from(adjoints.len()).unwrap();
+                        global_adjoints.push(Some(index));
+                        adjoints.push(val_type);
+                    } else {
+                        global_adjoints.push(None);
+                    }
+                }
+                // The adjoint globals go right after all the globals from the original module.
+                for val_type in adjoints {
+                    let global_type = wasm_encoder::GlobalType {
+                        val_type: val_type.into(),
+                        mutable: true,
+                        shared: false,
+                    };
+                    match val_type {
+                        ValType::I32 | ValType::I64 => unreachable!(),
+                        ValType::F32 => globals.global(global_type, &ConstExpr::f32_const(0.)),
+                        ValType::F64 => globals.global(global_type, &ConstExpr::f64_const(0.)),
+                    };
                 }
             }
             Payload::ExportSection(section) => {
                 validator.export_section(&section)?;
+                let mut func_exports = HashMap::new();
                 for export in section {
                     let e = export?;
                     let kind = RoundtripReencoder.export_kind(e.kind);
@@ -175,56 +276,323 @@ pub fn transform(
                                 funcidx += OFFSET_FUNCTIONS;
                             }
                             exports.export(e.name, kind, funcidx);
-                            if let Some(name) = config.exports.get(e.name) {
-                                exports.export(name, kind, funcidx + 1);
+                            func_exports.insert(e.name, e.index);
+                            if config.skip.contains(e.name) {
+                                backwards.insert(e.index, Backward::Zero);
+                            }
+                            if let Some(name) = config.export_name(e.name) {
+                                match config.partials.get(e.name) {
+                                    Some(indices) => partials.push((
+                                        name,
+                                        funcidx + 1,
+                                        func_types[u32_to_usize(e.index)],
+                                        indices,
+                                    )),
+                                    None => {
+                                        exports.export(&name, kind, funcidx + 1);
+                                    }
+                                }
                             }
                         }
                         ExportKind::Memory => {
-                            let memidx = OFFSET_MEMORIES + 2 * e.index;
+                            let memidx = memidx(num_imports, e.index);
                             exports.export(e.name, kind, memidx);
                             if let Some(name) = config.exports.get(e.name) {
                                 exports.export(name, kind, memidx + 1);
                             }
                         }
+                        ExportKind::Table => {
+                            let tableidx = 2 * e.index;
+                            exports.export(e.name, kind, tableidx);
+                            if let Some(name) = config.exports.get(e.name) {
+                                exports.export(name, kind, tableidx + 1);
+                            }
+                        }
+                        ExportKind::Global => {
+                            exports.export(e.name, kind, globalidx(num_imports, e.index));
+                            let adjoint = global_adjoints[u32_to_usize(e.index)];
+                            if let (Some(name), Some(index)) = (config.exports.get(e.name), adjoint)
+                            {
+                                exports.export(name, kind, index);
+                            }
+                        }
                         _ => {
                             exports.export(e.name, kind, e.index);
                         }
                     }
                 }
+                for (primal, derivative) in &config.custom_gradients {
+                    if let (Some(&f), Some(&g)) = (
+                        func_exports.get(primal.as_str()),
+                        func_exports.get(derivative.as_str()),
+                    ) {
+                        backwards.insert(f, Backward::Custom(g));
+                        backwards.entry(g).or_insert(Backward::Zero);
+                    }
+                }
+            }
+            Payload::StartSection { func, range } => {
+                validator.start_section(func, &range)?;
+                start = Some(func);
+            }
+            Payload::ElementSection(section) => {
+                validator.element_section(&section)?;
+                let mut reencoder = IndexReencoder { num_imports };
+                for element in section {
+                    let Element { kind, items, .. } = element?;
+                    // Each segment gets a counterpart holding the backward passes of its functions,
+                    // so segment indices double just like table indices.
+                    let (fwd, bwd) = match items {
+                        ElementItems::Functions(reader) => {
+                            let mut fwd = Vec::new();
+                            let mut bwd = Vec::new();
+                            for funcidx in reader {
+                                let mapped = reencoder.function_index(funcidx?);
+                                fwd.push(mapped);
+                                bwd.push(mapped + 1);
+                            }
+                            (
+                                Elements::Functions(fwd.into()),
+                                Elements::Functions(bwd.into()),
+                            )
+                        }
+                        ElementItems::Expressions(ref_type, reader) => {
+                            let ref_type = reencoder.ref_type(ref_type)?;
+                            let mut fwd = Vec::new();
+                            let mut bwd = Vec::new();
+                            for expr in reader {
+                                let (expr_fwd, expr_bwd) = element_exprs(&mut reencoder, expr?)?;
+                                fwd.push(expr_fwd);
+                                bwd.push(expr_bwd);
+                            }
+                            (
+                                Elements::Expressions(ref_type, fwd.into()),
+                                Elements::Expressions(ref_type, bwd.into()),
+                            )
+                        }
+                    };
+                    match kind {
+                        ElementKind::Passive => {
+                            elements.passive(fwd);
+                            elements.passive(bwd);
+                        }
+                        ElementKind::Declared => {
+                            elements.declared(fwd);
+                            elements.declared(bwd);
+                        }
+                        ElementKind::Active {
+                            table_index,
+                            offset_expr,
+                        } => {
+                            let offset = reencoder.const_expr(offset_expr)?;
+                            let tableidx = reencoder.table_index(table_index.unwrap_or(0));
+                            elements.active(Some(tableidx), &offset, fwd);
+                            elements.active(Some(tableidx + 1), &offset, bwd);
+                        }
+                    }
+                }
+            }
+            Payload::DataCountSection { count, range } => {
+                validator.data_count_section(count, &range)?;
+                data_count = Some(DataCountSection { count });
             }
             Payload::CodeSectionEntry(body) => {
                 let func = validator.code_section_entry(&body)?;
                 let index = func_infos.len().try_into().unwrap();
-                let (info, fwd, bwd) =
-                    function(func, &type_sigs, num_imports, &func_types, index, body)?;
+                let helpers = if config.tape_per_function {
+                    let num_functions = u32::try_from(func_types.len()).unwrap() - num_imports.func;
+                    FuncOffsets::partition(num_imports, num_functions, index - num_imports.func)
+                } else {
+                    FuncOffsets::new(num_imports)
+                };
+                let (info, fwd, bwd) = function(
+                    func,
+                    &type_sigs,
+                    num_imports,
+                    helpers,
+                    &func_types,
+                    &global_types,
+                    &global_adjoints,
+                    config.error_policy,
+                    backwards
+                        .get(&index)
+                        .copied()
+                        .unwrap_or(Backward::Differentiate),
+                    index,
+                    body,
+                )?;
                 func_infos.push(info);
                 code.raw(&fwd);
                 code.raw(&bwd);
             }
-
-            #[cfg(feature = "names")]
-            Payload::CustomSection(section) => {
-                if let wasmparser::KnownCustom::Name(reader) = section.as_known() {
+            Payload::DataSection(section) => {
+                validator.data_section(&section)?;
+                let mut reencoder = IndexReencoder { num_imports };
+                for segment in section {
+                    let Data {
+                        kind, data: bytes, ..
+                    } = segment?;
+                    // Only the primal memories get initialized; their adjoints start out zeroed.
+                    match kind {
+                        DataKind::Passive => data.passive(bytes.iter().copied()),
+                        DataKind::Active {
+                            memory_index,
+                            offset_expr,
+                        } => data.active(
+                            reencoder.memory_index(memory_index),
+                            &reencoder.const_expr(offset_expr)?,
+                            bytes.iter().copied(),
+                        ),
+                    };
+                }
+            }
+            Payload::CustomSection(section) => match section.as_known() {
+                #[cfg(feature = "names")]
+                KnownCustom::Name(reader) => {
                     if config.names {
                         names = Some(crate::name::Names::new(
                             (&type_sigs, num_imports, func_infos.as_slice()),
                             reader,
+                            &config.backward_suffix,
                         )?);
                     }
                 }
-            }
+                // The indices in the name section refer to the input module, so it can't be copied.
+                #[cfg(not(feature = "names"))]
+                KnownCustom::Name(_) => {}
+                _ => customs.push(CustomSection {
+                    name: section.name().into(),
+                    data: section.data().into(),
+                }),
+            },
 
             other => validator.payload(&other)?,
         }
     }
+    // A module with no functions of its own has no function section, so we might not have emitted
+    // the helper functions yet.
+    shared_helper_functions(&mut functions, &mut code, num_imports);
+    if config.tape_per_function {
+        // Each function defined in the module gets its own copy of the tape memory, tape
+        // global, and helper functions, placed after all the ones from the original module.
+        let num_functions = u32::try_from(func_types.len()).unwrap() - num_imports.func;
+        // This includes the tape globals and the adjoint globals.
+        let num_globals = num_imports.global + num_imports.global_adjoint + globals.len();
+        for index in 0..num_functions {
+            let tapes = Tapes {
+                memories: memidx(num_imports, num_imports.memory + num_memories)
+                    + OFFSET_MEMORIES * index,
+                globals: num_globals + OFFSET_GLOBALS * index,
+            };
+            for (_, memory) in helper_memories(config.tape_initial_pages) {
+                memories.memory(memory);
+            }
+            for (_, ty, init) in helper_globals() {
+                globals.global(ty, &init);
+            }
+            for (_, i, f) in helper_functions(tapes) {
+                functions.function(i);
+                code.function(&f);
+            }
+        }
+    }
+    // If every function has its own tape, we need new functions to reset them all at once or to
+    // add up their sizes; otherwise we can just export the helpers for the shared tape.
+    let num_functions = u32::try_from(func_types.len()).unwrap() - num_imports.func;
+    let partitions: Vec<_> = (0..num_functions)
+        .map(|index| FuncOffsets::partition(num_imports, num_functions, index))
+        .collect();
+    if let Some(name) = &config.tape_reset {
+        let funcidx = if config.tape_per_function {
+            let mut f = Function::new([]);
+            for helpers in &partitions {
+                f.instructions().call(helpers.tape_reset());
+            }
+            f.instructions().end();
+            extra_function(&mut functions, &mut code, num_imports, TYPE_NULLARY, &f)
+        } else {
+            FuncOffsets::new(num_imports).tape_reset()
+        };
+        exports.export(name, ExportKind::Func, funcidx);
+    }
+    if let Some(name) = &config.tape_pointer {
+        let funcidx = if config.tape_per_function {
+            let mut f = Function::new([]);
+            f.instructions().i32_const(0);
+            for helpers in &partitions {
+                f.instructions().call(helpers.tape_ptr()).i32_add();
+            }
+            f.instructions().end();
+            extra_function(
+                &mut functions,
+                &mut code,
+                num_imports,
+                TYPE_TAPE_I32_BWD,
+                &f,
+            )
+        } else {
+            FuncOffsets::new(num_imports).tape_ptr()
+        };
+        exports.export(name, ExportKind::Func, funcidx);
+    }
+    for (name, bwd, typeidx, indices) in partials {
+        let export = partial_backward(&type_sigs, typeidx, indices)?;
+        let ty = types.len();
+        types.ty().function(
+            tuple(type_sigs.results(typeidx)),
+            export.results.iter().copied(),
+        );
+        let funcidx = extra_function(
+            &mut functions,
+            &mut code,
+            num_imports,
+            ty,
+            &export.function(bwd),
+        );
+        exports.export(&name, ExportKind::Func, funcidx);
+    }
+    let start = start.map(|func| {
+        // The start function runs once at instantiation, and nothing can ever call its backward
+        // pass, so we run its forward pass and then throw away whatever it put on the tape.
+        let mut f = Function::new([]);
+        f.instructions()
+            .call(IndexReencoder { num_imports }.function_index(func));
+        if func >= num_imports.func {
+            let helpers = if config.tape_per_function {
+                partitions[u32_to_usize(func - num_imports.func)]
+            } else {
+                FuncOffsets::new(num_imports)
+            };
+            f.instructions().call(helpers.tape_reset());
+        }
+        f.instructions().end();
+        StartSection {
+            function_index: extra_function(
+                &mut functions,
+                &mut code,
+                num_imports,
+                TYPE_NULLARY,
+                &f,
+            ),
+        }
+    });
     let mut module = Module::new();
     module.section(&types);
     module.section(&imports);
     module.section(&functions);
+    module.section(&tables);
     module.section(&memories);
     module.section(&globals);
     module.section(&exports);
+    if let Some(start) = &start {
+        module.section(start);
+    }
+    module.section(&elements);
+    if let Some(data_count) = &data_count {
+        module.section(data_count);
+    }
     module.section(&code);
+    module.section(&data);
 
     #[cfg(feature = "names")]
     if config.names {
@@ -233,10 +601,132 @@ pub fn transform(
             names,
         ));
     }
+    for custom in &customs {
+        module.section(custom);
+    }
 
     Ok(module.finish())
 }
 
+/// Emit the helper functions for the tape shared by the whole module, unless we already have.
+///
+/// These refer to the tape memories and globals, whose indices depend on how many memories and
+/// globals are imported, so this must happen after the import section.
+fn shared_helper_functions(
+    functions: &mut FunctionSection,
+    code: &mut CodeSection,
+    num_imports: NumImports,
+) {
+    if functions.is_empty() {
+        for (_, i, f) in helper_functions(Tapes::new(num_imports)) {
+            functions.function(i);
+            code.function(&f);
+        }
+        assert_eq!(functions.len(), OFFSET_FUNCTIONS);
+        assert_eq!(code.len(), OFFSET_FUNCTIONS);
+    }
+}
+
+/// Add a function after all the others in the module, and return its index.
+fn extra_function(
+    functions: &mut FunctionSection,
+    code: &mut CodeSection,
+    num_imports: NumImports,
+    typeidx: u32,
+    f: &Function,
+) -> u32 {
+    let funcidx = 2 * num_imports.func + functions.len();
+    functions.function(typeidx);
+    code.function(f);
+    funcidx
+}
+
+/// A wrapper around a backward pass that returns only some of its parameter adjoints.
+struct PartialBackward {
+    /// Number of parameters of the backward pass, one for each floating-point result.
+    arity: u32,
+
+    /// Types of the results of the backward pass, one for each floating-point parameter.
+    adjoints: Vec<wasm_encoder::ValType>,
+
+    /// For each result of the wrapper, its index among the results of the backward pass.
+    selected: Vec<u32>,
+
+    /// Types of the results of the wrapper.
+    results: Vec<wasm_encoder::ValType>,
+}
+
+impl PartialBackward {
+    fn function(&self, bwd: u32) -> Function {
+        // Stash every adjoint in a local, then put back only the ones we want.
+        let mut f = Function::new_with_locals_types(self.adjoints.iter().copied());
+        for i in 0..self.arity {
+            f.instructions().local_get(i);
+        }
+        f.instructions().call(bwd);
+        for i in (0..u32::try_from(self.adjoints.len()).unwrap()).rev() {
+            f.instructions().local_set(self.arity + i);
+        }
+        for &i in &self.selected {
+            f.instructions().local_get(self.arity + i);
+        }
+        f.instructions().end();
+        f
+    }
+}
+
+/// Check the parameter `indices` for the function type at `typeidx`, and figure out how to pick
+/// their adjoints out of the results of its backward pass.
+fn partial_backward(
+    type_sigs: &FuncTypes,
+    typeidx: u32,
+    indices: &[u32],
+) -> crate::Result<PartialBackward> {
+    let params = type_sigs.params(typeidx);
+    let mut selected = Vec::new();
+    let mut results = Vec::new();
+    for &index in indices {
+        let ty = *params
+            .get(u32_to_usize(index))
+            .ok_or(ErrorImpl::Transform("parameter index out of bounds"))?;
+        if !ty.is_float() {
+            return Err(ErrorImpl::Transform(
+                "integer parameters have no adjoints to differentiate",
+            ));
+        }
+        let position = params[..u32_to_usize(index)]
+            .iter()
+            .filter(|ty| ty.is_float())
+            .count();
+        selected.push(u32::try_from(position).unwrap());
+        results.push(ty.into());
+    }
+    Ok(PartialBackward {
+        arity: u32::try_from(tuple(type_sigs.results(typeidx)).len()).unwrap(),
+        adjoints: tuple(params),
+        selected,
+        results,
+    })
+}
+
+/// Look up the configured import for the backward pass of an imported function, or for the adjoint
+/// of an imported memory or global.
+fn import_bwd<'a>(
+    config: &'a Autodiff,
+    module: &str,
+    name: &str,
+) -> crate::Result<(&'a str, &'a str)> {
+    let (module_bwd, name_bwd) =
+        config
+            .imports
+            .get(&TwoStrs(module, name))
+            .ok_or_else(|| ErrorImpl::Import {
+                module: module.to_string(),
+                name: name.to_string(),
+            })?;
+    Ok((module_bwd, name_bwd))
+}
+
 /// Remove all integer types for the backward pass.
 fn tuple(val_types: &[ValType]) -> Vec<wasm_encoder::ValType> {
     val_types
@@ -260,6 +750,9 @@ struct FunctionInfo {
     locals: LocalMap,
     stack_locals: StackHeight,
     branch_locals: StackHeight,
+
+    /// Number of basic blocks dispatched by the backward pass, or zero if it doesn't dispatch.
+    basic_blocks: u32,
 }
 
 #[cfg(feature = "names")]
@@ -293,13 +786,23 @@ impl crate::name::FuncInfo for (&FuncTypes, NumImports, &[FunctionInfo]) {
     fn branch_locals(&self, funcidx: u32) -> StackHeight {
         self.2[u32_to_usize(funcidx)].branch_locals
     }
+
+    fn basic_blocks(&self, funcidx: u32) -> u32 {
+        self.2[u32_to_usize(funcidx)].basic_blocks
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn function(
     mut validator: impl FunctionValidator,
     type_sigs: &FuncTypes,
     num_imports: NumImports,
+    helpers: FuncOffsets,
     func_types: &[u32],
+    global_types: &[ValType],
+    global_adjoints: &[Option<u32>],
+    error_policy: UnsupportedInstructionPolicy,
+    backward: Backward,
     funcidx: u32,
     body: FunctionBody,
 ) -> crate::Result<(FunctionInfo, Vec<u8>, Vec<u8>)> {
@@ -328,16 +831,34 @@ fn function(
     locals.push(1, ValType::F32);
     let (tmp_f64_fwd, tmp_f64_bwd) = (locals.count_keys(), num_float_results + locals.count_vals());
     locals.push(1, ValType::F64);
+    // Most instructions only need one `i32` local in the forward pass, but `memory.copy` and
+    // `memory.fill` need to hold onto all three of their operands.
     let tmp_i32_fwd = locals.count_keys();
-    locals.push(1, ValType::I32);
+    locals.push(3, ValType::I32);
     // We added a single-local entry for each parameter from the original function type, so when we
     // encode the rest of the locals, we need to skip over the parameters.
     let fwd = Function::new(locals.keys().skip(params.len()));
-    let mut bwd = ReverseFunction::new(num_imports, num_float_results);
+    let mut bwd = ReverseFunction::new(helpers, num_float_results);
     for (count, ty) in locals.vals() {
         bwd.locals(count, ty);
     }
+    // Likewise, `memory.copy` needs seven `i32` locals and an `i64` local in the backward pass.
     let tmp_i32_bwd = bwd.local(ValType::I32);
+    bwd.locals(6, ValType::I32);
+    bwd.locals(1, ValType::I64);
+    if let Backward::Zero | Backward::Custom(_) = backward {
+        return opaque_function(
+            validator,
+            body,
+            type_sigs,
+            num_imports,
+            func_types,
+            backward,
+            typeidx,
+            locals,
+            fwd,
+        );
+    }
     // The first basic block in the forward pass corresponds to the last basic block in the backward
     // pass, and because each basic block will be reversed, the first instructions we write will
     // become the last instructions in the function body of the backward pass. Because Wasm
@@ -356,13 +877,19 @@ fn function(
     let mut func = Func {
         type_sigs,
         num_imports,
+        helpers,
         func_types,
+        global_types,
+        global_adjoints,
+        error_policy,
+        funcidx,
         num_float_results,
         locals,
         offset: 0, // This initial value should be unused; to be set before each instruction.
         operand_stack: Vec::new(),
         operand_stack_height: StackHeight::new(),
         operand_stack_height_min: 0,
+        unreachable: false,
         control_stack: vec![Control::Block(BlockType::Func(typeidx))],
         fwd,
         bwd,
@@ -394,12 +921,431 @@ fn function(
             locals: func.locals,
             stack_locals: func.bwd.max_stack_values,
             branch_locals: func.bwd.max_branch_values,
+            basic_blocks: func.bwd.basic_blocks.len().try_into().unwrap(),
         },
         func.fwd.into_raw_body(),
         func.bwd.into_raw_body(&func.operand_stack),
     ))
 }
 
+/// How to produce the backward pass of a function.
+#[derive(Clone, Copy)]
+enum Backward {
+    /// Differentiate the function body.
+    Differentiate,
+
+    /// Copy the function body as-is, and return zero for every parameter adjoint; see
+    /// [`Autodiff::skip`].
+    Zero,
+
+    /// Copy the function body as-is, and call the function at this index with the result adjoints;
+    /// see [`Autodiff::custom_gradient`].
+    Custom(u32),
+}
+
+/// Forward and backward passes for a function whose body is copied as-is instead of
+/// differentiated.
+#[allow(clippy::too_many_arguments)]
+fn opaque_function(
+    mut validator: impl FunctionValidator,
+    body: FunctionBody,
+    type_sigs: &FuncTypes,
+    num_imports: NumImports,
+    func_types: &[u32],
+    backward: Backward,
+    typeidx: u32,
+    locals: LocalMap,
+    mut fwd: Function,
+) -> crate::Result<(FunctionInfo, Vec<u8>, Vec<u8>)> {
+    let mut reencoder = IndexReencoder { num_imports };
+    let mut operators_reader = body.get_operators_reader()?;
+    while !operators_reader.eof() {
+        let (op, offset) = operators_reader.read_with_offset()?;
+        validator.op(offset, &op)?;
+        fwd.instruction(&reencoder.instruction(op)?);
+    }
+    validator.finish(operators_reader.original_position())?;
+    // The backward pass declares the same locals as usual so that the name section still lines up,
+    // including the seven `i32` temporaries, even though it doesn't use any of them.
+    let bwd_locals: Vec<_> = locals
+        .vals()
+        .chain([(7, ValType::I32)])
+        .map(|(count, ty)| (count, ty.into()))
+        .collect();
+    let mut bwd = Function::new(bwd_locals);
+    let params = type_sigs.params(typeidx);
+    match backward {
+        Backward::Custom(derivative) => {
+            let float_params: Vec<ValType> =
+                params.iter().copied().filter(|ty| ty.is_float()).collect();
+            let float_results: Vec<ValType> = type_sigs
+                .results(typeidx)
+                .iter()
+                .copied()
+                .filter(|ty| ty.is_float())
+                .collect();
+            let derivative_typeidx = func_types[u32_to_usize(derivative)];
+            if type_sigs.params(derivative_typeidx) != float_results
+                || type_sigs.results(derivative_typeidx) != float_params
+            {
+                return Err(ErrorImpl::Transform(
+                    "custom gradient type doesn't match the backward pass",
+                ));
+            }
+            for i in 0..float_results.len().try_into().unwrap() {
+                bwd.instructions().local_get(i);
+            }
+            // The derivative is never differentiated itself, so its forward pass is just the
+            // original function.
+            let funcidx = IndexReencoder { num_imports }.function_index(derivative);
+            bwd.instructions().call(funcidx);
+        }
+        _ => {
+            for &param in params {
+                match param {
+                    ValType::F32 => bwd.instructions().f32_const(0.),
+                    ValType::F64 => bwd.instructions().f64_const(0.),
+                    _ => continue,
+                };
+            }
+        }
+    }
+    bwd.instructions().end();
+    let info = FunctionInfo {
+        typeidx,
+        locals,
+        stack_locals: StackHeight::new(),
+        branch_locals: StackHeight::new(),
+        basic_blocks: 0,
+    };
+    Ok((info, fwd.into_raw_body(), bwd.into_raw_body()))
+}
+
+/// Copies the instructions of an opaque function into its forward pass, or the offset of a data
+/// segment, adjusting indices to match the output module.
+struct IndexReencoder {
+    num_imports: NumImports,
+}
+
+impl Reencode for IndexReencoder {
+    type Error = Infallible;
+
+    fn data_index(&mut self, data: u32) -> u32 {
+        data
+    }
+
+    fn element_index(&mut self, element: u32) -> u32 {
+        2 * element
+    }
+
+    fn function_index(&mut self, func: u32) -> u32 {
+        let mut funcidx = 2 * func;
+        if func >= self.num_imports.func {
+            funcidx += OFFSET_FUNCTIONS;
+        }
+        funcidx
+    }
+
+    fn global_index(&mut self, global: u32) -> u32 {
+        globalidx(self.num_imports, global)
+    }
+
+    fn memory_index(&mut self, memory: u32) -> u32 {
+        memidx(self.num_imports, memory)
+    }
+
+    fn table_index(&mut self, table: u32) -> u32 {
+        2 * table
+    }
+
+    fn type_index(&mut self, ty: u32) -> u32 {
+        OFFSET_TYPES + 2 * ty
+    }
+}
+
+/// Forward-pass and backward-pass counterparts of a constant expression for a table element, which
+/// refer to the forward and backward passes of the same function.
+fn element_exprs(
+    reencoder: &mut IndexReencoder,
+    expr: wasmparser::ConstExpr,
+) -> crate::Result<(ConstExpr, ConstExpr)> {
+    let mut reader = expr.get_operators_reader();
+    match (reader.read()?, reader.is_end_then_eof()) {
+        (Operator::RefFunc { function_index }, true) => {
+            let funcidx = reencoder.function_index(function_index);
+            Ok((
+                ConstExpr::ref_func(funcidx),
+                ConstExpr::ref_func(funcidx + 1),
+            ))
+        }
+        (Operator::RefNull { hty }, true) => {
+            let heap_type = reencoder.heap_type(hty)?;
+            Ok((
+                ConstExpr::ref_null(heap_type),
+                ConstExpr::ref_null(heap_type),
+            ))
+        }
+        _ => Err(ErrorImpl::Transform(
+            "element expressions other than `ref.func` and `ref.null` are unsupported",
+        )),
+    }
+}
+
+/// Names of the [`Operator`] variants that [`Func::instruction`] supports, in the same order as its
+/// `match` arms; a test checks this against every operator that `wasmparser` knows about.
+pub const SUPPORTED_INSTRUCTIONS: &[&str] = &[
+    "Block",
+    "Loop",
+    "If",
+    "Else",
+    "End",
+    "Br",
+    "Nop",
+    "Unreachable",
+    "Return",
+    "BrTable",
+    "BrIf",
+    "Call",
+    "CallIndirect",
+    "Drop",
+    "Select",
+    "TypedSelect",
+    "LocalGet",
+    "LocalSet",
+    "LocalTee",
+    "GlobalGet",
+    "GlobalSet",
+    "F32Load",
+    "F64Load",
+    "F32Store",
+    "F64Store",
+    "I32Load",
+    "I64Load",
+    "I32Load8S",
+    "I32Load8U",
+    "I32Load16S",
+    "I32Load16U",
+    "I64Load8S",
+    "I64Load8U",
+    "I64Load16S",
+    "I64Load16U",
+    "I64Load32S",
+    "I64Load32U",
+    "I32Store",
+    "I64Store",
+    "I32Store8",
+    "I32Store16",
+    "I64Store8",
+    "I64Store16",
+    "I64Store32",
+    "MemorySize",
+    "MemoryGrow",
+    "MemoryCopy",
+    "MemoryFill",
+    "MemoryAtomicNotify",
+    "MemoryAtomicWait32",
+    "MemoryAtomicWait64",
+    "AtomicFence",
+    "I32Const",
+    "I64Const",
+    "F32Const",
+    "F64Const",
+    "I32Eqz",
+    "I32Eq",
+    "I32Ne",
+    "I32LtS",
+    "I32LtU",
+    "I32GtS",
+    "I32GtU",
+    "I32LeS",
+    "I32LeU",
+    "I32GeS",
+    "I32GeU",
+    "I64Eqz",
+    "I64Eq",
+    "I64Ne",
+    "I64LtS",
+    "I64LtU",
+    "I64GtS",
+    "I64GtU",
+    "I64LeS",
+    "I64LeU",
+    "I64GeS",
+    "I64GeU",
+    "F32Eq",
+    "F32Ne",
+    "F32Lt",
+    "F32Gt",
+    "F32Le",
+    "F32Ge",
+    "F64Eq",
+    "F64Ne",
+    "F64Lt",
+    "F64Gt",
+    "F64Le",
+    "F64Ge",
+    "I32Clz",
+    "I32Ctz",
+    "I32Popcnt",
+    "I32Add",
+    "I32Sub",
+    "I32Mul",
+    "I32DivS",
+    "I32DivU",
+    "I32RemS",
+    "I32RemU",
+    "I32And",
+    "I32Or",
+    "I32Xor",
+    "I32Shl",
+    "I32ShrS",
+    "I32ShrU",
+    "I32Rotl",
+    "I32Rotr",
+    "I64Clz",
+    "I64Ctz",
+    "I64Popcnt",
+    "I64Add",
+    "I64Sub",
+    "I64Mul",
+    "I64DivS",
+    "I64DivU",
+    "I64RemS",
+    "I64RemU",
+    "I64And",
+    "I64Or",
+    "I64Xor",
+    "I64Shl",
+    "I64ShrS",
+    "I64ShrU",
+    "I64Rotl",
+    "I64Rotr",
+    "F32Abs",
+    "F32Neg",
+    "F32Sqrt",
+    "F32Ceil",
+    "F32Floor",
+    "F32Trunc",
+    "F32Nearest",
+    "F32Add",
+    "F32Sub",
+    "F32Mul",
+    "F32Div",
+    "F32Min",
+    "F32Max",
+    "F32Copysign",
+    "F64Abs",
+    "F64Neg",
+    "F64Sqrt",
+    "F64Ceil",
+    "F64Floor",
+    "F64Trunc",
+    "F64Nearest",
+    "F64Add",
+    "F64Sub",
+    "F64Mul",
+    "F64Div",
+    "F64Min",
+    "F64Max",
+    "F64Copysign",
+    "F32ConvertI32S",
+    "F32ConvertI32U",
+    "F32ConvertI64S",
+    "F32ConvertI64U",
+    "F32DemoteF64",
+    "F64PromoteF32",
+    "F64ConvertI32S",
+    "F64ConvertI32U",
+    "F64ConvertI64S",
+    "F64ConvertI64U",
+    "I32ReinterpretF32",
+    "I64ReinterpretF64",
+    "F32ReinterpretI32",
+    "F64ReinterpretI64",
+    "I32TruncF32S",
+    "I32TruncSatF32S",
+    "I32TruncF32U",
+    "I32TruncSatF32U",
+    "I32TruncF64S",
+    "I32TruncSatF64S",
+    "I32TruncF64U",
+    "I32TruncSatF64U",
+    "I64TruncF32S",
+    "I64TruncSatF32S",
+    "I64TruncF32U",
+    "I64TruncSatF32U",
+    "I64TruncF64S",
+    "I64TruncSatF64S",
+    "I64TruncF64U",
+    "I64TruncSatF64U",
+];
+
+/// Find all the instructions in a module that [`transform`] would reject, along with the indices
+/// of the functions containing them, without transforming anything.
+pub fn unsupported_instructions(
+    config: &Autodiff,
+    wasm_module: &[u8],
+) -> crate::Result<Vec<(u32, &'static str)>> {
+    let mut num_func_imports = 0;
+    let mut opaque = HashSet::new();
+    let mut unsupported = Vec::new();
+    let mut index = 0;
+    for payload in Parser::new(0).parse_all(wasm_module) {
+        match payload? {
+            Payload::ImportSection(section) => {
+                for import in section {
+                    if let TypeRef::Func(_) = import?.ty {
+                        num_func_imports += 1;
+                    }
+                }
+                index = num_func_imports;
+            }
+            Payload::ExportSection(section) => {
+                for export in section {
+                    let e = export?;
+                    if e.kind == wasmparser::ExternalKind::Func && config.is_opaque(e.name) {
+                        opaque.insert(e.index);
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                if !opaque.contains(&index) {
+                    let mut operators_reader = body.get_operators_reader()?;
+                    while !operators_reader.eof() {
+                        let op = operators_reader.read()?;
+                        let name = operator_name(&op);
+                        if !SUPPORTED_INSTRUCTIONS.contains(&name)
+                            && policy_signature(config.error_policy, &op).is_none()
+                        {
+                            unsupported.push((index, name));
+                        }
+                    }
+                }
+                index += 1;
+            }
+            _ => {}
+        }
+    }
+    Ok(unsupported)
+}
+
+/// Operand and result types of an instruction that [`Func::instruction`] doesn't support, if
+/// `policy` allows it anyway.
+fn policy_signature(
+    policy: UnsupportedInstructionPolicy,
+    op: &Operator,
+) -> Option<(&'static [ValType], &'static [ValType])> {
+    match policy {
+        UnsupportedInstructionPolicy::Fail => None,
+        UnsupportedInstructionPolicy::ZeroGradient => numeric_signature(op),
+        UnsupportedInstructionPolicy::Passthrough => {
+            numeric_signature(op).filter(|(params, results)| {
+                !params.iter().chain(results.iter()).any(|ty| ty.is_float())
+            })
+        }
+    }
+}
+
 struct Func<'a> {
     /// All type signatures in the module.
     type_sigs: &'a FuncTypes,
@@ -407,9 +1353,24 @@ struct Func<'a> {
     /// Number of imports in the module.
     num_imports: NumImports,
 
+    /// Indices of the helper functions to use for the tape.
+    helpers: FuncOffsets,
+
     /// Type indices for all the functions in the module.
     func_types: &'a [u32],
 
+    /// Types of all the globals in the module.
+    global_types: &'a [ValType],
+
+    /// Indices of the adjoint globals for all the mutable float globals in the module.
+    global_adjoints: &'a [Option<u32>],
+
+    /// What to do with instructions not handled by [`Func::instruction`].
+    error_policy: UnsupportedInstructionPolicy,
+
+    /// Index of this function in the input module.
+    funcidx: u32,
+
     /// Number of floating-point results in the original function type.
     num_float_results: u32,
 
@@ -427,6 +1388,9 @@ struct Func<'a> {
     /// The minimum operand stack height reached since this was last reset.
     operand_stack_height_min: usize,
 
+    /// Whether the rest of the current block is unreachable because of an unconditional branch.
+    unreachable: bool,
+
     control_stack: Vec<Control>,
 
     /// The forward pass under construction.
@@ -459,6 +1423,14 @@ impl<'a> Func<'a> {
     fn instruction(&mut self, op: Operator<'_>) -> crate::Result<()> {
         let helper = self.helpers();
         match op {
+            Operator::Block { blockty } => {
+                // Nothing can branch into the middle of a block, so unlike a loop, we don't need to
+                // start a new basic block here.
+                let block_type = BlockType::try_from(blockty)?;
+                self.control_stack.push(Control::Block(block_type));
+                let reencoded = self.blockty(block_type);
+                self.fwd.instructions().block(reencoded);
+            }
             Operator::Loop { blockty } => {
                 let block_type = BlockType::try_from(blockty)?;
                 self.control_stack.push(Control::Loop(block_type));
@@ -488,6 +1460,7 @@ impl<'a> Func<'a> {
                         block_type,
                         stack_height,
                     } => {
+                        self.end_unreachable(block_type);
                         let branch_values = self.blockty_results(block_type);
                         let branch_values_next = self.blockty_params(block_type);
                         self.split_basic_block(branch_values, stack_height, branch_values_next);
@@ -497,6 +1470,7 @@ impl<'a> Func<'a> {
             }
             Operator::End => match self.control_stack.pop().unwrap() {
                 Control::Block(block_type) => {
+                    self.end_unreachable(block_type);
                     self.fwd_control_store();
                     self.fwd.instructions().end();
                     if self.control_stack.is_empty() {
@@ -509,13 +1483,15 @@ impl<'a> Func<'a> {
                         self.split_basic_block_with_results(block_type);
                     }
                 }
-                Control::Loop(_) => {
+                Control::Loop(block_type) => {
+                    self.end_unreachable(block_type);
                     self.fwd.instructions().end();
                 }
                 Control::If {
                     block_type,
                     stack_height: _,
                 } => {
+                    self.end_unreachable(block_type);
                     self.fwd_control_store();
                     self.fwd.instructions().end();
                     self.split_basic_block_with_results(block_type);
@@ -529,6 +1505,46 @@ impl<'a> Func<'a> {
                 let stack_reset =
                     current_stack_height - u32::try_from(branch_values.len()).unwrap();
                 self.split_basic_block(branch_values, stack_reset, &[]);
+                self.unreachable = true;
+            }
+            Operator::Nop => {
+                self.fwd.instructions().nop();
+            }
+            Operator::Unreachable => {
+                // Like a branch, this ends the basic block; but since the forward pass traps, the
+                // backward pass never dispatches to it, so no values flow out of it and there's no
+                // need to store a basic block index.
+                self.fwd.instructions().unreachable();
+                let current_stack_height = self.operand_stack_height.sum();
+                self.split_basic_block(&[], current_stack_height, &[]);
+                self.unreachable = true;
+            }
+            Operator::Return => {
+                // This is just like a `br` to the outermost block, which is the function body.
+                self.fwd_control_store();
+                self.fwd.instructions().return_();
+                let relative_depth = u32::try_from(self.control_stack.len() - 1).unwrap();
+                let branch_values = self.branch_values(relative_depth);
+                let current_stack_height = self.operand_stack_height.sum();
+                let stack_reset =
+                    current_stack_height - u32::try_from(branch_values.len()).unwrap();
+                self.split_basic_block(branch_values, stack_reset, &[]);
+                self.unreachable = true;
+            }
+            Operator::BrTable { targets } => {
+                self.pop();
+                // The backward pass dispatches on the basic block we branch from, not the target,
+                // and every target takes the same values, so this is just like a `br`.
+                self.fwd_control_store();
+                let default = targets.default();
+                let targets = targets.targets().collect::<Result<Vec<u32>, _>>()?;
+                self.fwd.instructions().br_table(targets, default);
+                let branch_values = self.branch_values(default);
+                let current_stack_height = self.operand_stack_height.sum();
+                let stack_reset =
+                    current_stack_height - u32::try_from(branch_values.len()).unwrap();
+                self.split_basic_block(branch_values, stack_reset, &[]);
+                self.unreachable = true;
             }
             Operator::BrIf { relative_depth } => {
                 self.pop();
@@ -549,6 +1565,33 @@ impl<'a> Func<'a> {
                 self.fwd.instructions().call(fwd);
                 self.bwd.instructions(|insn| insn.call(bwd));
             }
+            Operator::CallIndirect {
+                type_index,
+                table_index,
+            } => {
+                self.pop();
+                for _ in self.type_sigs.params(type_index) {
+                    self.pop();
+                }
+                for &result in self.type_sigs.results(type_index) {
+                    self.push(result);
+                }
+                let typeidx = OFFSET_TYPES + 2 * type_index;
+                let tableidx = 2 * table_index;
+                // The callee pushes onto the tape too, so we wait until after the call to store the
+                // element index; that way, the backward pass reads it back before calling the
+                // backward pass of the callee.
+                self.fwd
+                    .instructions()
+                    .local_tee(self.tmp_i32_fwd)
+                    .call_indirect(tableidx, typeidx)
+                    .local_get(self.tmp_i32_fwd)
+                    .call(helper.tape_i32());
+                self.bwd.instructions(|insn| {
+                    insn.call(helper.tape_i32_bwd())
+                        .call_indirect(tableidx + 1, typeidx + 1)
+                });
+            }
             Operator::Drop => {
                 let ty = self.pop();
                 self.fwd.instructions().drop();
@@ -558,6 +1601,21 @@ impl<'a> Func<'a> {
                     ValType::F64 => self.bwd.instructions(|insn| insn.f64_const(0.)),
                 }
             }
+            Operator::Select => {
+                self.pop();
+                self.pop();
+                let ty = self.pop();
+                self.push(ty);
+                self.select(ty);
+            }
+            Operator::TypedSelect { ty } => {
+                self.pop();
+                self.pop();
+                self.pop();
+                let ty = ValType::try_from(ty)?;
+                self.push(ty);
+                self.select(ty);
+            }
             Operator::LocalGet { local_index } => {
                 let (ty, i) = self.local(local_index);
                 self.push(ty);
@@ -615,6 +1673,47 @@ impl<'a> Func<'a> {
                     }
                 }
             }
+            Operator::GlobalGet { global_index } => {
+                let ty = self.global_types[u32_to_usize(global_index)];
+                self.push(ty);
+                self.fwd
+                    .instructions()
+                    .global_get(globalidx(self.num_imports, global_index));
+                match (ty, self.global_adjoints[u32_to_usize(global_index)]) {
+                    (ValType::I32 | ValType::I64, _) => {}
+                    // An immutable global is a constant, so its adjoint goes nowhere.
+                    (ValType::F32 | ValType::F64, None) => {
+                        self.bwd.instructions(|insn| insn.drop())
+                    }
+                    (ValType::F32, Some(i)) => {
+                        self.bwd
+                            .instructions(|insn| insn.global_get(i).f32_add().global_set(i));
+                    }
+                    (ValType::F64, Some(i)) => {
+                        self.bwd
+                            .instructions(|insn| insn.global_get(i).f64_add().global_set(i));
+                    }
+                }
+            }
+            Operator::GlobalSet { global_index } => {
+                let ty = self.pop();
+                self.fwd
+                    .instructions()
+                    .global_set(globalidx(self.num_imports, global_index));
+                // Only mutable globals can be set, so every float global here has an adjoint.
+                match (ty, self.global_adjoints[u32_to_usize(global_index)]) {
+                    (ValType::I32 | ValType::I64, _) => {}
+                    (ValType::F32 | ValType::F64, None) => unreachable!(),
+                    (ValType::F32, Some(i)) => {
+                        self.bwd
+                            .instructions(|insn| insn.global_get(i).f32_const(0.).global_set(i));
+                    }
+                    (ValType::F64, Some(i)) => {
+                        self.bwd
+                            .instructions(|insn| insn.global_get(i).f64_const(0.).global_set(i));
+                    }
+                }
+            }
             Operator::F32Load { memarg } => {
                 self.pop();
                 self.push_f32();
@@ -697,6 +1796,206 @@ impl<'a> Func<'a> {
                         .f64_store(bwd)
                 });
             }
+            Operator::I32Load { memarg } => {
+                self.pop();
+                self.push_i32();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i32_load(fwd);
+            }
+            Operator::I64Load { memarg } => {
+                self.pop();
+                self.push_i64();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_load(fwd);
+            }
+            Operator::I32Load8S { memarg } => {
+                self.pop();
+                self.push_i32();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i32_load8_s(fwd);
+            }
+            Operator::I32Load8U { memarg } => {
+                self.pop();
+                self.push_i32();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i32_load8_u(fwd);
+            }
+            Operator::I32Load16S { memarg } => {
+                self.pop();
+                self.push_i32();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i32_load16_s(fwd);
+            }
+            Operator::I32Load16U { memarg } => {
+                self.pop();
+                self.push_i32();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i32_load16_u(fwd);
+            }
+            Operator::I64Load8S { memarg } => {
+                self.pop();
+                self.push_i64();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_load8_s(fwd);
+            }
+            Operator::I64Load8U { memarg } => {
+                self.pop();
+                self.push_i64();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_load8_u(fwd);
+            }
+            Operator::I64Load16S { memarg } => {
+                self.pop();
+                self.push_i64();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_load16_s(fwd);
+            }
+            Operator::I64Load16U { memarg } => {
+                self.pop();
+                self.push_i64();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_load16_u(fwd);
+            }
+            Operator::I64Load32S { memarg } => {
+                self.pop();
+                self.push_i64();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_load32_s(fwd);
+            }
+            Operator::I64Load32U { memarg } => {
+                self.pop();
+                self.push_i64();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_load32_u(fwd);
+            }
+            Operator::I32Store { memarg } => {
+                self.pop2();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i32_store(fwd);
+            }
+            Operator::I64Store { memarg } => {
+                self.pop2();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_store(fwd);
+            }
+            Operator::I32Store8 { memarg } => {
+                self.pop2();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i32_store8(fwd);
+            }
+            Operator::I32Store16 { memarg } => {
+                self.pop2();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i32_store16(fwd);
+            }
+            Operator::I64Store8 { memarg } => {
+                self.pop2();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_store8(fwd);
+            }
+            Operator::I64Store16 { memarg } => {
+                self.pop2();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_store16(fwd);
+            }
+            Operator::I64Store32 { memarg } => {
+                self.pop2();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().i64_store32(fwd);
+            }
+            Operator::MemorySize { mem } => {
+                self.push_i32();
+                let memidx = self.memidx(mem);
+                self.fwd.instructions().memory_size(memidx);
+            }
+            Operator::MemoryGrow { mem } => {
+                self.pop();
+                self.push_i32();
+                // The adjoint memory has to grow along with the primal memory, so that the backward
+                // pass can access the same addresses.
+                let memidx = self.memidx(mem);
+                self.fwd
+                    .instructions()
+                    .local_tee(self.tmp_i32_fwd)
+                    .memory_grow(memidx)
+                    .local_get(self.tmp_i32_fwd)
+                    .memory_grow(memidx + 1)
+                    .drop();
+            }
+            Operator::MemoryCopy { dst_mem, src_mem } => {
+                self.pop();
+                self.pop();
+                self.pop();
+                let (n, s, d) = (self.tmp_i32_fwd + 2, self.tmp_i32_fwd + 1, self.tmp_i32_fwd);
+                let (dst, src) = (self.memidx(dst_mem), self.memidx(src_mem));
+                self.fwd
+                    .instructions()
+                    .local_set(n)
+                    .local_set(s)
+                    .local_tee(d)
+                    .call(helper.tape_i32())
+                    .local_get(s)
+                    .call(helper.tape_i32())
+                    .local_get(n)
+                    .call(helper.tape_i32())
+                    .local_get(d)
+                    .local_get(s)
+                    .local_get(n)
+                    .memory_copy(dst, src);
+                self.memory_copy_bwd(dst_mem, src_mem);
+            }
+            Operator::MemoryFill { mem } => {
+                self.pop();
+                self.pop();
+                self.pop();
+                let (n, val, d) = (self.tmp_i32_fwd + 2, self.tmp_i32_fwd + 1, self.tmp_i32_fwd);
+                let memidx = self.memidx(mem);
+                self.fwd
+                    .instructions()
+                    .local_set(n)
+                    .local_set(val)
+                    .local_tee(d)
+                    .call(helper.tape_i32())
+                    .local_get(n)
+                    .call(helper.tape_i32())
+                    .local_get(d)
+                    .local_get(val)
+                    .local_get(n)
+                    .memory_fill(memidx);
+                // The filled bytes are integers, so whatever floats they overwrote lose their
+                // adjoints.
+                self.bwd.instructions(|insn| {
+                    insn.call(helper.tape_i32_bwd())
+                        .local_set(self.tmp_i32_bwd)
+                        .call(helper.tape_i32_bwd())
+                        .i32_const(0)
+                        .local_get(self.tmp_i32_bwd)
+                        .memory_fill(memidx + 1)
+                });
+            }
+            Operator::MemoryAtomicNotify { memarg } => {
+                self.pop2();
+                self.push_i32();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().memory_atomic_notify(fwd);
+            }
+            Operator::MemoryAtomicWait32 { memarg } => {
+                self.pop2();
+                self.pop();
+                self.push_i32();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().memory_atomic_wait32(fwd);
+            }
+            Operator::MemoryAtomicWait64 { memarg } => {
+                self.pop2();
+                self.pop();
+                self.push_i32();
+                let (fwd, _) = self.memarg(memarg);
+                self.fwd.instructions().memory_atomic_wait64(fwd);
+            }
+            Operator::AtomicFence => {
+                self.fwd.instructions().atomic_fence();
+            }
             Operator::I32Const { value } => {
                 self.push_i32();
                 self.fwd.instructions().i32_const(value);
@@ -1089,6 +2388,13 @@ impl<'a> Func<'a> {
                 self.push_i64();
                 self.fwd.instructions().i64_rotr();
             }
+            Operator::F32Abs => {
+                self.pop();
+                self.push_f32();
+                self.fwd.instructions().call(helper.f32_abs_fwd());
+                self.bwd
+                    .instructions(|insn| insn.call(helper.f32_abs_bwd()));
+            }
             Operator::F32Neg => {
                 self.pop();
                 self.push_f32();
@@ -1102,6 +2408,31 @@ impl<'a> Func<'a> {
                 self.bwd
                     .instructions(|insn| insn.call(helper.f32_sqrt_bwd()));
             }
+            Operator::F32Ceil => {
+                self.pop();
+                self.push_f32();
+                self.fwd.instructions().f32_ceil();
+                // Rounding is piecewise constant, so its derivative is zero almost everywhere.
+                self.bwd.instructions(|insn| insn.drop().f32_const(0.));
+            }
+            Operator::F32Floor => {
+                self.pop();
+                self.push_f32();
+                self.fwd.instructions().f32_floor();
+                self.bwd.instructions(|insn| insn.drop().f32_const(0.));
+            }
+            Operator::F32Trunc => {
+                self.pop();
+                self.push_f32();
+                self.fwd.instructions().f32_trunc();
+                self.bwd.instructions(|insn| insn.drop().f32_const(0.));
+            }
+            Operator::F32Nearest => {
+                self.pop();
+                self.push_f32();
+                self.fwd.instructions().f32_nearest();
+                self.bwd.instructions(|insn| insn.drop().f32_const(0.));
+            }
             Operator::F32Add => {
                 self.pop2();
                 self.push_f32();
@@ -1155,6 +2486,13 @@ impl<'a> Func<'a> {
                 self.bwd
                     .instructions(|insn| insn.call(helper.f32_copysign_bwd()));
             }
+            Operator::F64Abs => {
+                self.pop();
+                self.push_f64();
+                self.fwd.instructions().call(helper.f64_abs_fwd());
+                self.bwd
+                    .instructions(|insn| insn.call(helper.f64_abs_bwd()));
+            }
             Operator::F64Neg => {
                 self.pop();
                 self.push_f64();
@@ -1168,6 +2506,30 @@ impl<'a> Func<'a> {
                 self.bwd
                     .instructions(|insn| insn.call(helper.f64_sqrt_bwd()));
             }
+            Operator::F64Ceil => {
+                self.pop();
+                self.push_f64();
+                self.fwd.instructions().f64_ceil();
+                self.bwd.instructions(|insn| insn.drop().f64_const(0.));
+            }
+            Operator::F64Floor => {
+                self.pop();
+                self.push_f64();
+                self.fwd.instructions().f64_floor();
+                self.bwd.instructions(|insn| insn.drop().f64_const(0.));
+            }
+            Operator::F64Trunc => {
+                self.pop();
+                self.push_f64();
+                self.fwd.instructions().f64_trunc();
+                self.bwd.instructions(|insn| insn.drop().f64_const(0.));
+            }
+            Operator::F64Nearest => {
+                self.pop();
+                self.push_f64();
+                self.fwd.instructions().f64_nearest();
+                self.bwd.instructions(|insn| insn.drop().f64_const(0.));
+            }
             Operator::F64Add => {
                 self.pop2();
                 self.push_f64();
@@ -1245,6 +2607,19 @@ impl<'a> Func<'a> {
                 self.fwd.instructions().f32_convert_i64_u();
                 self.bwd.instructions(|insn| insn.drop());
             }
+            Operator::F32DemoteF64 => {
+                self.pop();
+                self.push_f32();
+                self.fwd.instructions().f32_demote_f64();
+                // The adjoint of a narrowing conversion is the widened adjoint of its result.
+                self.bwd.instructions(|insn| insn.f64_promote_f32());
+            }
+            Operator::F64PromoteF32 => {
+                self.pop();
+                self.push_f64();
+                self.fwd.instructions().f64_promote_f32();
+                self.bwd.instructions(|insn| insn.f32_demote_f64());
+            }
             Operator::F64ConvertI32S => {
                 self.pop();
                 self.push_f64();
@@ -1269,11 +2644,231 @@ impl<'a> Func<'a> {
                 self.fwd.instructions().f64_convert_i64_u();
                 self.bwd.instructions(|insn| insn.drop());
             }
-            _ => unimplemented!("{op:?}"),
+            Operator::I32ReinterpretF32 => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_reinterpret_f32();
+                // The bits of a float aren't differentiable with respect to its value.
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I64ReinterpretF64 => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_reinterpret_f64();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            Operator::F32ReinterpretI32 => {
+                self.pop();
+                self.push_f32();
+                self.fwd.instructions().f32_reinterpret_i32();
+                self.bwd.instructions(|insn| insn.drop());
+            }
+            Operator::F64ReinterpretI64 => {
+                self.pop();
+                self.push_f64();
+                self.fwd.instructions().f64_reinterpret_i64();
+                self.bwd.instructions(|insn| insn.drop());
+            }
+            Operator::I32TruncF32S => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_trunc_f32_s();
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I32TruncSatF32S => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_trunc_sat_f32_s();
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I32TruncF32U => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_trunc_f32_u();
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I32TruncSatF32U => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_trunc_sat_f32_u();
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I32TruncF64S => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_trunc_f64_s();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            Operator::I32TruncSatF64S => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_trunc_sat_f64_s();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            Operator::I32TruncF64U => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_trunc_f64_u();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            Operator::I32TruncSatF64U => {
+                self.pop();
+                self.push_i32();
+                self.fwd.instructions().i32_trunc_sat_f64_u();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            Operator::I64TruncF32S => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_trunc_f32_s();
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I64TruncSatF32S => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_trunc_sat_f32_s();
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I64TruncF32U => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_trunc_f32_u();
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I64TruncSatF32U => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_trunc_sat_f32_u();
+                self.bwd.instructions(|insn| insn.f32_const(0.));
+            }
+            Operator::I64TruncF64S => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_trunc_f64_s();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            Operator::I64TruncSatF64S => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_trunc_sat_f64_s();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            Operator::I64TruncF64U => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_trunc_f64_u();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            Operator::I64TruncSatF64U => {
+                self.pop();
+                self.push_i64();
+                self.fwd.instructions().i64_trunc_sat_f64_u();
+                self.bwd.instructions(|insn| insn.f64_const(0.));
+            }
+            _ => self.unsupported(op)?,
         }
         Ok(())
     }
 
+    /// In the backward pass of `memory.copy`, add the adjoints of the destination back onto the
+    /// source, and zero the destination adjoints.
+    ///
+    /// We don't know the types of the values being copied, so we guess from the length: if it's a
+    /// multiple of 8 then we go by `f64`, else if it's a multiple of 4 then we go by `f32`, else we
+    /// go by bytes. Whenever a source element has a zero adjoint, the destination adjoint gets
+    /// moved onto it bit for bit, so the guess only matters when both adjoints are nonzero; in that
+    /// case they get added as floats of the guessed type, or for single bytes, we trap. Like
+    /// `memory.copy` itself, the elements are visited in whichever order keeps overlapping regions
+    /// correct.
+    fn memory_copy_bwd(&mut self, dst_mem: u32, src_mem: u32) {
+        let helper = self.helpers();
+        let addr = self.tmp_i32_bwd;
+        let (n, s, d) = (addr + 1, addr + 2, addr + 3);
+        let dst = wasm_encoder::MemArg {
+            offset: 0,
+            align: 0,
+            memory_index: self.memidx(dst_mem) + 1,
+        };
+        let src = wasm_encoder::MemArg {
+            memory_index: self.memidx(src_mem) + 1,
+            ..dst
+        };
+        let elements = MemoryCopyElements {
+            addr,
+            n,
+            s,
+            d,
+            i: addr + 4,
+            j: addr + 5,
+            v: addr + 6,
+            v64: addr + 7,
+            dst,
+            src,
+        };
+        self.bwd.instructions(|insn| {
+            insn.call(helper.tape_i32_bwd())
+                .local_set(n)
+                .call(helper.tape_i32_bwd())
+                .local_set(s)
+                .call(helper.tape_i32_bwd())
+                .local_set(d)
+                .local_get(n)
+                .i32_const(7)
+                .i32_and()
+                .i32_eqz()
+                .if_(wasm_encoder::BlockType::Empty);
+            elements.encode(insn, 8);
+            insn.else_()
+                .local_get(n)
+                .i32_const(3)
+                .i32_and()
+                .i32_eqz()
+                .if_(wasm_encoder::BlockType::Empty);
+            elements.encode(insn, 4);
+            insn.else_();
+            elements.encode(insn, 1);
+            insn.end().end()
+        });
+    }
+
+    /// Handle an instruction not otherwise supported, according to the error policy.
+    fn unsupported(&mut self, op: Operator<'_>) -> crate::Result<()> {
+        let Some((params, results)) = policy_signature(self.error_policy, &op) else {
+            return Err(ErrorImpl::Unsupported {
+                function_index: self.funcidx,
+                byte_offset: self.offset,
+                instruction: operator_name(&op),
+            });
+        };
+        for _ in params {
+            self.pop();
+        }
+        for &ty in results {
+            self.push(ty);
+        }
+        self.fwd.instruction(&RoundtripReencoder.instruction(op)?);
+        self.bwd.instructions(|insn| {
+            for ty in results {
+                if ty.is_float() {
+                    insn.drop();
+                }
+            }
+            for ty in params {
+                match ty {
+                    ValType::I32 | ValType::I64 => {}
+                    ValType::F32 => {
+                        insn.f32_const(0.);
+                    }
+                    ValType::F64 => {
+                        insn.f64_const(0.);
+                    }
+                }
+            }
+            insn
+        });
+        Ok(())
+    }
+
     fn blockty_params(&self, block_type: BlockType) -> &'a [ValType] {
         match block_type {
             BlockType::Empty | BlockType::Result(_) => &[],
@@ -1289,6 +2884,17 @@ impl<'a> Func<'a> {
         }
     }
 
+    /// After an unconditional branch, the operand stack of the input function is polymorphic until
+    /// the end of the block, so we push the results that the block would have left on the stack.
+    fn end_unreachable(&mut self, block_type: BlockType) {
+        if self.unreachable {
+            self.unreachable = false;
+            for &ty in self.blockty_results(block_type) {
+                self.push(ty);
+            }
+        }
+    }
+
     fn push(&mut self, ty: ValType) {
         self.operand_stack.push(ty);
         self.operand_stack_height.push(ty);
@@ -1327,6 +2933,51 @@ impl<'a> Func<'a> {
         self.pop();
     }
 
+    /// Process a `select` instruction whose operands have type `ty`.
+    fn select(&mut self, ty: ValType) {
+        let helper = self.helpers();
+        if !ty.is_float() {
+            // Integers disappear in the backward pass, so there's no need to store the condition.
+            self.fwd.instructions().select();
+            return;
+        }
+        self.fwd
+            .instructions()
+            .local_tee(self.tmp_i32_fwd)
+            .call(helper.tape_i32())
+            .local_get(self.tmp_i32_fwd)
+            .select();
+        match ty {
+            ValType::I32 | ValType::I64 => unreachable!(),
+            ValType::F32 => self.bwd.instructions(|insn| {
+                insn.local_set(self.tmp_f32_bwd)
+                    .call(helper.tape_i32_bwd())
+                    .local_set(self.tmp_i32_bwd)
+                    .local_get(self.tmp_f32_bwd)
+                    .f32_const(0.)
+                    .local_get(self.tmp_i32_bwd)
+                    .select()
+                    .f32_const(0.)
+                    .local_get(self.tmp_f32_bwd)
+                    .local_get(self.tmp_i32_bwd)
+                    .select()
+            }),
+            ValType::F64 => self.bwd.instructions(|insn| {
+                insn.local_set(self.tmp_f64_bwd)
+                    .call(helper.tape_i32_bwd())
+                    .local_set(self.tmp_i32_bwd)
+                    .local_get(self.tmp_f64_bwd)
+                    .f64_const(0.)
+                    .local_get(self.tmp_i32_bwd)
+                    .select()
+                    .f64_const(0.)
+                    .local_get(self.tmp_f64_bwd)
+                    .local_get(self.tmp_i32_bwd)
+                    .select()
+            }),
+        }
+    }
+
     fn blockty(&self, block_type: BlockType) -> wasm_encoder::BlockType {
         match block_type {
             BlockType::Empty => wasm_encoder::BlockType::Empty,
@@ -1336,7 +2987,7 @@ impl<'a> Func<'a> {
     }
 
     fn helpers(&self) -> FuncOffsets {
-        FuncOffsets::new(self.num_imports)
+        self.helpers
     }
 
     fn func(&self, funcidx: u32) -> (u32, u32) {
@@ -1353,9 +3004,13 @@ impl<'a> Func<'a> {
         (ty, mapped.map(|i| self.num_float_results + i))
     }
 
+    fn memidx(&self, index: u32) -> u32 {
+        memidx(self.num_imports, index)
+    }
+
     fn memarg(&self, memarg: wasmparser::MemArg) -> (wasm_encoder::MemArg, wasm_encoder::MemArg) {
         let mut fwd = RoundtripReencoder.mem_arg(memarg);
-        fwd.memory_index = OFFSET_MEMORIES + 2 * fwd.memory_index;
+        fwd.memory_index = self.memidx(fwd.memory_index);
         let mut bwd = fwd;
         bwd.memory_index += 1;
         (fwd, bwd)
@@ -1476,6 +3131,149 @@ impl Sub for StackHeight {
     }
 }
 
+/// Locals and memory arguments for the loop in the backward pass of `memory.copy`.
+struct MemoryCopyElements {
+    addr: u32,
+    n: u32,
+    s: u32,
+    d: u32,
+    i: u32,
+    j: u32,
+    v: u32,
+    v64: u32,
+    dst: wasm_encoder::MemArg,
+    src: wasm_encoder::MemArg,
+}
+
+impl MemoryCopyElements {
+    /// Encode a loop over the copied elements of `width` bytes, which must be 8, 4, or 1.
+    fn encode(&self, insn: &mut InstructionSink, width: i32) {
+        let Self {
+            addr,
+            n,
+            s,
+            d,
+            i,
+            j,
+            v,
+            v64,
+            dst,
+            src,
+        } = *self;
+        insn.i32_const(0)
+            .local_set(i)
+            .block(wasm_encoder::BlockType::Empty)
+            .loop_(wasm_encoder::BlockType::Empty)
+            .local_get(i)
+            .local_get(n)
+            .i32_ge_u()
+            .br_if(1)
+            // If the destination comes before the source, go backward.
+            .local_get(d)
+            .local_get(s)
+            .i32_lt_u()
+            .if_(wasm_encoder::BlockType::Result(wasm_encoder::ValType::I32))
+            .local_get(n)
+            .local_get(i)
+            .i32_sub()
+            .i32_const(width)
+            .i32_sub()
+            .else_()
+            .local_get(i)
+            .end()
+            .local_set(j)
+            .local_get(d)
+            .local_get(j)
+            .i32_add()
+            .local_set(addr);
+        // Take the destination adjoint, leaving zero in its place.
+        match width {
+            8 => insn
+                .local_get(addr)
+                .i64_load(dst)
+                .local_set(v64)
+                .local_get(addr)
+                .i64_const(0)
+                .i64_store(dst)
+                .local_get(v64)
+                .i64_eqz()
+                .i32_eqz(),
+            4 => insn
+                .local_get(addr)
+                .i32_load(dst)
+                .local_set(v)
+                .local_get(addr)
+                .i32_const(0)
+                .i32_store(dst)
+                .local_get(v),
+            _ => insn
+                .local_get(addr)
+                .i32_load8_u(dst)
+                .local_set(v)
+                .local_get(addr)
+                .i32_const(0)
+                .i32_store8(dst)
+                .local_get(v),
+        };
+        insn.if_(wasm_encoder::BlockType::Empty)
+            .local_get(s)
+            .local_get(j)
+            .i32_add()
+            .local_tee(addr);
+        // If the source adjoint is zero, move the destination adjoint onto it; otherwise add them.
+        match width {
+            8 => insn
+                .i64_load(src)
+                .i64_eqz()
+                .if_(wasm_encoder::BlockType::Empty)
+                .local_get(addr)
+                .local_get(v64)
+                .i64_store(src)
+                .else_()
+                .local_get(addr)
+                .local_get(addr)
+                .f64_load(src)
+                .local_get(v64)
+                .f64_reinterpret_i64()
+                .f64_add()
+                .f64_store(src),
+            4 => insn
+                .i32_load(src)
+                .i32_eqz()
+                .if_(wasm_encoder::BlockType::Empty)
+                .local_get(addr)
+                .local_get(v)
+                .i32_store(src)
+                .else_()
+                .local_get(addr)
+                .local_get(addr)
+                .f32_load(src)
+                .local_get(v)
+                .f32_reinterpret_i32()
+                .f32_add()
+                .f32_store(src),
+            _ => insn
+                .i32_load8_u(src)
+                .i32_eqz()
+                .if_(wasm_encoder::BlockType::Empty)
+                .local_get(addr)
+                .local_get(v)
+                .i32_store8(src)
+                .else_()
+                .unreachable(),
+        };
+        insn.end()
+            .end()
+            .local_get(i)
+            .i32_const(width)
+            .i32_add()
+            .local_set(i)
+            .br(0)
+            .end()
+            .end();
+    }
+}
+
 #[derive(Clone, Copy)]
 enum Control {
     Block(BlockType),
@@ -1561,7 +3359,7 @@ struct BasicBlock {
 }
 
 struct ReverseFunction {
-    num_imports: NumImports,
+    helpers: FuncOffsets,
     locals: Locals,
     body: Vec<u8>,
     stacks: Vec<ValType>,
@@ -1574,9 +3372,9 @@ struct ReverseFunction {
 }
 
 impl ReverseFunction {
-    fn new(num_imports: NumImports, params: u32) -> Self {
+    fn new(helpers: FuncOffsets, params: u32) -> Self {
         Self {
-            num_imports,
+            helpers,
             locals: Locals::new(params),
             body: Vec::new(),
             stacks: Vec::new(),
@@ -1687,10 +3485,16 @@ struct ReverseReverseFunction {
 
 impl ReverseReverseFunction {
     fn consume(mut self, operand_stack: &[ValType]) -> Vec<u8> {
-        let helper = FuncOffsets::new(self.func.num_imports);
+        let helper = self.func.helpers;
         let mut return_values = StackHeight::new();
         // Integers disappear in the backward pass.
-        for (i, &ty) in (0..).zip(operand_stack.iter().filter(|&ty| ty.is_float())) {
+        let results: Vec<ValType> = operand_stack
+            .iter()
+            .copied()
+            .filter(|ty| ty.is_float())
+            .collect();
+        // Branch locals are counted from the top of the stack, so we start from the last result.
+        for (i, ty) in (0..results.len().try_into().unwrap()).zip(results).rev() {
             self.instructions().local_get(i);
             let j = self.branch_local_index(return_values, ty).unwrap();
             self.instructions().local_set(j);
@@ -10,94 +10,109 @@
   (type $f64_unary (;8;) (func (param f64) (result f64)))
   (type $f64_bin (;9;) (func (param f64 f64) (result f64)))
   (type $f64_bin_bwd (;10;) (func (param f64) (result f64 f64)))
-  (type $my_type (;11;) (func (param i32 f64) (result f64 i32)))
-  (type $my_type_bwd (;12;) (func (param f64) (result f64)))
+  (type $nullary (;11;) (func))
+  (type $my_type (;12;) (func (param i32 f64) (result f64 i32)))
+  (type $my_type_bwd (;13;) (func (param f64) (result f64)))
   (import "foo" "bar" (func $my_imported_func (;0;) (type $my_type)))
   (import "baz" "qux" (func $my_imported_func_bwd (;1;) (type $my_type_bwd)))
-  (memory $tape_align_1 (;0;) 0)
-  (memory $tape_align_4 (;1;) 0)
-  (memory $tape_align_8 (;2;) 0)
-  (memory $my_memory (;3;) 0)
-  (memory $my_memory_bwd (;4;) 0)
-  (global $tape_align_1 (;0;) (mut i32) i32.const 0)
-  (global $tape_align_4 (;1;) (mut i32) i32.const 0)
-  (global $tape_align_8 (;2;) (mut i32) i32.const 0)
-  (global $my_global (;3;) f64 f64.const 0x0p+0 (;=0;))
+  (table $my_table (;0;) 1 funcref)
+  (table $my_table_bwd (;1;) 1 funcref)
+  (memory $tape (;0;) 0)
+  (memory $my_memory (;1;) 0)
+  (memory $my_memory_bwd (;2;) 0)
+  (global $tape (;0;) (mut i32) i32.const 0)
+  (global $my_global (;1;) f64 f64.const 0x0p+0 (;=0;))
   (export "my_exported_memory" (memory $my_memory))
   (export "my_other_exported_memory" (memory $my_memory_bwd))
   (export "my_exported_func" (func $my_func))
   (export "my_other_exported_func" (func $my_func_bwd))
+  (elem $my_elem (;0;) (table $my_table) (i32.const 0) func $my_func)
+  (elem $my_elem_bwd (;1;) (table $my_table_bwd) (i32.const 0) func $my_func_bwd)
   (func $tape_i32 (;2;) (type $tape_i32) (param i32)
     (local i32 i32)
-    global.get $tape_align_4
+    global.get $tape
     local.tee 1
     i32.const 65539
     i32.add
     i32.const 16
     i32.shr_u
-    memory.size $tape_align_4
+    memory.size
     i32.sub
     local.tee 2
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 2
-      memory.grow $tape_align_4
+      i32.const 16
+      local.get 2
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
       drop
     end
     local.get 1
     i32.const 4
     i32.add
-    global.set $tape_align_4
+    global.set $tape
     local.get 1
     local.get 0
-    i32.store $tape_align_4
+    i32.store align=1
   )
   (func $tape_i32_bwd (;3;) (type $tape_i32_bwd) (result i32)
     (local i32)
-    global.get $tape_align_4
+    global.get $tape
     i32.const 4
     i32.sub
     local.tee 0
-    global.set $tape_align_4
+    global.set $tape
     local.get 0
-    i32.load $tape_align_4
+    i32.load align=1
   )
   (func $f32_sqrt (;4;) (type $f32_unary) (param f32) (result f32)
     (local f32 i32 i32)
-    global.get $tape_align_4
+    global.get $tape
     local.tee 2
     i32.const 65539
     i32.add
     i32.const 16
     i32.shr_u
-    memory.size $tape_align_4
+    memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
-      memory.grow $tape_align_4
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
       drop
     end
     local.get 2
     i32.const 4
     i32.add
-    global.set $tape_align_4
+    global.set $tape
     local.get 2
     local.get 0
     f32.sqrt
     local.tee 1
-    f32.store $tape_align_4
+    f32.store align=1
     local.get 1
   )
   (func $f32_sqrt_bwd (;5;) (type $f32_unary) (param f32) (result f32)
     (local f32 i32)
-    global.get $tape_align_4
+    global.get $tape
     i32.const 4
     i32.sub
     local.tee 2
-    global.set $tape_align_4
+    global.set $tape
     local.get 0
     local.get 2
-    f32.load $tape_align_4
+    f32.load align=1
     local.tee 1
     local.get 1
     f32.add
@@ -105,102 +120,116 @@
   )
   (func $f32_mul (;6;) (type $f32_bin) (param f32 f32) (result f32)
     (local i32 i32)
-    global.get $tape_align_4
+    global.get $tape
     local.tee 2
     i32.const 65543
     i32.add
     i32.const 16
     i32.shr_u
-    memory.size $tape_align_4
+    memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
-      memory.grow $tape_align_4
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
       drop
     end
     local.get 2
     i32.const 8
     i32.add
-    global.set $tape_align_4
+    global.set $tape
     local.get 2
     local.get 0
-    f32.store $tape_align_4
+    f32.store align=1
     local.get 2
     local.get 1
-    f32.store $tape_align_4 offset=4
+    f32.store offset=4 align=1
     local.get 0
     local.get 1
     f32.mul
   )
   (func $f32_mul_bwd (;7;) (type $f32_bin_bwd) (param f32) (result f32 f32)
     (local i32)
-    global.get $tape_align_4
+    global.get $tape
     i32.const 8
     i32.sub
     local.tee 1
-    global.set $tape_align_4
+    global.set $tape
     local.get 0
     local.get 1
-    f32.load $tape_align_4 offset=4
+    f32.load offset=4 align=1
     f32.mul
     local.get 0
     local.get 1
-    f32.load $tape_align_4
+    f32.load align=1
     f32.mul
   )
   (func $f32_div (;8;) (type $f32_bin) (param f32 f32) (result f32)
     (local f32 i32 i32)
-    global.get $tape_align_4
+    global.get $tape
     local.tee 3
     i32.const 65543
     i32.add
     i32.const 16
     i32.shr_u
-    memory.size $tape_align_4
+    memory.size
     i32.sub
     local.tee 4
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 4
-      memory.grow $tape_align_4
+      i32.const 16
+      local.get 4
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
       drop
     end
     local.get 3
     i32.const 8
     i32.add
-    global.set $tape_align_4
+    global.set $tape
     local.get 3
     local.get 1
-    f32.store $tape_align_4
+    f32.store align=1
     local.get 3
     local.get 0
     local.get 1
     f32.div
     local.tee 2
-    f32.store $tape_align_4 offset=4
+    f32.store offset=4 align=1
     local.get 2
   )
   (func $f32_div_bwd (;9;) (type $f32_bin_bwd) (param f32) (result f32 f32)
     (local f32 i32)
-    global.get $tape_align_4
+    global.get $tape
     i32.const 8
     i32.sub
     local.tee 2
-    global.set $tape_align_4
+    global.set $tape
     local.get 0
     local.get 2
-    f32.load $tape_align_4
+    f32.load align=1
     f32.div
     local.tee 1
     local.get 1
     local.get 2
-    f32.load $tape_align_4 offset=4
+    f32.load offset=4 align=1
     f32.neg
     f32.mul
   )
   (func $f32_min (;10;) (type $f32_bin) (param f32 f32) (result f32)
     (local i32 i32)
-    global.get $tape_align_1
+    global.get $tape
     local.tee 2
     i32.const 65536
     i32.add
@@ -209,15 +238,22 @@
     memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
       memory.grow
       drop
     end
     local.get 2
     i32.const 1
     i32.add
-    global.set $tape_align_1
+    global.set $tape
     local.get 2
     local.get 0
     local.get 1
@@ -229,11 +265,11 @@
   )
   (func $f32_min_bwd (;11;) (type $f32_bin_bwd) (param f32) (result f32 f32)
     (local i32)
-    global.get $tape_align_1
+    global.get $tape
     i32.const 1
     i32.sub
     local.tee 1
-    global.set $tape_align_1
+    global.set $tape
     local.get 1
     i32.load8_u
     if (type $f32_pair) (result f32 f32) ;; label = @1
@@ -246,7 +282,7 @@
   )
   (func $f32_max (;12;) (type $f32_bin) (param f32 f32) (result f32)
     (local i32 i32)
-    global.get $tape_align_1
+    global.get $tape
     local.tee 2
     i32.const 65536
     i32.add
@@ -255,15 +291,22 @@
     memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
       memory.grow
       drop
     end
     local.get 2
     i32.const 1
     i32.add
-    global.set $tape_align_1
+    global.set $tape
     local.get 2
     local.get 0
     local.get 1
@@ -275,11 +318,11 @@
   )
   (func $f32_max_bwd (;13;) (type $f32_bin_bwd) (param f32) (result f32 f32)
     (local i32)
-    global.get $tape_align_1
+    global.get $tape
     i32.const 1
     i32.sub
     local.tee 1
-    global.set $tape_align_1
+    global.set $tape
     local.get 1
     i32.load8_u
     if (type $f32_pair) (result f32 f32) ;; label = @1
@@ -292,7 +335,7 @@
   )
   (func $f32_copysign (;14;) (type $f32_bin) (param f32 f32) (result f32)
     (local i32 i32)
-    global.get $tape_align_1
+    global.get $tape
     local.tee 2
     i32.const 65536
     i32.add
@@ -301,15 +344,22 @@
     memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
       memory.grow
       drop
     end
     local.get 2
     i32.const 1
     i32.add
-    global.set $tape_align_1
+    global.set $tape
     local.get 2
     local.get 0
     local.get 1
@@ -323,11 +373,11 @@
   )
   (func $f32_copysign_bwd (;15;) (type $f32_bin_bwd) (param f32) (result f32 f32)
     (local i32)
-    global.get $tape_align_1
+    global.get $tape
     i32.const 1
     i32.sub
     local.tee 1
-    global.set $tape_align_1
+    global.set $tape
     local.get 0
     local.get 0
     f32.const -0x0p+0 (;=-0;)
@@ -337,146 +387,216 @@
     select
     f32.const 0x0p+0 (;=0;)
   )
-  (func $f64_sqrt (;16;) (type $f64_unary) (param f64) (result f64)
+  (func $f32_abs (;16;) (type $f32_unary) (param f32) (result f32)
+    (local i32 i32)
+    global.get $tape
+    local.tee 1
+    i32.const 65536
+    i32.add
+    i32.const 16
+    i32.shr_u
+    memory.size
+    i32.sub
+    local.tee 2
+    i32.const 0
+    i32.gt_s
+    if ;; label = @1
+      local.get 2
+      i32.const 16
+      local.get 2
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
+      drop
+    end
+    local.get 1
+    i32.const 1
+    i32.add
+    global.set $tape
+    local.get 1
+    local.get 0
+    f32.const 0x0p+0 (;=0;)
+    f32.lt
+    i32.store8
+    local.get 0
+    f32.abs
+  )
+  (func $f32_abs_bwd (;17;) (type $f32_unary) (param f32) (result f32)
+    (local i32)
+    global.get $tape
+    i32.const 1
+    i32.sub
+    local.tee 1
+    global.set $tape
+    local.get 0
+    f32.neg
+    local.get 0
+    local.get 1
+    i32.load8_u
+    select
+  )
+  (func $f64_sqrt (;18;) (type $f64_unary) (param f64) (result f64)
     (local f64 i32 i32)
-    global.get $tape_align_8
+    global.get $tape
     local.tee 2
     i32.const 65543
     i32.add
     i32.const 16
     i32.shr_u
-    memory.size $tape_align_8
+    memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
-      memory.grow $tape_align_8
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
       drop
     end
     local.get 2
     i32.const 8
     i32.add
-    global.set $tape_align_8
+    global.set $tape
     local.get 2
     local.get 0
     f64.sqrt
     local.tee 1
-    f64.store $tape_align_8
+    f64.store align=1
     local.get 1
   )
-  (func $f64_sqrt_bwd (;17;) (type $f64_unary) (param f64) (result f64)
+  (func $f64_sqrt_bwd (;19;) (type $f64_unary) (param f64) (result f64)
     (local f64 i32)
-    global.get $tape_align_8
+    global.get $tape
     i32.const 8
     i32.sub
     local.tee 2
-    global.set $tape_align_8
+    global.set $tape
     local.get 0
     local.get 2
-    f64.load $tape_align_8
+    f64.load align=1
     local.tee 1
     local.get 1
     f64.add
     f64.div
   )
-  (func $f64_mul (;18;) (type $f64_bin) (param f64 f64) (result f64)
+  (func $f64_mul (;20;) (type $f64_bin) (param f64 f64) (result f64)
     (local i32 i32)
-    global.get $tape_align_8
+    global.get $tape
     local.tee 2
     i32.const 65551
     i32.add
     i32.const 16
     i32.shr_u
-    memory.size $tape_align_8
+    memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
-      memory.grow $tape_align_8
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
       drop
     end
     local.get 2
     i32.const 16
     i32.add
-    global.set $tape_align_8
+    global.set $tape
     local.get 2
     local.get 0
-    f64.store $tape_align_8
+    f64.store align=1
     local.get 2
     local.get 1
-    f64.store $tape_align_8 offset=8
+    f64.store offset=8 align=1
     local.get 0
     local.get 1
     f64.mul
   )
-  (func $f64_mul_bwd (;19;) (type $f64_bin_bwd) (param f64) (result f64 f64)
+  (func $f64_mul_bwd (;21;) (type $f64_bin_bwd) (param f64) (result f64 f64)
     (local i32)
-    global.get $tape_align_8
+    global.get $tape
     i32.const 16
     i32.sub
     local.tee 1
-    global.set $tape_align_8
+    global.set $tape
     local.get 0
     local.get 1
-    f64.load $tape_align_8 offset=8
+    f64.load offset=8 align=1
     f64.mul
     local.get 0
     local.get 1
-    f64.load $tape_align_8
+    f64.load align=1
     f64.mul
   )
-  (func $f64_div (;20;) (type $f64_bin) (param f64 f64) (result f64)
+  (func $f64_div (;22;) (type $f64_bin) (param f64 f64) (result f64)
     (local f64 i32 i32)
-    global.get $tape_align_8
+    global.get $tape
     local.tee 3
     i32.const 65551
     i32.add
     i32.const 16
     i32.shr_u
-    memory.size $tape_align_8
+    memory.size
     i32.sub
     local.tee 4
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 4
-      memory.grow $tape_align_8
+      i32.const 16
+      local.get 4
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
       drop
     end
     local.get 3
     i32.const 16
     i32.add
-    global.set $tape_align_8
+    global.set $tape
     local.get 3
     local.get 1
-    f64.store $tape_align_8
+    f64.store align=1
     local.get 3
     local.get 0
     local.get 1
     f64.div
     local.tee 2
-    f64.store $tape_align_8 offset=8
+    f64.store offset=8 align=1
     local.get 2
   )
-  (func $f64_div_bwd (;21;) (type $f64_bin_bwd) (param f64) (result f64 f64)
+  (func $f64_div_bwd (;23;) (type $f64_bin_bwd) (param f64) (result f64 f64)
     (local f64 i32)
-    global.get $tape_align_8
+    global.get $tape
     i32.const 16
     i32.sub
     local.tee 2
-    global.set $tape_align_8
+    global.set $tape
     local.get 0
     local.get 2
-    f64.load $tape_align_8
+    f64.load align=1
     f64.div
     local.tee 1
     local.get 1
     local.get 2
-    f64.load $tape_align_8 offset=8
+    f64.load offset=8 align=1
     f64.neg
     f64.mul
   )
-  (func $f64_min (;22;) (type $f64_bin) (param f64 f64) (result f64)
+  (func $f64_min (;24;) (type $f64_bin) (param f64 f64) (result f64)
     (local i32 i32)
-    global.get $tape_align_1
+    global.get $tape
     local.tee 2
     i32.const 65536
     i32.add
@@ -485,15 +605,22 @@
     memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
       memory.grow
       drop
     end
     local.get 2
     i32.const 1
     i32.add
-    global.set $tape_align_1
+    global.set $tape
     local.get 2
     local.get 0
     local.get 1
@@ -503,13 +630,13 @@
     local.get 1
     f64.min
   )
-  (func $f64_min_bwd (;23;) (type $f64_bin_bwd) (param f64) (result f64 f64)
+  (func $f64_min_bwd (;25;) (type $f64_bin_bwd) (param f64) (result f64 f64)
     (local i32)
-    global.get $tape_align_1
+    global.get $tape
     i32.const 1
     i32.sub
     local.tee 1
-    global.set $tape_align_1
+    global.set $tape
     local.get 1
     i32.load8_u
     if (type $f64_pair) (result f64 f64) ;; label = @1
@@ -520,9 +647,9 @@
       f64.const 0x0p+0 (;=0;)
     end
   )
-  (func $f64_max (;24;) (type $f64_bin) (param f64 f64) (result f64)
+  (func $f64_max (;26;) (type $f64_bin) (param f64 f64) (result f64)
     (local i32 i32)
-    global.get $tape_align_1
+    global.get $tape
     local.tee 2
     i32.const 65536
     i32.add
@@ -531,15 +658,22 @@
     memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
       memory.grow
       drop
     end
     local.get 2
     i32.const 1
     i32.add
-    global.set $tape_align_1
+    global.set $tape
     local.get 2
     local.get 0
     local.get 1
@@ -549,13 +683,13 @@
     local.get 1
     f64.max
   )
-  (func $f64_max_bwd (;25;) (type $f64_bin_bwd) (param f64) (result f64 f64)
+  (func $f64_max_bwd (;27;) (type $f64_bin_bwd) (param f64) (result f64 f64)
     (local i32)
-    global.get $tape_align_1
+    global.get $tape
     i32.const 1
     i32.sub
     local.tee 1
-    global.set $tape_align_1
+    global.set $tape
     local.get 1
     i32.load8_u
     if (type $f64_pair) (result f64 f64) ;; label = @1
@@ -566,9 +700,9 @@
       f64.const 0x0p+0 (;=0;)
     end
   )
-  (func $f64_copysign (;26;) (type $f64_bin) (param f64 f64) (result f64)
+  (func $f64_copysign (;28;) (type $f64_bin) (param f64 f64) (result f64)
     (local i32 i32)
-    global.get $tape_align_1
+    global.get $tape
     local.tee 2
     i32.const 65536
     i32.add
@@ -577,15 +711,22 @@
     memory.size
     i32.sub
     local.tee 3
+    i32.const 0
+    i32.gt_s
     if ;; label = @1
       local.get 3
+      i32.const 16
+      local.get 3
+      i32.const 16
+      i32.gt_s
+      select
       memory.grow
       drop
     end
     local.get 2
     i32.const 1
     i32.add
-    global.set $tape_align_1
+    global.set $tape
     local.get 2
     local.get 0
     local.get 1
@@ -597,13 +738,13 @@
     local.get 1
     f64.copysign
   )
-  (func $f64_copysign_bwd (;27;) (type $f64_bin_bwd) (param f64) (result f64 f64)
+  (func $f64_copysign_bwd (;29;) (type $f64_bin_bwd) (param f64) (result f64 f64)
     (local i32)
-    global.get $tape_align_1
+    global.get $tape
     i32.const 1
     i32.sub
     local.tee 1
-    global.set $tape_align_1
+    global.set $tape
     local.get 0
     local.get 0
     f64.const -0x0p+0 (;=-0;)
@@ -613,32 +754,96 @@
     select
     f64.const 0x0p+0 (;=0;)
   )
-  (func $my_func (;28;) (type $my_type) (param $my_int_param i32) (param $my_float_param f64) (result f64 i32)
-    (local f32 f64 i32)
+  (func $f64_abs (;30;) (type $f64_unary) (param f64) (result f64)
+    (local i32 i32)
+    global.get $tape
+    local.tee 1
+    i32.const 65536
+    i32.add
+    i32.const 16
+    i32.shr_u
+    memory.size
+    i32.sub
+    local.tee 2
+    i32.const 0
+    i32.gt_s
+    if ;; label = @1
+      local.get 2
+      i32.const 16
+      local.get 2
+      i32.const 16
+      i32.gt_s
+      select
+      memory.grow
+      drop
+    end
+    local.get 1
+    i32.const 1
+    i32.add
+    global.set $tape
+    local.get 1
+    local.get 0
+    f64.const 0x0p+0 (;=0;)
+    f64.lt
+    i32.store8
+    local.get 0
+    f64.abs
+  )
+  (func $f64_abs_bwd (;31;) (type $f64_unary) (param f64) (result f64)
+    (local i32)
+    global.get $tape
+    i32.const 1
+    i32.sub
+    local.tee 1
+    global.set $tape
+    local.get 0
+    f64.neg
+    local.get 0
+    local.get 1
+    i32.load8_u
+    select
+  )
+  (func $tape_reset (;32;) (type $nullary)
+    i32.const 0
+    global.set $tape
+  )
+  (func $tape_ptr (;33;) (type $tape_i32_bwd) (result i32)
+    global.get $tape
+  )
+  (func $my_func (;34;) (type $my_type) (param $my_int_param i32) (param $my_float_param f64) (result f64 i32)
+    (local $tmp_f32 f32) (local $tmp_f64 f64) (local $tmp_i32 i32) (local $tmp_i32_1 i32) (local $tmp_i32_2 i32)
+    block $my_block
+      i32.const 0
+      call $tape_i32
+    end
     local.get $my_float_param
     local.get $my_int_param
-    i32.const 0
+    i32.const 1
     call $tape_i32
   )
-  (func $my_func_bwd (;29;) (type $my_type_bwd) (param $result_0 f64) (result f64)
-    (local $my_float_param f64) (local f32 f64) (local $tmp_i32 i32) (local $branch_f64_0 f64)
+  (func $my_func_bwd (;35;) (type $my_type_bwd) (param $result_0 f64) (result f64)
+    (local $my_float_param f64) (local $tmp_f32 f32) (local $tmp_f64 f64) (local $tmp_i32 i32) (local $tmp_i32_1 i32) (local $tmp_i32_2 i32) (local $tmp_i32_3 i32) (local $tmp_i32_4 i32) (local $tmp_i32_5 i32) (local $tmp_i32_6 i32) (local $tmp_i64 i64) (local $branch_f64_0 f64)
     local.get $result_0
     local.set $branch_f64_0
     call $tape_i32_bwd
-    loop (type $dispatch) (param i32) ;; label = @1
-      block (type $dispatch) (param i32) ;; label = @2
-        block (type $dispatch) (param i32) ;; label = @3
-          br_table 1 (;@2;) 0 (;@3;)
+    loop $dispatch (type $dispatch) (param i32)
+      block $bb_0 (type $dispatch) (param i32)
+        block $bb_1 (type $dispatch) (param i32)
+          block $invalid (type $dispatch) (param i32)
+            br_table $bb_0 $bb_1 $invalid
+          end
+          unreachable
         end
-        unreachable
+        local.get $branch_f64_0
+        f64.const 0x0p+0 (;=0;)
+        local.set $branch_f64_0
+        local.get $my_float_param
+        f64.add
+        local.set $my_float_param
+        call $tape_i32_bwd
+        br $dispatch
       end
     end
-    local.get $branch_f64_0
-    f64.const 0x0p+0 (;=0;)
-    local.set $branch_f64_0
-    local.get $my_float_param
-    f64.add
-    local.set $my_float_param
     local.get $my_float_param
   )
 )
@@ -1,10 +1,26 @@
-use std::{fmt, io::Write};
+#[cfg(feature = "names")]
+use std::io::Write;
+use std::{cell::RefCell, fmt};
 
+#[cfg(feature = "names")]
 use goldenfile::Mint;
+use proptest::{
+    collection::vec, num::f64::NORMAL, prop_assert, prop_assume, strategy::Strategy,
+    test_runner::TestRunner,
+};
 use rstest::rstest;
-use wasmtime::{Caller, Engine, Linker, Module, Store, TypedFunc, WasmParams, WasmResults};
+use wasmtime::{
+    Caller, Engine, Global, GlobalType, Instance, Linker, Memory, MemoryType, Module, Mutability,
+    Ref, RefType, Store, Table, TableType, TypedFunc, Val, ValType, WasmParams, WasmResults,
+};
 
-use crate::Autodiff;
+use wasmparser::Operator;
+
+use super::policy_signature;
+use crate::{
+    reverse::SUPPORTED_INSTRUCTIONS, util::OPERATOR_NAMES, Autodiff, ErrorKind,
+    UnsupportedInstructionPolicy,
+};
 
 #[test]
 #[cfg(feature = "names")]
@@ -21,6 +37,262 @@ fn test_names() {
     file.write_all(output.as_bytes()).unwrap();
 }
 
+#[test]
+#[cfg(feature = "names")]
+fn test_names_temporaries() {
+    let input = wat::parse_str(include_str!("../wat/loop.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.names();
+    let output = wasmprinter::print_bytes(ad.reverse(&input).unwrap()).unwrap();
+    for name in [
+        "$tmp_i32 ",
+        "$tmp_i32_6 ",
+        "$stack_f64_0 ",
+        "$branch_f64_0 ",
+    ] {
+        assert!(output.contains(name), "missing {name}");
+    }
+}
+
+#[test]
+#[cfg(feature = "names")]
+fn test_with_names() {
+    let input = wat::parse_str(include_str!("../wat/names.wat")).unwrap();
+    let config = |enabled| {
+        let mut ad = Autodiff::new().with_names(enabled);
+        ad.import(("foo", "bar"), ("baz", "qux"));
+        ad
+    };
+    let mut ad = config(false);
+    ad.names();
+    assert_eq!(
+        config(true).reverse(&input).unwrap(),
+        ad.reverse(&input).unwrap(),
+    );
+    assert_ne!(
+        config(false).reverse(&input).unwrap(),
+        ad.reverse(&input).unwrap(),
+    );
+}
+
+#[test]
+#[cfg(feature = "names")]
+fn test_backward_suffix() {
+    let input = wat::parse_str(include_str!("../wat/names.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.names();
+    ad.backward_suffix("_grad");
+    ad.import(("foo", "bar"), ("baz", "qux"));
+    let output = wasmprinter::print_bytes(ad.reverse(&input).unwrap()).unwrap();
+    assert!(output.contains("(func $my_func_grad "));
+    assert!(output.contains("(type $my_type_grad "));
+    assert!(output.contains("(memory $my_memory_grad "));
+    assert!(!output.contains("$my_func_bwd"));
+}
+
+#[test]
+fn test_with_validate() {
+    let input = wat::parse_str(
+        r#"
+(module
+  (func (result f64)
+    (f64.const 0)
+    (f64.const 0)))
+"#,
+    )
+    .unwrap();
+    assert!(Autodiff::no_validate()
+        .with_validate(true)
+        .reverse(&input)
+        .is_err());
+}
+
+#[test]
+fn test_with_validation() {
+    let input = wat::parse_str(
+        r#"
+(module
+  (func (result f64)
+    (f64.const 0)
+    (f64.const 0)))
+"#,
+    )
+    .unwrap();
+    let err = Autodiff::with_validation(true).reverse(&input).unwrap_err();
+    assert!(err.as_parse_error().is_some());
+    assert!(err.as_reencode_error().is_none());
+    let source = std::error::Error::source(&err).unwrap();
+    assert!(source.is::<wasmparser::BinaryReaderError>());
+}
+
+#[test]
+fn test_error_description() {
+    let input = wat::parse_str(include_str!("../wat/import_func.wat")).unwrap();
+    let err = Autodiff::new().reverse(&input).unwrap_err();
+    assert_eq!(err.description(), "no import configured");
+    assert_eq!(err.kind(), ErrorKind::Import);
+    assert_eq!(err.unsupported_instruction(), None);
+    assert_eq!(err.function_index(), None);
+    assert_eq!(
+        err.to_string(),
+        "no backward import registered for \"f64::exp\"",
+    );
+}
+
+#[test]
+fn test_clone() {
+    let input = wat::parse_str(include_str!("../wat/import_func.wat")).unwrap();
+    let mut base = Autodiff::new();
+    base.import(("f64", "exp"), ("f64", "exp_bwd"));
+    let mut ad = base.clone();
+    ad.export("sigmoid", "backprop");
+    assert!(base.reverse(&input).is_ok());
+    assert!(ad.reverse(&input).is_ok());
+    assert!(ad
+        .explain(&input)
+        .unwrap()
+        .contains("exported as \"backprop\""));
+    assert!(!base
+        .explain(&input)
+        .unwrap()
+        .contains("exported as \"backprop\""));
+}
+
+#[test]
+fn test_supported_instructions() {
+    let supported = Autodiff::supported_instructions();
+    assert!(supported.contains(&"F64Mul"));
+    assert!(!supported.contains(&"I32WrapI64"));
+}
+
+#[test]
+fn test_supported_instructions_match_visitor() {
+    // The operators that `Func::instruction` handles are exactly the ones with their own arm in its
+    // `match`, before the fallback to `Func::unsupported`.
+    let source = include_str!("../reverse.rs");
+    let start = source
+        .find("fn instruction(&mut self, op: Operator")
+        .unwrap();
+    let end = start + source[start..].find("_ => self.unsupported(op)").unwrap();
+    let arms: Vec<&str> = source[start..end]
+        .lines()
+        .filter_map(|line| line.strip_prefix("            Operator::"))
+        .map(|rest| rest.split(|c: char| !c.is_alphanumeric()).next().unwrap())
+        .collect();
+    assert_eq!(arms, SUPPORTED_INSTRUCTIONS);
+    let supported: Vec<&str> = OPERATOR_NAMES
+        .iter()
+        .copied()
+        .filter(|name| arms.contains(name))
+        .collect();
+    assert_eq!(supported.len(), arms.len());
+}
+
+#[test]
+fn test_check_support() {
+    let input = wat::parse_str(include_str!("../wat/f64_mul.wat")).unwrap();
+    assert_eq!(Autodiff::new().check_support(&input), Ok(()));
+    let input = wat::parse_str(include_str!("../wat/i32_wrap_i64.wat")).unwrap();
+    assert_eq!(
+        Autodiff::new().check_support(&input),
+        Err(vec!["I32WrapI64".to_string()]),
+    );
+    let ad = Autodiff::new().with_error_policy(UnsupportedInstructionPolicy::ZeroGradient);
+    assert_eq!(ad.check_support(&input), Ok(()));
+}
+
+#[test]
+fn test_check_support_skip() {
+    let input = wat::parse_str(include_str!("../wat/skip.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    assert_eq!(
+        ad.check_support(&input),
+        Err(vec!["I32Extend8S".to_string()]),
+    );
+    ad.skip("scale");
+    assert_eq!(ad.check_support(&input), Ok(()));
+}
+
+#[test]
+fn test_unsupported_instructions() {
+    let input = wat::parse_str(include_str!("../wat/f64_mul.wat")).unwrap();
+    assert_eq!(
+        Autodiff::new().unsupported_instructions(&input).unwrap(),
+        []
+    );
+    let input = wat::parse_str(include_str!("../wat/skip.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    assert_eq!(
+        ad.unsupported_instructions(&input).unwrap(),
+        [(0, "I32Extend8S")],
+    );
+    ad.skip("scale");
+    assert_eq!(ad.unsupported_instructions(&input).unwrap(), []);
+    assert!(ad.unsupported_instructions(b"not wasm").is_err());
+}
+
+#[test]
+fn test_error_policy_fail() {
+    let input = wat::parse_str(include_str!("../wat/i32_wrap_i64.wat")).unwrap();
+    let err = Autodiff::new()
+        .with_error_policy(UnsupportedInstructionPolicy::Fail)
+        .reverse(&input)
+        .unwrap_err();
+    assert_eq!(
+        err.description(),
+        "unsupported Wasm instruction: I32WrapI64 at offset 37"
+    );
+    assert_eq!(err.kind(), ErrorKind::UnsupportedInstruction);
+    assert_eq!(err.unsupported_instruction(), Some("I32WrapI64"));
+    assert_eq!(err.function_index(), Some(0));
+    assert_eq!(err.byte_offset(), Some(37));
+}
+
+#[test]
+fn test_error_policy_zero_gradient() {
+    let wat = include_str!("../wat/i32_wrap_i64.wat");
+    let (mut store, function, backprop) =
+        compile_with_imports::<i64, i32, (), ()>(wat, "wrap", |_, ad| {
+            *ad = Autodiff::new().with_error_policy(UnsupportedInstructionPolicy::ZeroGradient);
+        });
+    assert_eq!(function.call(&mut store, (1 << 32) + 7).unwrap(), 7);
+    backprop.call(&mut store, ()).unwrap();
+    // Every float instruction is currently supported, so check directly that unsupported ones
+    // would get zero adjoints instead of failing.
+    use crate::util::ValType::{F32, F64};
+    assert_eq!(
+        policy_signature(
+            UnsupportedInstructionPolicy::ZeroGradient,
+            &Operator::F64PromoteF32,
+        ),
+        Some((&[F32][..], &[F64][..])),
+    );
+}
+
+#[test]
+fn test_error_policy_passthrough() {
+    let wat = include_str!("../wat/i32_wrap_i64.wat");
+    let (mut store, function, backprop) =
+        compile_with_imports::<i64, i32, (), ()>(wat, "wrap", |_, ad| {
+            *ad = Autodiff::new().with_error_policy(UnsupportedInstructionPolicy::Passthrough);
+        });
+    assert_eq!(function.call(&mut store, (1 << 32) + 7).unwrap(), 7);
+    backprop.call(&mut store, ()).unwrap();
+    assert_eq!(
+        policy_signature(
+            UnsupportedInstructionPolicy::Passthrough,
+            &Operator::F64PromoteF32,
+        ),
+        None,
+    );
+}
+
+#[test]
+fn test_error_bounds() {
+    fn assert_bounds<E: std::error::Error + Send + Sync + 'static>() {}
+    assert_bounds::<crate::Error>();
+}
+
 struct Data {
     tape: Vec<f64>,
 }
@@ -86,6 +358,124 @@ impl<
     }
 }
 
+/// Estimate the derivative of `function` at `x` via central differences.
+fn numerical_gradient(mut function: impl FnMut(f64) -> f64, x: f64, eps: f64) -> f64 {
+    let y_plus = function(x + eps);
+    let y_minus = function(x - eps);
+    (y_plus - y_minus) / (2. * eps)
+}
+
+impl Backprop<f64, f64, f64, f64> {
+    /// Like [`Backprop::test`], but also check the gradient against a numerical estimate, in a
+    /// separate instance so that the extra calls don't disturb the tape.
+    fn test_numerically(self) {
+        let (mut store, function, _) = compile::<f64, f64, f64, f64>(self.wat, self.name);
+        let numerical = self.cotangent
+            * numerical_gradient(|x| function.call(&mut store, x).unwrap(), self.input, 1e-6);
+        assert!(
+            (numerical - self.gradient).abs() <= 1e-6 * (1. + self.gradient.abs()),
+            "gradient {} but numerical gradient {numerical}",
+            self.gradient,
+        );
+        self.test();
+    }
+}
+
+/// Normal floats small and large enough that squaring them stays normal, so that intermediate
+/// results don't lose precision to underflow.
+fn moderate_f64() -> impl Strategy<Value = f64> {
+    NORMAL.prop_filter("square would not be normal", |x| (x * x).is_normal())
+}
+
+/// Check the gradient of a function from floats to one float against central differences at
+/// random inputs from `strategy`, skipping inputs where the function or its gradient isn't finite.
+fn check_gradient_randomly(wat: &str, name: &str, strategy: impl Strategy<Value = Vec<f64>>) {
+    let input = wat::parse_str(wat).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export(name, "backprop");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let function = instance.get_func(&mut store, name).unwrap();
+    let backprop = instance.get_func(&mut store, "backprop").unwrap();
+    // Compiling is much slower than calling, so every case shares the same instance.
+    let store = RefCell::new(store);
+    let call = |x: &[f64]| {
+        let params: Vec<Val> = x.iter().map(|&x| Val::F64(x.to_bits())).collect();
+        let mut results = [Val::F64(0)];
+        function
+            .call(&mut *store.borrow_mut(), &params, &mut results)
+            .unwrap();
+        results[0].unwrap_f64()
+    };
+    let result = TestRunner::default().run(&strategy, |x| {
+        prop_assume!(call(&x).is_finite());
+        let mut gradient = vec![Val::F64(0); x.len()];
+        backprop
+            .call(
+                &mut *store.borrow_mut(),
+                &[Val::F64(1f64.to_bits())],
+                &mut gradient,
+            )
+            .unwrap();
+        for (i, g) in gradient.iter().enumerate() {
+            let g = g.unwrap_f64();
+            let partial = |xi: f64| {
+                let mut x = x.clone();
+                x[i] = xi;
+                call(&x)
+            };
+            let eps = 1e-6 * x[i].abs();
+            let (y_plus, y_minus) = (partial(x[i] + eps), partial(x[i] - eps));
+            if !(g.is_finite() && y_plus.is_finite() && y_minus.is_finite()) {
+                continue;
+            }
+            let numerical = numerical_gradient(partial, x[i], eps);
+            // Beyond the truncation error, the difference loses about as many digits as the
+            // function values have, so allow for that rounding error too.
+            let tolerance = 1e-6 * g.abs().max(1.) + 1e-10 * (y_plus.abs() + y_minus.abs()) / eps;
+            prop_assert!(
+                (numerical - g).abs() <= tolerance,
+                "{name} at {x:?}: gradient {g} but numerical gradient {numerical} for input {i}",
+            );
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        panic!("{err}");
+    }
+}
+
+#[test]
+fn test_random_square() {
+    check_gradient_randomly(include_str!("../wat/square.wat"), "square", vec(NORMAL, 1));
+}
+
+#[test]
+fn test_random_cube() {
+    check_gradient_randomly(include_str!("../wat/cube.wat"), "cube", vec(NORMAL, 1));
+}
+
+#[test]
+fn test_random_dot() {
+    check_gradient_randomly(
+        include_str!("../wat/dot.wat"),
+        "dot",
+        vec(moderate_f64(), 4),
+    );
+}
+
+#[test]
+fn test_random_norm() {
+    check_gradient_randomly(
+        include_str!("../wat/norm.wat"),
+        "norm",
+        vec(moderate_f64(), 2),
+    );
+}
+
 #[test]
 fn test_square() {
     Backprop {
@@ -96,127 +486,913 @@ fn test_square() {
         cotangent: 1.,
         gradient: 6.,
     }
-    .test()
+    .test_numerically()
 }
 
 #[test]
-fn test_import_func() {
-    let wat = include_str!("../wat/import_func.wat");
-    let (mut store, function, backprop) =
-        compile_with_imports::<f64, f64, f64, f64>(wat, "sigmoid", |linker, ad| {
-            linker
-                .func_wrap("f64", "exp", |mut caller: Caller<'_, Data>, x: f64| {
-                    let y = x.exp();
-                    caller.data_mut().tape.push(y);
-                    y
-                })
-                .unwrap();
-            linker
-                .func_wrap("f64", "exp_bwd", |mut caller: Caller<'_, Data>, dy: f64| {
-                    let y = caller.data_mut().tape.pop().unwrap();
-                    dy * y
-                })
-                .unwrap();
-            ad.import(("f64", "exp"), ("f64", "exp_bwd"));
-        });
-    {
-        let output = function.call(&mut store, 0.).unwrap();
-        assert_eq!(output, 0.5);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, 0.25);
-    }
+fn test_reverse_of_forward_duplicate_export() {
+    let input = wat::parse_str(include_str!("../wat/cube.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("cube", "backprop");
+    let err = ad
+        .reverse_of_forward(&input, "cube", "backprop")
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Export);
+    assert_eq!(
+        err.to_string(),
+        "mapping already exists for export \"cube\""
+    );
 }
 
 #[test]
-fn test_reexport_func() {
-    let wat = include_str!("../wat/reexport_func.wat");
-    let (mut store, function, backprop) =
-        compile_with_imports::<f64, f64, f64, f64>(wat, "id", |linker, ad| {
-            linker.func_wrap("f64", "id", |x: f64| x).unwrap();
-            ad.import(("f64", "id"), ("f64", "id"));
-        });
-    {
-        let output = function.call(&mut store, 2.).unwrap();
-        assert_eq!(output, 2.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, 1.);
-    }
+fn test_reverse_of_forward() {
+    let input = wat::parse_str(include_str!("../wat/cube.wat")).unwrap();
+    let output = Autodiff::new()
+        .reverse_of_forward(&input, "cube", "backprop")
+        .unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let cube = instance
+        .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, "cube")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(cube.call(&mut store, (2., 1.)).unwrap(), (8., 12.));
+    assert_eq!(backprop.call(&mut store, (1., 0.)).unwrap(), (12., 0.));
+    assert_eq!(cube.call(&mut store, (2., 1.)).unwrap(), (8., 12.));
+    assert_eq!(backprop.call(&mut store, (0., 1.)).unwrap(), (12., 12.));
 }
 
 #[test]
-fn test_if() {
-    let wat = include_str!("../wat/if.wat");
-    let (mut store, function, backprop) =
-        compile::<(i32, f64, f64), f64, (f64, f64), f64>(wat, "select");
-    {
-        let output = function.call(&mut store, (1, 2., 3.)).unwrap();
-        assert_eq!(output, 2.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, (1., 0.));
-    }
-    {
-        let output = function.call(&mut store, (0, 2., 3.)).unwrap();
-        assert_eq!(output, 3.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, (0., 1.));
+fn test_reverse_of_forward_hessian() {
+    let input = wat::parse_str(include_str!("../wat/hessian.wat")).unwrap();
+    let output = Autodiff::new()
+        .reverse_of_forward(&input, "f", "backprop")
+        .unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let f = instance
+        .get_typed_func::<(f64, f64, f64, f64), (f64, f64)>(&mut store, "f")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<(f64, f64), (f64, f64, f64, f64)>(&mut store, "backprop")
+        .unwrap();
+    // The Hessian of `x^2 y` is `[[2y, 2x], [2x, 0]]`.
+    let (x, y) = (3., 2.);
+    let mut hessian = Vec::new();
+    for (dx, dy) in [(1., 0.), (0., 1.)] {
+        f.call(&mut store, (x, dx, y, dy)).unwrap();
+        let (x_bar, dx_bar, y_bar, dy_bar) = backprop.call(&mut store, (0., 1.)).unwrap();
+        // The cotangents of the parameter tangents are just the gradient.
+        assert_eq!((dx_bar, dy_bar), (2. * x * y, x * x));
+        hessian.push([x_bar, y_bar]);
     }
+    assert_eq!(hessian, [[2. * y, 2. * x], [2. * x, 0.]]);
+}
+
+/// Forward mode with a tangent of one should give the same derivative as reverse mode with a
+/// cotangent of one.
+#[rstest]
+#[case(include_str!("../wat/square.wat"), "square", 3.)]
+#[case(include_str!("../wat/cube.wat"), "cube", 2.)]
+#[case(include_str!("../wat/atomic_fence.wat"), "square", 3.)]
+#[case(include_str!("../wat/data.wat"), "scale", 2.)]
+#[case(include_str!("../wat/f64_abs.wat"), "abs", -3.)]
+#[case(include_str!("../wat/f64_ceil.wat"), "ceil", 2.5)]
+#[case(include_str!("../wat/f64_floor.wat"), "floor", 2.5)]
+#[case(include_str!("../wat/f64_local.wat"), "cube", 2.)]
+#[case(include_str!("../wat/f64_nearest.wat"), "nearest", 2.7)]
+#[case(include_str!("../wat/f64_neg.wat"), "neg", 3.)]
+#[case(include_str!("../wat/f64_reinterpret.wat"), "bits", 3.)]
+#[case(include_str!("../wat/f64_sqrt.wat"), "sqrt", 16.)]
+#[case(include_str!("../wat/f64_store_load.wat"), "roundtrip", 3.)]
+#[case(include_str!("../wat/f64_trunc.wat"), "trunc", -2.5)]
+#[case(include_str!("../wat/global_get_f64.wat"), "scale", 2.)]
+#[case(include_str!("../wat/global_get_i32.wat"), "scale", 2.)]
+#[case(include_str!("../wat/global_mut_f64.wat"), "cube", 2.)]
+#[case(include_str!("../wat/loop.wat"), "loop", 3.5)]
+fn test_forward_agrees(#[case] wat: &str, #[case] name: &str, #[case] x: f64) {
+    let (mut store, function, backprop) = compile::<f64, f64, f64, f64>(wat, name);
+    let y = function.call(&mut store, x).unwrap();
+    let gradient = backprop.call(&mut store, 1.).unwrap();
+    let input = wat::parse_str(wat).unwrap();
+    let output = Autodiff::new().forward(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let jvp = instance
+        .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, name)
+        .unwrap();
+    assert_eq!(jvp.call(&mut store, (x, 1.)).unwrap(), (y, gradient));
 }
 
 #[test]
-fn test_else() {
-    let wat = include_str!("../wat/else.wat");
-    let (mut store, function, backprop) =
-        compile::<(i32, f64, f64), f64, (f64, f64), f64>(wat, "select");
-    {
-        let output = function.call(&mut store, (1, 2., 3.)).unwrap();
-        assert_eq!(output, 2.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, (1., 0.));
-    }
-    {
-        let output = function.call(&mut store, (0, 2., 3.)).unwrap();
-        assert_eq!(output, 3.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, (0., 1.));
-    }
+fn test_second_order_forward_over_reverse() {
+    let input = wat::parse_str(include_str!("../wat/cube.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("cube", "backprop");
+    let output = ad.second_order_forward_over_reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let cube = instance
+        .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, "cube")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<(f64, f64), (f64, f64)>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(cube.call(&mut store, (2., 1.)).unwrap(), (8., 12.));
+    assert_eq!(backprop.call(&mut store, (1., 0.)).unwrap(), (12., 12.));
+    assert_eq!(cube.call(&mut store, (2., 0.)).unwrap(), (8., 0.));
+    assert_eq!(backprop.call(&mut store, (1., 1.)).unwrap(), (12., 12.));
 }
 
 #[test]
-fn test_br() {
-    let wat = include_str!("../wat/br.wat");
-    let (mut store, function, backprop) =
-        compile::<(i32, f64, f64), f64, (f64, f64), f64>(wat, "select");
-    {
-        let output = function.call(&mut store, (1, 2., 3.)).unwrap();
-        assert_eq!(output, 2.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, (1., 0.));
-    }
-    {
-        let output = function.call(&mut store, (0, 2., 3.)).unwrap();
-        assert_eq!(output, 3.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, (0., 1.));
-    }
+fn test_second_order_reverse_over_forward() {
+    let input = wat::parse_str(include_str!("../wat/hessian.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("f", "backprop");
+    let output = ad.second_order_reverse_over_forward(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let f = instance
+        .get_typed_func::<(f64, f64, f64, f64), (f64, f64)>(&mut store, "f")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<(f64, f64), (f64, f64, f64, f64)>(&mut store, "backprop")
+        .unwrap();
+    // The Hessian of `x^2 y` is `[[2y, 2x], [2x, 0]]`.
+    let (x, y) = (3., 2.);
+    assert_eq!(
+        f.call(&mut store, (x, 0., y, 1.)).unwrap(),
+        (x * x * y, x * x)
+    );
+    assert_eq!(
+        backprop.call(&mut store, (0., 1.)).unwrap(),
+        (2. * x, 2. * x * y, 0., x * x),
+    );
 }
 
 #[test]
-fn test_br_if_return() {
-    let wat = include_str!("../wat/br_if_return.wat");
-    let (mut store, function, backprop) =
-        compile::<(i32, f64, f64), f64, (f64, f64), f64>(wat, "select");
-    {
-        let output = function.call(&mut store, (1, 2., 3.)).unwrap();
-        assert_eq!(output, 2.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, (1., 0.));
-    }
-    {
-        let output = function.call(&mut store, (0, 2., 3.)).unwrap();
-        assert_eq!(output, 3.);
-        let gradient = backprop.call(&mut store, 1.).unwrap();
-        assert_eq!(gradient, (0., 1.));
-    }
+fn test_tape_reset() {
+    let input = wat::parse_str(include_str!("../wat/f64_mul.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("mul", "backprop");
+    ad.export_tape_reset("reset");
+    ad.export_tape_pointer("tape");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let function = instance
+        .get_typed_func::<(f64, f64), f64>(&mut store, "mul")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, (f64, f64)>(&mut store, "backprop")
+        .unwrap();
+    let reset = instance
+        .get_typed_func::<(), ()>(&mut store, "reset")
+        .unwrap();
+    let tape = instance
+        .get_typed_func::<(), i32>(&mut store, "tape")
+        .unwrap();
+    assert_eq!(tape.call(&mut store, ()).unwrap(), 0);
+    assert_eq!(function.call(&mut store, (2., 3.)).unwrap(), 6.);
+    let size = tape.call(&mut store, ()).unwrap();
+    assert!(size > 0);
+    reset.call(&mut store, ()).unwrap();
+    assert_eq!(tape.call(&mut store, ()).unwrap(), 0);
+    assert_eq!(function.call(&mut store, (4., 5.)).unwrap(), 20.);
+    assert_eq!(tape.call(&mut store, ()).unwrap(), size);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), (5., 4.));
+    assert_eq!(tape.call(&mut store, ()).unwrap(), 0);
+    // The first forward pass is no longer on the tape.
+    assert!(backprop.call(&mut store, 1.).is_err());
+}
+
+#[test]
+fn test_start() {
+    let input = wat::parse_str(include_str!("../wat/start.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("scale", "backprop");
+    ad.export_tape_pointer("tape");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let function = instance
+        .get_typed_func::<f64, f64>(&mut store, "scale")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, f64>(&mut store, "backprop")
+        .unwrap();
+    let tape = instance
+        .get_typed_func::<(), i32>(&mut store, "tape")
+        .unwrap();
+    // The start function ran, but left nothing on the tape.
+    assert_eq!(tape.call(&mut store, ()).unwrap(), 0);
+    assert_eq!(function.call(&mut store, 2.).unwrap(), 6.);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), 3.);
+    assert_eq!(tape.call(&mut store, ()).unwrap(), 0);
+}
+
+#[test]
+fn test_custom_section() {
+    let input = wat::parse_str(include_str!("../wat/custom.wat")).unwrap();
+    let output = Autodiff::new().reverse(&input).unwrap();
+    let mut customs = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(&output) {
+        if let wasmparser::Payload::CustomSection(section) = payload.unwrap() {
+            customs.push((section.name().to_owned(), section.data().to_vec()));
+        }
+    }
+    // The name section is dropped because names weren't requested.
+    assert_eq!(customs, [("my_section".to_owned(), b"hello".to_vec())]);
+}
+
+#[test]
+fn test_tape_reset_per_function() {
+    let input = wat::parse_str(include_str!("../wat/square_cube.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.tape_per_function();
+    ad.export("square", "square_bwd");
+    ad.export("cube", "cube_bwd");
+    ad.export_tape_reset("reset");
+    ad.export_tape_pointer("tape");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let square = instance
+        .get_typed_func::<f64, f64>(&mut store, "square")
+        .unwrap();
+    let square_bwd = instance
+        .get_typed_func::<f64, f64>(&mut store, "square_bwd")
+        .unwrap();
+    let cube = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube")
+        .unwrap();
+    let cube_bwd = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube_bwd")
+        .unwrap();
+    let reset = instance
+        .get_typed_func::<(), ()>(&mut store, "reset")
+        .unwrap();
+    let tape = instance
+        .get_typed_func::<(), i32>(&mut store, "tape")
+        .unwrap();
+    assert_eq!(square.call(&mut store, 3.).unwrap(), 9.);
+    let size = tape.call(&mut store, ()).unwrap();
+    assert_eq!(cube.call(&mut store, 2.).unwrap(), 8.);
+    assert!(tape.call(&mut store, ()).unwrap() > size);
+    reset.call(&mut store, ()).unwrap();
+    assert_eq!(tape.call(&mut store, ()).unwrap(), 0);
+    assert!(square_bwd.call(&mut store, 1.).is_err());
+    assert!(cube_bwd.call(&mut store, 1.).is_err());
+}
+
+#[test]
+fn test_export_all() {
+    let input = wat::parse_str(include_str!("../wat/square_cube.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export_all();
+    ad.export("cube", "cube_grad");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let square = instance
+        .get_typed_func::<f64, f64>(&mut store, "square")
+        .unwrap();
+    let square_bwd = instance
+        .get_typed_func::<f64, f64>(&mut store, "square_bwd")
+        .unwrap();
+    let cube = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube")
+        .unwrap();
+    let cube_grad = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube_grad")
+        .unwrap();
+    assert!(instance.get_func(&mut store, "cube_bwd").is_none());
+    assert_eq!(square.call(&mut store, 3.).unwrap(), 9.);
+    assert_eq!(square_bwd.call(&mut store, 1.).unwrap(), 6.);
+    assert_eq!(cube.call(&mut store, 2.).unwrap(), 8.);
+    assert_eq!(cube_grad.call(&mut store, 1.).unwrap(), 12.);
+}
+
+#[test]
+fn test_export_all_backward_suffix() {
+    let input = wat::parse_str(include_str!("../wat/square_cube.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export_all();
+    ad.backward_suffix("_grad");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let square = instance
+        .get_typed_func::<f64, f64>(&mut store, "square")
+        .unwrap();
+    let square_grad = instance
+        .get_typed_func::<f64, f64>(&mut store, "square_grad")
+        .unwrap();
+    assert!(instance.get_func(&mut store, "square_bwd").is_none());
+    assert_eq!(square.call(&mut store, 3.).unwrap(), 9.);
+    assert_eq!(square_grad.call(&mut store, 1.).unwrap(), 6.);
+}
+
+#[test]
+fn test_export_all_with() {
+    let input = wat::parse_str(include_str!("../wat/square_cube.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export_all_with(|name| format!("{name}_grad"));
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let cube = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube")
+        .unwrap();
+    let cube_grad = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube_grad")
+        .unwrap();
+    assert_eq!(cube.call(&mut store, 2.).unwrap(), 8.);
+    assert_eq!(cube_grad.call(&mut store, 1.).unwrap(), 12.);
+}
+
+#[test]
+fn test_skip_unsupported() {
+    let input = wat::parse_str(include_str!("../wat/skip.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("mul_scaled", "backprop");
+    assert!(ad.reverse(&input).is_err());
+}
+
+#[test]
+fn test_skip() {
+    let input = wat::parse_str(include_str!("../wat/skip.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.skip("scale");
+    ad.export("mul_scaled", "backprop");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let mul_scaled = instance
+        .get_typed_func::<f64, f64>(&mut store, "mul_scaled")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, f64>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(mul_scaled.call(&mut store, 2.).unwrap(), 12.);
+    // The result of the skipped function is treated as a constant.
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), 6.);
+}
+
+#[test]
+fn test_differentiate_params() {
+    let input = wat::parse_str(include_str!("../wat/dot.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("dot", "backprop");
+    ad.differentiate_params("dot", [3, 0]);
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let dot = instance
+        .get_typed_func::<(f64, f64, f64, f64), f64>(&mut store, "dot")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, (f64, f64)>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(dot.call(&mut store, (1., 2., 3., 4.)).unwrap(), 11.);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), (2., 3.));
+}
+
+#[test]
+fn test_differentiate_params_skips_integers() {
+    let input = wat::parse_str(include_str!("../wat/call.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("tuple", "backprop");
+    ad.differentiate_params("tuple", [2]);
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let tuple = instance
+        .get_typed_func::<(f64, i64, f32, i32), (f32, i32, f64, i64)>(&mut store, "tuple")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<(f32, f64), f32>(&mut store, "backprop")
+        .unwrap();
+    tuple.call(&mut store, (2., 3, 4., 1)).unwrap();
+    assert_eq!(backprop.call(&mut store, (5., 6.)).unwrap(), 5.);
+}
+
+#[rstest]
+#[case(1)]
+#[case(4)]
+fn test_differentiate_params_invalid(#[case] index: u32) {
+    let input = wat::parse_str(include_str!("../wat/call.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("tuple", "backprop");
+    ad.differentiate_params("tuple", [0, index]);
+    let err = ad.reverse(&input).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Transform);
+}
+
+#[test]
+fn test_custom_gradient() {
+    let input = wat::parse_str(include_str!("../wat/custom_gradient.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.custom_gradient("cube", "cube_grad");
+    ad.export("cube", "cube_bwd");
+    ad.export("double_cube", "backprop");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let cube = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube")
+        .unwrap();
+    let cube_bwd = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube_bwd")
+        .unwrap();
+    let double_cube = instance
+        .get_typed_func::<f64, f64>(&mut store, "double_cube")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, f64>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(cube.call(&mut store, 2.).unwrap(), 8.);
+    assert_eq!(cube_bwd.call(&mut store, 1.).unwrap(), 12.);
+    assert_eq!(double_cube.call(&mut store, 2.).unwrap(), 16.);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), 24.);
+}
+
+#[test]
+fn test_custom_gradient_wrong_type() {
+    let input = wat::parse_str(include_str!("../wat/f64_mul.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.custom_gradient("mul", "mul");
+    let err = ad.reverse(&input).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Transform);
+}
+
+#[test]
+fn test_tape_initial_pages() {
+    let input = wat::parse_str(include_str!("../wat/f64_mul.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("mul", "backprop");
+    ad.tape_initial_pages(2);
+    let output = ad.reverse(&input).unwrap();
+    let mut minimums = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(&output) {
+        if let wasmparser::Payload::MemorySection(section) = payload.unwrap() {
+            for memory in section {
+                minimums.push(memory.unwrap().initial);
+            }
+        }
+    }
+    assert_eq!(minimums, [2]);
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let function = instance
+        .get_typed_func::<(f64, f64), f64>(&mut store, "mul")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, (f64, f64)>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(function.call(&mut store, (2., 3.)).unwrap(), 6.);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), (3., 2.));
+}
+
+#[test]
+fn test_tape_per_function() {
+    let input = wat::parse_str(include_str!("../wat/square_cube.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.tape_per_function();
+    ad.export("square", "square_bwd");
+    ad.export("cube", "cube_bwd");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap();
+    let square = instance
+        .get_typed_func::<f64, f64>(&mut store, "square")
+        .unwrap();
+    let square_bwd = instance
+        .get_typed_func::<f64, f64>(&mut store, "square_bwd")
+        .unwrap();
+    let cube = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube")
+        .unwrap();
+    let cube_bwd = instance
+        .get_typed_func::<f64, f64>(&mut store, "cube_bwd")
+        .unwrap();
+    assert_eq!(square.call(&mut store, 3.).unwrap(), 9.);
+    assert_eq!(cube.call(&mut store, 2.).unwrap(), 8.);
+    assert_eq!(square_bwd.call(&mut store, 1.).unwrap(), 6.);
+    assert_eq!(cube_bwd.call(&mut store, 1.).unwrap(), 12.);
+}
+
+#[test]
+fn test_import_func() {
+    let wat = include_str!("../wat/import_func.wat");
+    let (mut store, function, backprop) =
+        compile_with_imports::<f64, f64, f64, f64>(wat, "sigmoid", |linker, ad| {
+            linker
+                .func_wrap("f64", "exp", |mut caller: Caller<'_, Data>, x: f64| {
+                    let y = x.exp();
+                    caller.data_mut().tape.push(y);
+                    y
+                })
+                .unwrap();
+            linker
+                .func_wrap("f64", "exp_bwd", |mut caller: Caller<'_, Data>, dy: f64| {
+                    let y = caller.data_mut().tape.pop().unwrap();
+                    dy * y
+                })
+                .unwrap();
+            ad.import(("f64", "exp"), ("f64", "exp_bwd"));
+        });
+    {
+        let output = function.call(&mut store, 0.).unwrap();
+        assert_eq!(output, 0.5);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 0.25);
+    }
+}
+
+#[test]
+fn test_import_memory_global() {
+    let input = wat::parse_str(include_str!("../wat/import_memory_global_mut.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.import(("env", "memory"), ("env", "memory_bwd"));
+    ad.import(("env", "offset"), ("env", "offset_bwd"));
+    ad.export("affine", "backprop");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let mut linker = Linker::new(&engine);
+    let memory_type = MemoryType::new(1, None);
+    let memory = Memory::new(&mut store, memory_type.clone()).unwrap();
+    let memory_bwd = Memory::new(&mut store, memory_type).unwrap();
+    let mutable = GlobalType::new(ValType::F64, Mutability::Var);
+    let offset = Global::new(&mut store, mutable.clone(), Val::F64(1f64.to_bits())).unwrap();
+    let offset_bwd = Global::new(&mut store, mutable, Val::F64(0f64.to_bits())).unwrap();
+    let immutable = GlobalType::new(ValType::F64, Mutability::Const);
+    let scale = Global::new(&mut store, immutable, Val::F64(2f64.to_bits())).unwrap();
+    linker.define(&store, "env", "memory", memory).unwrap();
+    linker
+        .define(&store, "env", "memory_bwd", memory_bwd)
+        .unwrap();
+    linker.define(&store, "env", "offset", offset).unwrap();
+    linker
+        .define(&store, "env", "offset_bwd", offset_bwd)
+        .unwrap();
+    linker.define(&store, "env", "scale", scale).unwrap();
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+    let function = instance
+        .get_typed_func::<f64, f64>(&mut store, "affine")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, f64>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(function.call(&mut store, 3.).unwrap(), 7.5);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), 2.);
+    assert_eq!(offset_bwd.get(&mut store).unwrap_f64(), 1.);
+}
+
+#[test]
+fn test_import_batch() {
+    let wat = include_str!("../wat/import_func.wat");
+    let (mut store, function, backprop) =
+        compile_with_imports::<f64, f64, f64, f64>(wat, "sigmoid", |linker, ad| {
+            linker
+                .func_wrap("f64", "exp", |mut caller: Caller<'_, Data>, x: f64| {
+                    let y = x.exp();
+                    caller.data_mut().tape.push(y);
+                    y
+                })
+                .unwrap();
+            linker
+                .func_wrap("f64", "exp_bwd", |mut caller: Caller<'_, Data>, dy: f64| {
+                    let y = caller.data_mut().tape.pop().unwrap();
+                    dy * y
+                })
+                .unwrap();
+            ad.import_batch([
+                (("f64", "exp"), ("f64", "exp_bwd")),
+                (("f64", "log"), ("f64", "log_bwd")),
+            ]);
+        });
+    {
+        let output = function.call(&mut store, 0.).unwrap();
+        assert_eq!(output, 0.5);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 0.25);
+    }
+}
+
+#[test]
+#[should_panic(expected = "mapping already exists for import")]
+fn test_import_batch_duplicate() {
+    let mut ad = Autodiff::new();
+    ad.import_batch([
+        (("f64", "exp"), ("f64", "exp_bwd")),
+        (("f64", "exp"), ("f64", "exp_bwd")),
+    ]);
+}
+
+#[test]
+fn test_reexport_func() {
+    let wat = include_str!("../wat/reexport_func.wat");
+    let (mut store, function, backprop) =
+        compile_with_imports::<f64, f64, f64, f64>(wat, "id", |linker, ad| {
+            linker.func_wrap("f64", "id", |x: f64| x).unwrap();
+            ad.import(("f64", "id"), ("f64", "id"));
+        });
+    {
+        let output = function.call(&mut store, 2.).unwrap();
+        assert_eq!(output, 2.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 1.);
+    }
+}
+
+#[test]
+fn test_if() {
+    let wat = include_str!("../wat/if.wat");
+    let (mut store, function, backprop) =
+        compile::<(i32, f64, f64), f64, (f64, f64), f64>(wat, "select");
+    {
+        let output = function.call(&mut store, (1, 2., 3.)).unwrap();
+        assert_eq!(output, 2.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (1., 0.));
+    }
+    {
+        let output = function.call(&mut store, (0, 2., 3.)).unwrap();
+        assert_eq!(output, 3.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (0., 1.));
+    }
+}
+
+#[test]
+fn test_else() {
+    let wat = include_str!("../wat/else.wat");
+    let (mut store, function, backprop) =
+        compile::<(i32, f64, f64), f64, (f64, f64), f64>(wat, "select");
+    {
+        let output = function.call(&mut store, (1, 2., 3.)).unwrap();
+        assert_eq!(output, 2.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (1., 0.));
+    }
+    {
+        let output = function.call(&mut store, (0, 2., 3.)).unwrap();
+        assert_eq!(output, 3.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (0., 1.));
+    }
+}
+
+#[test]
+fn test_br() {
+    let wat = include_str!("../wat/br.wat");
+    let (mut store, function, backprop) =
+        compile::<(i32, f64, f64), f64, (f64, f64), f64>(wat, "select");
+    {
+        let output = function.call(&mut store, (1, 2., 3.)).unwrap();
+        assert_eq!(output, 2.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (1., 0.));
+    }
+    {
+        let output = function.call(&mut store, (0, 2., 3.)).unwrap();
+        assert_eq!(output, 3.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (0., 1.));
+    }
+}
+
+#[test]
+fn test_return() {
+    let wat = include_str!("../wat/return_f64.wat");
+    let (mut store, function, backprop) = compile::<(i32, f64), f64, f64, f64>(wat, "maybe_square");
+    {
+        let output = function.call(&mut store, (1, 3.)).unwrap();
+        assert_eq!(output, 3.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 1.);
+    }
+    {
+        let output = function.call(&mut store, (0, 3.)).unwrap();
+        assert_eq!(output, 9.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 6.);
+    }
+}
+
+#[test]
+fn test_call_indirect() {
+    let wat = include_str!("../wat/call_indirect.wat");
+    let (mut store, function, backprop) = compile::<(i32, f64), f64, f64, f64>(wat, "apply");
+    {
+        let output = function.call(&mut store, (0, 3.)).unwrap();
+        assert_eq!(output, 9.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 6.);
+    }
+    {
+        let output = function.call(&mut store, (1, 3.)).unwrap();
+        assert_eq!(output, 27.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 27.);
+    }
+}
+
+#[test]
+fn test_call_indirect_elem() {
+    let input = wat::parse_str(include_str!("../wat/call_indirect_elem.wat")).unwrap();
+    // Validation would reject the reference types in the element segments.
+    let mut ad = Autodiff::no_validate();
+    ad.export("apply", "backprop");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let mut linker = Linker::new(&engine);
+    let immutable = GlobalType::new(ValType::I32, Mutability::Const);
+    let offset = Global::new(&mut store, immutable, Val::I32(1)).unwrap();
+    linker.define(&store, "env", "offset", offset).unwrap();
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+    let function = instance
+        .get_typed_func::<(i32, f64), f64>(&mut store, "apply")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, f64>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(function.call(&mut store, (1, 3.)).unwrap(), 9.);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), 6.);
+    assert_eq!(function.call(&mut store, (2, 3.)).unwrap(), 27.);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), 27.);
+    assert!(function.call(&mut store, (0, 3.)).is_err());
+}
+
+#[test]
+fn test_call_indirect_import_table() {
+    let input = wat::parse_str(include_str!("../wat/call_indirect_import.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.import(("env", "table"), ("env", "table_bwd"));
+    ad.export("apply", "backprop");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let mut linker = Linker::new(&engine);
+    let table_type = TableType::new(RefType::FUNCREF, 2, None);
+    let table = Table::new(&mut store, table_type.clone(), Ref::Func(None)).unwrap();
+    let table_bwd = Table::new(&mut store, table_type, Ref::Func(None)).unwrap();
+    linker.define(&store, "env", "table", table).unwrap();
+    linker
+        .define(&store, "env", "table_bwd", table_bwd)
+        .unwrap();
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+    let function = instance
+        .get_typed_func::<(i32, f64), f64>(&mut store, "apply")
+        .unwrap();
+    let backprop = instance
+        .get_typed_func::<f64, f64>(&mut store, "backprop")
+        .unwrap();
+    assert_eq!(function.call(&mut store, (1, 3.)).unwrap(), 27.);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), 27.);
+}
+
+#[test]
+fn test_import_table_missing() {
+    let input = wat::parse_str(include_str!("../wat/call_indirect_import.wat")).unwrap();
+    let err = Autodiff::new().reverse(&input).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Import);
+}
+
+#[rstest]
+#[case(0, 3., 1.)]
+#[case(1, 27., 27.)]
+#[case(2, 9., 6.)]
+#[case(3, 9., 6.)]
+fn test_br_table(#[case] index: i32, #[case] output: f64, #[case] gradient: f64) {
+    let wat = include_str!("../wat/br_table.wat");
+    let (mut store, function, backprop) = compile::<(i32, f64), f64, f64, f64>(wat, "switch");
+    assert_eq!(function.call(&mut store, (index, 3.)).unwrap(), output);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), gradient);
+}
+
+#[rstest]
+#[case(0, 9., 6.)]
+#[case(1, 3., 1.)]
+fn test_br_table_value(#[case] index: i32, #[case] output: f64, #[case] gradient: f64) {
+    let wat = include_str!("../wat/br_table.wat");
+    let (mut store, function, backprop) = compile::<(i32, f64), f64, f64, f64>(wat, "switch_value");
+    assert_eq!(function.call(&mut store, (index, 3.)).unwrap(), output);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), gradient);
+}
+
+#[test]
+fn test_unreachable() {
+    Backprop {
+        wat: include_str!("../wat/unreachable.wat"),
+        name: "square",
+        input: 3.,
+        output: 9.,
+        cotangent: 1.,
+        gradient: 6.,
+    }
+    .test_numerically()
+}
+
+#[test]
+fn test_unreachable_trap() {
+    let (mut store, function, _) =
+        compile::<f64, f64, f64, f64>(include_str!("../wat/unreachable.wat"), "square");
+    assert!(function.call(&mut store, -1.).is_err());
+    let (mut store, function, _) =
+        compile::<f64, f64, f64, f64>(include_str!("../wat/unreachable.wat"), "trap");
+    assert!(function.call(&mut store, 1.).is_err());
+}
+
+#[test]
+fn test_br_if_return() {
+    let wat = include_str!("../wat/br_if_return.wat");
+    let (mut store, function, backprop) =
+        compile::<(i32, f64, f64), f64, (f64, f64), f64>(wat, "select");
+    {
+        let output = function.call(&mut store, (1, 2., 3.)).unwrap();
+        assert_eq!(output, 2.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (1., 0.));
+    }
+    {
+        let output = function.call(&mut store, (0, 2., 3.)).unwrap();
+        assert_eq!(output, 3.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (0., 1.));
+    }
 }
 
 #[test]
@@ -255,109 +1431,437 @@ fn test_drop_i64() {
         cotangent: (),
         gradient: (),
     }
-    .test()
+    .test()
+}
+
+#[test]
+fn test_drop_f32() {
+    Backprop {
+        wat: include_str!("../wat/drop_f32.wat"),
+        name: "drop",
+        input: 42f32,
+        output: (),
+        cotangent: (),
+        gradient: 0f32,
+    }
+    .test()
+}
+
+#[test]
+fn test_drop_f64() {
+    Backprop {
+        wat: include_str!("../wat/drop_f64.wat"),
+        name: "drop",
+        input: 42.,
+        output: (),
+        cotangent: (),
+        gradient: 0.,
+    }
+    .test()
+}
+
+#[test]
+fn test_select_i32() {
+    Backprop {
+        wat: include_str!("../wat/select_i32.wat"),
+        name: "select",
+        input: (2, 3, 0),
+        output: 3,
+        cotangent: (),
+        gradient: (),
+    }
+    .test()
+}
+
+#[test]
+fn test_select_f64() {
+    let wat = include_str!("../wat/select_f64.wat");
+    let (mut store, function, backprop) =
+        compile::<(f64, f64, i32), f64, (f64, f64), f64>(wat, "select");
+    {
+        let output = function.call(&mut store, (2., 3., 1)).unwrap();
+        assert_eq!(output, 2.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (1., 0.));
+    }
+    {
+        let output = function.call(&mut store, (2., 3., 0)).unwrap();
+        assert_eq!(output, 3.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (0., 1.));
+    }
+}
+
+#[test]
+fn test_select_f32() {
+    let wat = include_str!("../wat/select_f32.wat");
+    let (mut store, function, backprop) =
+        compile::<(f32, f32, i32), f32, (f32, f32), f32>(wat, "select");
+    {
+        let output = function.call(&mut store, (2., 3., 1)).unwrap();
+        assert_eq!(output, 2.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (1., 0.));
+    }
+    {
+        let output = function.call(&mut store, (2., 3., 0)).unwrap();
+        assert_eq!(output, 3.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, (0., 1.));
+    }
+}
+
+#[test]
+fn test_select_mixed() {
+    let wat = include_str!("../wat/select_mixed.wat");
+    let (mut store, function, backprop) = compile::<(f64, i32, i32), f64, f64, f64>(wat, "select");
+    {
+        let output = function.call(&mut store, (3., 5, 1)).unwrap();
+        assert_eq!(output, 15.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 5.);
+    }
+    {
+        let output = function.call(&mut store, (3., 5, 0)).unwrap();
+        assert_eq!(output, 6.);
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 2.);
+    }
+}
+
+#[test]
+fn test_local_set_f32() {
+    Backprop {
+        wat: include_str!("../wat/local_set_f32.wat"),
+        name: "clobber",
+        input: (1f32, 2f32),
+        output: (2f32, 2f32),
+        cotangent: (3f32, 4f32),
+        gradient: (0f32, 7f32),
+    }
+    .test()
+}
+
+#[test]
+fn test_local_set_f64() {
+    Backprop {
+        wat: include_str!("../wat/local_set_f64.wat"),
+        name: "clobber",
+        input: (1., 2.),
+        output: (2., 2.),
+        cotangent: (3., 4.),
+        gradient: (0., 7.),
+    }
+    .test()
+}
+
+#[test]
+fn test_tuple() {
+    Backprop {
+        wat: include_str!("../wat/tuple.wat"),
+        name: "tuple",
+        input: (1i32, 2f64, 3i64, 4f32),
+        output: (4f32, 1i32, 2f64, 3i64),
+        cotangent: (5f32, 6f64),
+        gradient: (6f64, 5f32),
+    }
+    .test()
+}
+
+#[test]
+fn test_loop() {
+    Backprop {
+        wat: include_str!("../wat/loop.wat"),
+        name: "loop",
+        input: 1.1,
+        output: -0.99,
+        cotangent: 1.,
+        gradient: 0.20000000000000018,
+    }
+    .test_numerically()
+}
+
+#[test]
+fn test_f32_store_load() {
+    Backprop {
+        wat: include_str!("../wat/f32_store_load.wat"),
+        name: "roundtrip",
+        input: 42f32,
+        output: 42f32,
+        cotangent: 1f32,
+        gradient: 1f32,
+    }
+    .test()
+}
+
+#[test]
+fn test_f64_store_load() {
+    Backprop {
+        wat: include_str!("../wat/f64_store_load.wat"),
+        name: "roundtrip",
+        input: 42.,
+        output: 42.,
+        cotangent: 1.,
+        gradient: 1.,
+    }
+    .test_numerically()
+}
+
+#[test]
+fn test_i32_store_load() {
+    Backprop {
+        wat: include_str!("../wat/i32_store_load.wat"),
+        name: "roundtrip",
+        input: -2,
+        output: (-2, 254, -2),
+        cotangent: (),
+        gradient: (),
+    }
+    .test()
+}
+
+#[test]
+fn test_i64_store_load() {
+    Backprop {
+        wat: include_str!("../wat/i64_store_load.wat"),
+        name: "roundtrip",
+        input: -2i64,
+        output: (-2i64, 65534i64, -2i64),
+        cotangent: (),
+        gradient: (),
+    }
+    .test()
+}
+
+#[test]
+fn test_memory_grow() {
+    Backprop {
+        wat: include_str!("../wat/memory_grow.wat"),
+        name: "grow",
+        input: 3.,
+        output: (1, 2, 9.),
+        cotangent: 1.,
+        gradient: 6.,
+    }
+    .test()
+}
+
+#[test]
+fn test_memory_copy_up() {
+    Backprop {
+        wat: include_str!("../wat/memory_copy.wat"),
+        name: "copy_up",
+        input: (3., 5.),
+        output: 15.,
+        cotangent: 1.,
+        gradient: (5., 3.),
+    }
+    .test()
+}
+
+#[test]
+fn test_memory_copy_down() {
+    Backprop {
+        wat: include_str!("../wat/memory_copy.wat"),
+        name: "copy_down",
+        input: (3., 2.),
+        output: 1.5,
+        cotangent: 1.,
+        gradient: (0.5, -0.75),
+    }
+    .test()
+}
+
+#[test]
+fn test_memory_copy_f64() {
+    Backprop {
+        wat: include_str!("../wat/memory_copy.wat"),
+        name: "copy_f64",
+        input: 3.,
+        output: 9.,
+        cotangent: 1.,
+        gradient: 6.,
+    }
+    .test()
+}
+
+#[test]
+fn test_memory_copy_f32() {
+    Backprop {
+        wat: include_str!("../wat/memory_copy.wat"),
+        name: "copy_f32",
+        input: 3f32,
+        output: 9f32,
+        cotangent: 1f32,
+        gradient: 6f32,
+    }
+    .test()
+}
+
+#[test]
+fn test_memory_fill() {
+    Backprop {
+        wat: include_str!("../wat/memory_fill.wat"),
+        name: "fill",
+        input: 3.,
+        output: 3.,
+        cotangent: 1.,
+        gradient: 1.,
+    }
+    .test_numerically()
+}
+
+#[test]
+fn test_data() {
+    Backprop {
+        wat: include_str!("../wat/data.wat"),
+        name: "scale",
+        input: 2.,
+        output: 30.,
+        cotangent: 1.,
+        gradient: 15.,
+    }
+    .test_numerically()
+}
+
+#[test]
+fn test_memory_atomic_wait32() {
+    let wat = include_str!("../wat/memory_atomic_wait32.wat");
+    let (mut store, function, backprop) = compile::<(i32, f64), (i32, f64), f64, f64>(wat, "wait");
+    {
+        let output = function.call(&mut store, (1, 2.)).unwrap();
+        assert_eq!(output, (1, 2.)); // Not equal.
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 1.);
+    }
+    {
+        let output = function.call(&mut store, (0, 3.)).unwrap();
+        assert_eq!(output, (2, 3.)); // Timed out.
+        let gradient = backprop.call(&mut store, 1.).unwrap();
+        assert_eq!(gradient, 1.);
+    }
 }
 
 #[test]
-fn test_drop_f32() {
+fn test_memory_atomic_notify() {
     Backprop {
-        wat: include_str!("../wat/drop_f32.wat"),
-        name: "drop",
-        input: 42f32,
-        output: (),
-        cotangent: (),
-        gradient: 0f32,
+        wat: include_str!("../wat/memory_atomic_notify.wat"),
+        name: "notify",
+        input: 42.,
+        output: (0, 42.),
+        cotangent: 1.,
+        gradient: 1.,
     }
     .test()
 }
 
 #[test]
-fn test_drop_f64() {
+fn test_atomic_fence() {
     Backprop {
-        wat: include_str!("../wat/drop_f64.wat"),
-        name: "drop",
-        input: 42.,
-        output: (),
-        cotangent: (),
-        gradient: 0.,
+        wat: include_str!("../wat/atomic_fence.wat"),
+        name: "square",
+        input: 3.,
+        output: 9.,
+        cotangent: 1.,
+        gradient: 6.,
     }
-    .test()
+    .test_numerically()
 }
 
 #[test]
-fn test_local_set_f32() {
+fn test_global_get_i32() {
     Backprop {
-        wat: include_str!("../wat/local_set_f32.wat"),
-        name: "clobber",
-        input: (1f32, 2f32),
-        output: (2f32, 2f32),
-        cotangent: (3f32, 4f32),
-        gradient: (0f32, 7f32),
+        wat: include_str!("../wat/global_get_i32.wat"),
+        name: "scale",
+        input: 2f64,
+        output: 6f64,
+        cotangent: 1f64,
+        gradient: 3f64,
     }
-    .test()
+    .test_numerically()
 }
 
 #[test]
-fn test_local_set_f64() {
+fn test_global_get_f64() {
     Backprop {
-        wat: include_str!("../wat/local_set_f64.wat"),
-        name: "clobber",
-        input: (1., 2.),
-        output: (2., 2.),
-        cotangent: (3., 4.),
-        gradient: (0., 7.),
+        wat: include_str!("../wat/global_get_f64.wat"),
+        name: "scale",
+        input: 2f64,
+        output: 5f64,
+        cotangent: 1f64,
+        gradient: 2.5f64,
     }
-    .test()
+    .test_numerically()
 }
 
 #[test]
-fn test_tuple() {
+fn test_global_mut_f64() {
     Backprop {
-        wat: include_str!("../wat/tuple.wat"),
-        name: "tuple",
-        input: (1i32, 2f64, 3i64, 4f32),
-        output: (4f32, 1i32, 2f64, 3i64),
-        cotangent: (5f32, 6f64),
-        gradient: (6f64, 5f32),
+        wat: include_str!("../wat/global_mut_f64.wat"),
+        name: "cube",
+        input: 2f64,
+        output: 8f64,
+        cotangent: 1f64,
+        gradient: 12f64,
     }
-    .test()
+    .test_numerically()
 }
 
 #[test]
-fn test_loop() {
-    Backprop {
-        wat: include_str!("../wat/loop.wat"),
-        name: "loop",
-        input: 1.1,
-        output: -0.99,
-        cotangent: 1.,
-        gradient: 0.20000000000000018,
-    }
-    .test()
+fn test_global_set() {
+    let input = wat::parse_str(include_str!("../wat/global_set.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("set", "set_bwd");
+    ad.export("get_x", "get_x_bwd");
+    ad.export("x", "x_bwd");
+    let output = ad.reverse(&input).unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &output).unwrap();
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let set = instance
+        .get_typed_func::<(i32, f64), ()>(&mut store, "set")
+        .unwrap();
+    let set_bwd = instance
+        .get_typed_func::<(), f64>(&mut store, "set_bwd")
+        .unwrap();
+    let get_x = instance
+        .get_typed_func::<(), f64>(&mut store, "get_x")
+        .unwrap();
+    let get_x_bwd = instance
+        .get_typed_func::<f64, ()>(&mut store, "get_x_bwd")
+        .unwrap();
+    let x_bwd = instance.get_global(&mut store, "x_bwd").unwrap();
+    set.call(&mut store, (1, 2.)).unwrap();
+    assert_eq!(get_x.call(&mut store, ()).unwrap(), 2.);
+    get_x_bwd.call(&mut store, 3.).unwrap();
+    assert_eq!(x_bwd.get(&mut store).unwrap_f64(), 3.);
+    assert_eq!(set_bwd.call(&mut store, ()).unwrap(), 3.);
+    assert_eq!(x_bwd.get(&mut store).unwrap_f64(), 0.);
 }
 
 #[test]
-fn test_f32_store_load() {
+fn test_f64_promote_f32() {
     Backprop {
-        wat: include_str!("../wat/f32_store_load.wat"),
-        name: "roundtrip",
-        input: 42f32,
-        output: 42f32,
-        cotangent: 1f32,
-        gradient: 1f32,
+        wat: include_str!("../wat/f64_promote_f32.wat"),
+        name: "promote",
+        input: 1.5f32,
+        output: 1.5f64,
+        cotangent: 0.25f64,
+        gradient: 0.25f32,
     }
     .test()
 }
 
 #[test]
-fn test_f64_store_load() {
+fn test_f32_demote_f64() {
     Backprop {
-        wat: include_str!("../wat/f64_store_load.wat"),
-        name: "roundtrip",
-        input: 42.,
-        output: 42.,
-        cotangent: 1.,
-        gradient: 1.,
+        wat: include_str!("../wat/f32_demote_f64.wat"),
+        name: "demote",
+        input: 1.5f64,
+        output: 1.5f32,
+        cotangent: 0.25f32,
+        gradient: 0.25f64,
     }
     .test()
 }
@@ -1004,6 +2508,34 @@ fn test_i64_rotr() {
     .test()
 }
 
+#[rstest]
+#[case(3., 3., 1.)]
+#[case(-3., 3., -1.)]
+fn test_f32_abs(#[case] x: f32, #[case] out: f32, #[case] grad: f32) {
+    Backprop {
+        wat: include_str!("../wat/f32_abs.wat"),
+        name: "abs",
+        input: x,
+        output: out,
+        cotangent: 2f32,
+        gradient: 2. * grad,
+    }
+    .test()
+}
+
+#[test]
+fn test_f32_abs_mul() {
+    Backprop {
+        wat: include_str!("../wat/f32_abs_mul.wat"),
+        name: "abs_mul",
+        input: (-3f32, 2f32),
+        output: 6f32,
+        cotangent: 1f32,
+        gradient: (-2f32, 3f32),
+    }
+    .test()
+}
+
 #[test]
 fn test_f32_neg() {
     Backprop {
@@ -1030,6 +2562,71 @@ fn test_f32_sqrt() {
     .test()
 }
 
+#[test]
+fn test_f32_ceil() {
+    Backprop {
+        wat: include_str!("../wat/f32_ceil.wat"),
+        name: "ceil",
+        input: 2.5f32,
+        output: 3f32,
+        cotangent: 1f32,
+        gradient: 0f32,
+    }
+    .test()
+}
+
+#[test]
+fn test_f32_floor() {
+    Backprop {
+        wat: include_str!("../wat/f32_floor.wat"),
+        name: "floor",
+        input: 2.5f32,
+        output: 2f32,
+        cotangent: 1f32,
+        gradient: 0f32,
+    }
+    .test()
+}
+
+#[test]
+fn test_f32_trunc() {
+    Backprop {
+        wat: include_str!("../wat/f32_trunc.wat"),
+        name: "trunc",
+        input: -2.5f32,
+        output: -2f32,
+        cotangent: 1f32,
+        gradient: 0f32,
+    }
+    .test()
+}
+
+#[test]
+fn test_f32_nearest() {
+    Backprop {
+        wat: include_str!("../wat/f32_nearest.wat"),
+        name: "nearest",
+        input: 2.5f32,
+        output: 2f32,
+        cotangent: 1f32,
+        gradient: 0f32,
+    }
+    .test()
+}
+
+#[test]
+fn test_f32_reinterpret() {
+    Backprop {
+        wat: include_str!("../wat/f32_reinterpret.wat"),
+        name: "bits",
+        input: 2.5f32,
+        output: 2.5f32,
+        cotangent: 1f32,
+        gradient: 0f32,
+    }
+    .test()
+}
+
 #[test]
 fn test_f32_add() {
     Backprop {
@@ -1125,6 +2722,34 @@ fn test_f32_copysign(#[case] to: f32, #[case] from: f32, #[case] out: f32, #[cas
     .test()
 }
 
+#[rstest]
+#[case(3., 3., 1.)]
+#[case(-3., 3., -1.)]
+fn test_f64_abs(#[case] x: f64, #[case] out: f64, #[case] grad: f64) {
+    Backprop {
+        wat: include_str!("../wat/f64_abs.wat"),
+        name: "abs",
+        input: x,
+        output: out,
+        cotangent: 2f64,
+        gradient: 2. * grad,
+    }
+    .test()
+}
+
+#[test]
+fn test_f64_abs_mul() {
+    Backprop {
+        wat: include_str!("../wat/f64_abs_mul.wat"),
+        name: "abs_mul",
+        input: (-3f64, 2f64),
+        output: 6f64,
+        cotangent: 1f64,
+        gradient: (-2f64, 3f64),
+    }
+    .test()
+}
+
 #[test]
 fn test_f64_neg() {
     Backprop {
@@ -1135,7 +2760,7 @@ fn test_f64_neg() {
         cotangent: 1.,
         gradient: -1.,
     }
-    .test()
+    .test_numerically()
 }
 
 #[test]
@@ -1148,6 +2773,71 @@ fn test_f64_sqrt() {
         cotangent: 1.,
         gradient: 0.125,
     }
+    .test_numerically()
+}
+
+#[test]
+fn test_f64_ceil() {
+    Backprop {
+        wat: include_str!("../wat/f64_ceil.wat"),
+        name: "ceil",
+        input: 2.5,
+        output: 3.,
+        cotangent: 1.,
+        gradient: 0.,
+    }
+    .test_numerically()
+}
+
+#[test]
+fn test_f64_floor() {
+    Backprop {
+        wat: include_str!("../wat/f64_floor.wat"),
+        name: "floor",
+        input: 2.5,
+        output: 2.,
+        cotangent: 1.,
+        gradient: 0.,
+    }
+    .test_numerically()
+}
+
+#[test]
+fn test_f64_trunc() {
+    Backprop {
+        wat: include_str!("../wat/f64_trunc.wat"),
+        name: "trunc",
+        input: -2.5,
+        output: -2.,
+        cotangent: 1.,
+        gradient: 0.,
+    }
+    .test_numerically()
+}
+
+#[test]
+fn test_f64_nearest() {
+    Backprop {
+        wat: include_str!("../wat/f64_nearest.wat"),
+        name: "nearest",
+        input: 2.5,
+        output: 2.,
+        cotangent: 1.,
+        gradient: 0.,
+    }
+    .test()
+}
+
+#[test]
+fn test_f64_reinterpret() {
+    Backprop {
+        wat: include_str!("../wat/f64_reinterpret.wat"),
+        name: "bits",
+        input: 2.5,
+        output: 2.5,
+        cotangent: 1.,
+        gradient: 0.,
+    }
     .test()
 }
 
@@ -1349,3 +3039,130 @@ fn test_f64_convert_i64_u() {
     }
     .test()
 }
+
+#[rstest]
+#[case("i32.trunc_sat_f32_s")]
+#[case("i32.trunc_sat_f32_u")]
+#[case("i32.trunc_f32_s")]
+#[case("i32.trunc_f32_u")]
+fn i32_trunc_f32(#[case] name: &str) {
+    let (mut store, function, backprop) = compile::<f32, i32, f32, ()>(
+        &format!(
+            "
+(module
+  (func (export {name:?}) (param f32) (result i32)
+    ({name}
+      (local.get 0))))
+"
+        ),
+        name,
+    );
+    assert_eq!(function.call(&mut store, 3.7).unwrap(), 3);
+    assert_eq!(backprop.call(&mut store, ()).unwrap(), 0.);
+}
+
+#[rstest]
+#[case("i32.trunc_sat_f64_s")]
+#[case("i32.trunc_sat_f64_u")]
+#[case("i32.trunc_f64_s")]
+#[case("i32.trunc_f64_u")]
+fn i32_trunc_f64(#[case] name: &str) {
+    let (mut store, function, backprop) = compile::<f64, i32, f64, ()>(
+        &format!(
+            "
+(module
+  (func (export {name:?}) (param f64) (result i32)
+    ({name}
+      (local.get 0))))
+"
+        ),
+        name,
+    );
+    assert_eq!(function.call(&mut store, 3.7).unwrap(), 3);
+    assert_eq!(backprop.call(&mut store, ()).unwrap(), 0.);
+}
+
+#[rstest]
+#[case("i64.trunc_sat_f32_s")]
+#[case("i64.trunc_sat_f32_u")]
+#[case("i64.trunc_f32_s")]
+#[case("i64.trunc_f32_u")]
+fn i64_trunc_f32(#[case] name: &str) {
+    let (mut store, function, backprop) = compile::<f32, i64, f32, ()>(
+        &format!(
+            "
+(module
+  (func (export {name:?}) (param f32) (result i64)
+    ({name}
+      (local.get 0))))
+"
+        ),
+        name,
+    );
+    assert_eq!(function.call(&mut store, 3.7).unwrap(), 3);
+    assert_eq!(backprop.call(&mut store, ()).unwrap(), 0.);
+}
+
+#[rstest]
+#[case("i64.trunc_sat_f64_s")]
+#[case("i64.trunc_sat_f64_u")]
+#[case("i64.trunc_f64_s")]
+#[case("i64.trunc_f64_u")]
+fn i64_trunc_f64(#[case] name: &str) {
+    let (mut store, function, backprop) = compile::<f64, i64, f64, ()>(
+        &format!(
+            "
+(module
+  (func (export {name:?}) (param f64) (result i64)
+    ({name}
+      (local.get 0))))
+"
+        ),
+        name,
+    );
+    assert_eq!(function.call(&mut store, 3.7).unwrap(), 3);
+    assert_eq!(backprop.call(&mut store, ()).unwrap(), 0.);
+}
+
+#[test]
+#[cfg(feature = "wasmtime")]
+fn test_reverse_typed() {
+    let input = wat::parse_str(include_str!("../wat/square.wat")).unwrap();
+    let typed = Autodiff::new()
+        .reverse_typed::<f64, f64, f64, f64>(&input, "square", "backprop")
+        .unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let (square, backprop) = typed
+        .instantiate(&mut store, &Linker::new(&engine))
+        .unwrap();
+    assert_eq!(square.call(&mut store, 3.).unwrap(), 9.);
+    assert_eq!(backprop.call(&mut store, 1.).unwrap(), 6.);
+}
+
+#[test]
+#[cfg(feature = "wasmtime")]
+fn test_reverse_typed_duplicate_export() {
+    let input = wat::parse_str(include_str!("../wat/square.wat")).unwrap();
+    let mut ad = Autodiff::new();
+    ad.export("square", "backprop");
+    let err = ad
+        .reverse_typed::<f64, f64, f64, f64>(&input, "square", "backprop")
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::Export);
+}
+
+#[test]
+#[cfg(feature = "wasmtime")]
+fn test_reverse_typed_mismatch() {
+    let input = wat::parse_str(include_str!("../wat/square.wat")).unwrap();
+    let typed = Autodiff::new()
+        .reverse_typed::<f64, f64, (f64, f64), f64>(&input, "square", "backprop")
+        .unwrap();
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    assert!(typed
+        .instantiate(&mut store, &Linker::new(&engine))
+        .is_err());
+}
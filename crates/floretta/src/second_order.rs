@@ -0,0 +1,37 @@
+//! Second derivatives, by composing the forward-mode and reverse-mode transformations.
+
+use crate::{forward, Autodiff, NoValidate, Transform};
+
+/// Apply reverse mode to the result of forward mode, additionally exporting the backward pass of
+/// the `name` export as `name_rvf`.
+pub fn reverse_of_forward(
+    config: &Autodiff,
+    wasm_module: &[u8],
+    name: String,
+    name_rvf: String,
+) -> crate::Result<Vec<u8>> {
+    let mut config = config.clone();
+    config.try_export(name, name_rvf)?;
+    reverse_over_forward(&config, wasm_module)
+}
+
+/// Apply reverse mode to the result of forward mode.
+///
+/// Every function in the forward-mode module takes and returns a tangent alongside each float, so
+/// its backward pass takes and returns a cotangent for each of those tangents too.
+pub fn reverse_over_forward(config: &Autodiff, wasm_module: &[u8]) -> crate::Result<Vec<u8>> {
+    let jvp = config.transform.forward(config, wasm_module, 1)?;
+    // We just produced the forward-mode module ourselves, so there's no need to validate it.
+    NoValidate.reverse(config, &jvp)
+}
+
+/// Apply forward mode to the result of reverse mode.
+///
+/// The forward pass of every function in the reverse-mode module writes primals to the tape, and
+/// its backward pass reads them back. In forward mode, the tape memory gets a tangent memory next
+/// to it like any other memory, so each value on the tape carries its tangent along with it.
+pub fn forward_over_reverse(config: &Autodiff, wasm_module: &[u8]) -> crate::Result<Vec<u8>> {
+    let vjp = config.transform.reverse(config, wasm_module)?;
+    // We just produced the reverse-mode module ourselves, so there's no need to validate it.
+    forward::transform((), config, &vjp, 1)
+}
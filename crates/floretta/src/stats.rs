@@ -0,0 +1,143 @@
+use wasmparser::{Operator, Parser, Payload, TypeRef};
+
+use crate::{
+    helper::{FuncOffsets, OFFSET_FUNCTIONS},
+    util::{u32_to_usize, NumImports},
+    Autodiff, Stats,
+};
+
+/// Transform a Wasm module in reverse mode, then gather [`Stats`] about the output.
+pub fn stats(config: &Autodiff, wasm_module: &[u8]) -> crate::Result<Stats> {
+    let mut num_imports = NumImports::default();
+    let mut num_functions = 0;
+    for payload in Parser::new(0).parse_all(wasm_module) {
+        match payload? {
+            Payload::ImportSection(section) => {
+                for import in section {
+                    if let TypeRef::Func(_) = import?.ty {
+                        num_imports.func += 1;
+                    }
+                }
+            }
+            Payload::FunctionSection(section) => num_functions += section.count(),
+            _ => {}
+        }
+    }
+    let output = config.transform.reverse(config, wasm_module)?;
+    let mut helpers = vec![FuncOffsets::new(num_imports)];
+    if config.tape_per_function {
+        helpers.extend(
+            (0..num_functions)
+                .map(|index| FuncOffsets::partition(num_imports, num_functions, index)),
+        );
+    }
+    // Forward and backward passes alternate, right after the shared helpers.
+    let first = 2 * num_imports.func + OFFSET_FUNCTIONS;
+    let passes = first..first + 2 * num_functions;
+    let mut helper_bytes = 0;
+    let mut bodies = Vec::new();
+    let mut funcidx = 2 * num_imports.func;
+    for payload in Parser::new(0).parse_all(&output) {
+        if let Payload::CodeSectionEntry(body) = payload? {
+            if passes.contains(&funcidx) {
+                bodies.push(body);
+            } else {
+                // Everything that isn't a forward or backward pass is some kind of helper.
+                helper_bytes += body.range().len();
+            }
+            funcidx += 1;
+        }
+    }
+    let mut direct = Vec::new();
+    for body in bodies.iter().step_by(2) {
+        let mut bytes: Option<u32> = Some(0);
+        let mut callees = Vec::new();
+        for op in body.get_operators_reader()? {
+            match op? {
+                Operator::Loop { .. }
+                | Operator::CallIndirect { .. }
+                | Operator::ReturnCallIndirect { .. } => bytes = None,
+                Operator::Call { function_index } | Operator::ReturnCall { function_index } => {
+                    if let Some(helper) = helpers.iter().find(|h| h.contains(function_index)) {
+                        bytes =
+                            bytes.and_then(|n| n.checked_add(helper.tape_bytes(function_index)));
+                    } else if passes.contains(&function_index) {
+                        callees.push((function_index - first) / 2);
+                    }
+                }
+                _ => {}
+            }
+        }
+        direct.push((bytes, callees));
+    }
+    let mut memo = vec![None; direct.len()];
+    let mut visiting = vec![false; direct.len()];
+    let tape_bytes = (0..num_functions)
+        .map(|index| bound(&direct, &mut memo, &mut visiting, index))
+        .collect();
+    Ok(Stats {
+        imported_functions: num_imports.func,
+        functions: num_functions,
+        helper_bytes,
+        tape_bytes,
+    })
+}
+
+/// Add up the bytes pushed by the function at `index` and everything it calls, or give `None` if
+/// that's unbounded.
+fn bound(
+    direct: &[(Option<u32>, Vec<u32>)],
+    memo: &mut [Option<Option<u32>>],
+    visiting: &mut [bool],
+    index: u32,
+) -> Option<u32> {
+    let i = u32_to_usize(index);
+    if let Some(bytes) = memo[i] {
+        return bytes;
+    }
+    if visiting[i] {
+        // Reaching a function again while still adding up its callees means recursion.
+        return None;
+    }
+    visiting[i] = true;
+    let (bytes, callees) = &direct[i];
+    let mut total = *bytes;
+    for &callee in callees {
+        let n = bound(direct, memo, visiting, callee);
+        total = total.zip(n).and_then(|(a, b)| a.checked_add(b));
+    }
+    visiting[i] = false;
+    memo[i] = Some(total);
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Autodiff;
+
+    #[test]
+    fn test_stats() {
+        let input = wat::parse_str(include_str!("wat/call.wat")).unwrap();
+        let stats = Autodiff::new().stats(&input).unwrap();
+        assert_eq!(stats.functions, 2);
+        assert_eq!(stats.tape_bytes, [Some(4), Some(8)]);
+        let mut ad = Autodiff::new();
+        ad.tape_per_function();
+        assert!(ad.stats(&input).unwrap().helper_bytes > stats.helper_bytes);
+    }
+
+    #[test]
+    fn test_stats_square() {
+        let input = wat::parse_str(include_str!("wat/square.wat")).unwrap();
+        let stats = Autodiff::new().stats(&input).unwrap();
+        // One `f64.mul` saves both operands, and the function saves the basic block it ended in.
+        assert_eq!(stats.tape_bytes, [Some(16 + 4)]);
+    }
+
+    #[test]
+    fn test_stats_loop() {
+        let input = wat::parse_str(include_str!("wat/loop.wat")).unwrap();
+        let stats = Autodiff::new().stats(&input).unwrap();
+        assert_eq!(stats.tape_bytes, [None]);
+    }
+}
@@ -0,0 +1,72 @@
+use std::marker::PhantomData;
+
+use wasmtime::{AsContextMut, Linker, Module, TypedFunc, WasmParams, WasmResults};
+
+/// A Wasm module transformed in reverse mode, along with the expected types of one exported
+/// function and its backward pass.
+///
+/// Construct this via [`Autodiff::reverse_typed`](crate::Autodiff::reverse_typed).
+pub struct ReverseTyped<P, R, DP, DR> {
+    wasm: Vec<u8>,
+    name: String,
+    name_bwd: String,
+    tape_reset: Option<String>,
+    fwd: PhantomData<fn(P) -> R>,
+    bwd: PhantomData<fn(DR) -> DP>,
+}
+
+impl<P, R, DP, DR> ReverseTyped<P, R, DP, DR> {
+    pub(crate) fn new(
+        wasm: Vec<u8>,
+        name: String,
+        name_bwd: String,
+        tape_reset: Option<String>,
+    ) -> Self {
+        Self {
+            wasm,
+            name,
+            name_bwd,
+            tape_reset,
+            fwd: PhantomData,
+            bwd: PhantomData,
+        }
+    }
+
+    /// The transformed Wasm module.
+    pub fn wasm(&self) -> &[u8] {
+        &self.wasm
+    }
+
+    /// The name of the exported function.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of the exported backward pass.
+    pub fn name_bwd(&self) -> &str {
+        &self.name_bwd
+    }
+
+    /// The name of the exported function that empties the tape, if one was configured via
+    /// [`Autodiff::export_tape_reset`](crate::Autodiff::export_tape_reset).
+    pub fn tape_reset(&self) -> Option<&str> {
+        self.tape_reset.as_deref()
+    }
+}
+
+impl<P: WasmParams, R: WasmResults, DP: WasmResults, DR: WasmParams> ReverseTyped<P, R, DP, DR> {
+    /// Instantiate the transformed module, returning the forward and backward passes.
+    ///
+    /// Returns an error if either function doesn't have the expected type.
+    pub fn instantiate<T>(
+        &self,
+        mut store: impl AsContextMut<Data = T>,
+        linker: &Linker<T>,
+    ) -> wasmtime::Result<(TypedFunc<P, R>, TypedFunc<DR, DP>)> {
+        let module = Module::new(linker.engine(), &self.wasm)?;
+        let instance = linker.instantiate(&mut store, &module)?;
+        let fwd = instance.get_typed_func::<P, R>(&mut store, &self.name)?;
+        let bwd = instance.get_typed_func::<DR, DP>(&mut store, &self.name_bwd)?;
+        Ok((fwd, bwd))
+    }
+}
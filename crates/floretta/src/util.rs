@@ -1,4 +1,5 @@
 use hashbrown::Equivalent;
+use wasmparser::Operator;
 
 use crate::ErrorImpl;
 
@@ -7,6 +8,56 @@ pub fn u32_to_usize(n: u32) -> usize {
         .expect("pointer size is assumed to be at least 32 bits")
 }
 
+macro_rules! define_operator_helpers {
+    (@sig unary $ty:ident) => {
+        define_operator_helpers!(@ty $ty).map(|ty| (ty.singleton(), ty.singleton()))
+    };
+    (@sig binary $ty:ident) => {
+        define_operator_helpers!(@ty $ty).map(|ty| (ty.pair(), ty.singleton()))
+    };
+    (@sig test $ty:ident) => {
+        define_operator_helpers!(@ty $ty).map(|ty| (ty.singleton(), ValType::I32.singleton()))
+    };
+    (@sig cmp $ty:ident) => {
+        define_operator_helpers!(@ty $ty).map(|ty| (ty.pair(), ValType::I32.singleton()))
+    };
+    (@sig conversion $result:ident $operand:ident) => {
+        define_operator_helpers!(@ty $operand)
+            .zip(define_operator_helpers!(@ty $result))
+            .map(|(operand, result)| (operand.singleton(), result.singleton()))
+    };
+    (@sig $($ann:tt)*) => { None };
+    (@ty i32) => { Some(ValType::I32) };
+    (@ty i64) => { Some(ValType::I64) };
+    (@ty f32) => { Some(ValType::F32) };
+    (@ty f64) => { Some(ValType::F64) };
+    (@ty $other:ident) => { None::<ValType> };
+    ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident ($($ann:tt)*))*) => {
+        /// The name of the [`Operator`] variant for an instruction, without its immediates.
+        pub fn operator_name(op: &Operator) -> &'static str {
+            match op {
+                $(Operator::$op { .. } => stringify!($op),)*
+                _ => "Unknown",
+            }
+        }
+
+        /// The names of all the [`Operator`] variants.
+        #[cfg(test)]
+        pub const OPERATOR_NAMES: &[&str] = &[$(stringify!($op)),*];
+
+        /// The operand and result types of a numeric instruction that has no immediates, if it
+        /// only involves the value types that [`ValType`] covers.
+        pub fn numeric_signature(op: &Operator) -> Option<(&'static [ValType], &'static [ValType])> {
+            match op {
+                $(Operator::$op { .. } => define_operator_helpers!(@sig $($ann)*),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+wasmparser::for_each_operator!(define_operator_helpers);
+
 #[derive(Hash)]
 pub struct TwoStrs<'a>(pub &'a str, pub &'a str);
 
@@ -16,7 +67,8 @@ impl Equivalent<(String, String)> for TwoStrs<'_> {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// Ordered the same way as the fields of [`TypeMap`]: `i32`, `i64`, `f32`, `f64`.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub(crate) enum ValType {
     I32,
     I64,
@@ -37,6 +89,15 @@ impl ValType {
             ValType::F64 => &[ValType::F64],
         }
     }
+
+    pub fn pair(self) -> &'static [Self] {
+        match self {
+            ValType::I32 => &[ValType::I32, ValType::I32],
+            ValType::I64 => &[ValType::I64, ValType::I64],
+            ValType::F32 => &[ValType::F32, ValType::F32],
+            ValType::F64 => &[ValType::F64, ValType::F64],
+        }
+    }
 }
 
 impl TryFrom<wasmparser::ValType> for ValType {
@@ -144,12 +205,24 @@ impl FuncTypes {
             None => &self.val_types[i..],
         }
     }
+
+    /// Iterate over all function types as `(typeidx, params, results)` triples.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (u32, &[ValType], &[ValType])> + '_ {
+        // Same reasoning as in `push` for why the number of types fits in a `u32`.
+        let num_types = u32::try_from(self.offsets.len()).unwrap();
+        (0..num_types).map(|typeidx| (typeidx, self.params(typeidx), self.results(typeidx)))
+    }
 }
 
 /// Number of imports in a Wasm module.
 #[derive(Clone, Copy, Default)]
 pub struct NumImports {
     pub func: u32,
+    pub memory: u32,
+    pub global: u32,
+
+    /// In reverse mode, the number of imported globals that also get an imported adjoint global.
+    pub global_adjoint: u32,
 }
 
 /// A map whose keys are Wasm types.
@@ -258,6 +331,22 @@ impl LocalMap {
             })
     }
 
+    /// Return an iterator over the individual source locals, with each entry expanded into `count`
+    /// pairs of a local index and its type.
+    // When the `names` feature is disabled, this gets marked as dead code.
+    #[allow(dead_code)]
+    pub fn keys_flat(&self) -> impl Iterator<Item = (u32, ValType)> + '_ {
+        let mut start = 0;
+        self.ends
+            .iter()
+            .zip(self.types.iter())
+            .flat_map(move |(&(end, _), &ty)| {
+                let indices = start..end;
+                start = end;
+                indices.map(move |index| (index, ty))
+            })
+    }
+
     /// Return an iterator over the transformed entries of the local map.
     pub fn vals(&self) -> impl ExactSizeIterator<Item = (u32, ValType)> + '_ {
         let mut start = 0;
@@ -275,7 +364,7 @@ impl LocalMap {
 #[cfg(test)]
 mod tests {
 
-    use crate::util::{LocalMap, TypeMap, ValType};
+    use crate::util::{FuncTypes, LocalMap, TypeMap, ValType};
 
     fn ones() -> TypeMap<u32> {
         TypeMap {
@@ -322,4 +411,48 @@ mod tests {
         assert_eq!(locals.get(6), (ValType::I32, Some(9)));
         assert_eq!(locals.get(7), (ValType::I32, Some(11)));
     }
+
+    #[test]
+    fn test_func_types_iter() {
+        use wasmparser::ValType::{F64, I32};
+        let mut types = FuncTypes::new();
+        types
+            .push(wasmparser::FuncType::new([I32, F64], [F64]))
+            .unwrap();
+        types.push(wasmparser::FuncType::new([], [])).unwrap();
+        types
+            .push(wasmparser::FuncType::new([F64], [I32, F64]))
+            .unwrap();
+        let all: Vec<_> = types.iter().collect();
+        assert_eq!(
+            all,
+            [
+                (0, &[ValType::I32, ValType::F64][..], &[ValType::F64][..]),
+                (1, &[][..], &[][..]),
+                (2, &[ValType::F64][..], &[ValType::I32, ValType::F64][..]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_val_type_ord() {
+        let mut types = vec![ValType::F64, ValType::I32, ValType::F32, ValType::I64];
+        types.sort();
+        assert_eq!(
+            types,
+            [ValType::I32, ValType::I64, ValType::F32, ValType::F64],
+        );
+    }
+
+    #[test]
+    fn test_locals_keys_flat() {
+        let mut locals = LocalMap::new(ones());
+        locals.push(2, ValType::I32);
+        locals.push(0, ValType::F32);
+        locals.push(1, ValType::F64);
+        assert_eq!(
+            locals.keys_flat().collect::<Vec<_>>(),
+            [(0, ValType::I32), (1, ValType::I32), (2, ValType::F64)],
+        );
+    }
 }
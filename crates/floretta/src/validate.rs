@@ -1,7 +1,8 @@
 use wasmparser::{
-    ExportSectionReader, FuncValidator, FuncValidatorAllocations, FunctionBody,
-    FunctionSectionReader, GlobalSectionReader, ImportSectionReader, MemorySectionReader, Operator,
-    Payload, TypeSectionReader, Validator, ValidatorResources, WasmModuleResources,
+    DataSectionReader, ElementSectionReader, ExportSectionReader, FuncValidator,
+    FuncValidatorAllocations, FunctionBody, FunctionSectionReader, GlobalSectionReader,
+    ImportSectionReader, MemorySectionReader, Operator, Payload, TableSectionReader,
+    TypeSectionReader, Validator, ValidatorResources, WasmModuleResources,
 };
 
 /// Trait counterpart to [`wasmparser::Validator`].
@@ -16,12 +17,30 @@ pub trait ModuleValidator {
 
     fn function_section(&mut self, section: &FunctionSectionReader) -> wasmparser::Result<()>;
 
+    fn table_section(&mut self, section: &TableSectionReader) -> wasmparser::Result<()>;
+
     fn memory_section(&mut self, section: &MemorySectionReader) -> wasmparser::Result<()>;
 
     fn global_section(&mut self, section: &GlobalSectionReader) -> wasmparser::Result<()>;
 
     fn export_section(&mut self, section: &ExportSectionReader) -> wasmparser::Result<()>;
 
+    fn start_section(
+        &mut self,
+        func: u32,
+        range: &std::ops::Range<usize>,
+    ) -> wasmparser::Result<()>;
+
+    fn element_section(&mut self, section: &ElementSectionReader) -> wasmparser::Result<()>;
+
+    fn data_count_section(
+        &mut self,
+        count: u32,
+        range: &std::ops::Range<usize>,
+    ) -> wasmparser::Result<()>;
+
+    fn data_section(&mut self, section: &DataSectionReader) -> wasmparser::Result<()>;
+
     fn code_section_entry(&mut self, body: &FunctionBody) -> wasmparser::Result<Self::Func>;
 }
 
@@ -64,6 +83,10 @@ impl ModuleValidator for () {
         Ok(())
     }
 
+    fn table_section(&mut self, _: &TableSectionReader) -> wasmparser::Result<()> {
+        Ok(())
+    }
+
     fn memory_section(&mut self, _: &MemorySectionReader) -> wasmparser::Result<()> {
         Ok(())
     }
@@ -76,6 +99,22 @@ impl ModuleValidator for () {
         Ok(())
     }
 
+    fn start_section(&mut self, _: u32, _: &std::ops::Range<usize>) -> wasmparser::Result<()> {
+        Ok(())
+    }
+
+    fn element_section(&mut self, _: &ElementSectionReader) -> wasmparser::Result<()> {
+        Ok(())
+    }
+
+    fn data_count_section(&mut self, _: u32, _: &std::ops::Range<usize>) -> wasmparser::Result<()> {
+        Ok(())
+    }
+
+    fn data_section(&mut self, _: &DataSectionReader) -> wasmparser::Result<()> {
+        Ok(())
+    }
+
     fn code_section_entry(&mut self, _: &FunctionBody) -> wasmparser::Result<Self::Func> {
         Ok(())
     }
@@ -124,6 +163,10 @@ impl ModuleValidator for Validator {
         self.function_section(section)
     }
 
+    fn table_section(&mut self, section: &TableSectionReader) -> wasmparser::Result<()> {
+        self.table_section(section)
+    }
+
     fn memory_section(&mut self, section: &MemorySectionReader) -> wasmparser::Result<()> {
         self.memory_section(section)
     }
@@ -136,6 +179,30 @@ impl ModuleValidator for Validator {
         self.export_section(section)
     }
 
+    fn start_section(
+        &mut self,
+        func: u32,
+        range: &std::ops::Range<usize>,
+    ) -> wasmparser::Result<()> {
+        self.start_section(func, range)
+    }
+
+    fn element_section(&mut self, section: &ElementSectionReader) -> wasmparser::Result<()> {
+        self.element_section(section)
+    }
+
+    fn data_count_section(
+        &mut self,
+        count: u32,
+        range: &std::ops::Range<usize>,
+    ) -> wasmparser::Result<()> {
+        self.data_count_section(count, range)
+    }
+
+    fn data_section(&mut self, section: &DataSectionReader) -> wasmparser::Result<()> {
+        self.data_section(section)
+    }
+
     fn code_section_entry(&mut self, body: &FunctionBody) -> wasmparser::Result<Self::Func> {
         let func = self.code_section_entry(body)?;
         Ok(func.into_validator(FuncValidatorAllocations::default()))
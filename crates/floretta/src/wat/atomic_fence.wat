@@ -0,0 +1,6 @@
+(module
+  (func (export "square") (param f64) (result f64)
+    (atomic.fence)
+    (f64.mul
+      (local.get 0)
+      (local.get 0))))
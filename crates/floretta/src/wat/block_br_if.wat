@@ -0,0 +1,8 @@
+(module
+  (func (export "maybe_mul") (param i32 f64 f64) (result f64)
+    (block (result f64)
+      (br_if 0
+        (local.get 1)
+        (local.get 0))
+      (local.get 2)
+      (f64.mul))))
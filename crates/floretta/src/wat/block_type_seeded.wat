@@ -0,0 +1,7 @@
+(module
+  (type $t (func (param f64) (result f64)))
+  (func (export "square") (type $t)
+    local.get 0
+    (block (type $t) (param f64) (result f64)
+      local.get 0
+      f64.mul)))
@@ -0,0 +1,15 @@
+(module
+  (func (export "abs") (param f64) (result f64)
+    (block (result f64)
+      local.get 0
+      (f64.ge
+        (local.get 0)
+        (f64.const 0))
+      br_if 0
+      f64.neg
+      return
+      f64.add
+      drop
+      local.get 0
+      br 0
+      f64.sqrt)))
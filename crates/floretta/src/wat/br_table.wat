@@ -0,0 +1,26 @@
+(module
+  (func (export "switch") (param i32 f64) (result f64)
+    (block $square
+      (block $cube
+        (block $id
+          (br_table $id $cube $square
+            (local.get 0)))
+        (return
+          (local.get 1)))
+      (return
+        (f64.mul
+          (f64.mul
+            (local.get 1)
+            (local.get 1))
+          (local.get 1))))
+    (f64.mul
+      (local.get 1)
+      (local.get 1)))
+  (func (export "switch_value") (param i32 f64) (result f64)
+    (block $outer (result f64)
+      (f64.mul
+        (block $inner (result f64)
+          (br_table $inner $outer
+            (local.get 1)
+            (local.get 0)))
+        (local.get 1)))))
@@ -0,0 +1,9 @@
+(module
+  (func $square (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (local.get 0)))
+  (func (export "fourth") (param f64) (result f64)
+    (call $square
+      (call $square
+        (local.get 0)))))
@@ -0,0 +1,18 @@
+(module
+  (type $unary (func (param f64) (result f64)))
+  (table 2 funcref)
+  (elem (i32.const 0) $square $cube)
+  (func $square (type $unary)
+    (f64.mul
+      (local.get 0)
+      (local.get 0)))
+  (func $cube (type $unary)
+    (f64.mul
+      (f64.mul
+        (local.get 0)
+        (local.get 0))
+      (local.get 0)))
+  (func (export "apply") (param i32 f64) (result f64)
+    (call_indirect (type $unary)
+      (local.get 1)
+      (local.get 0))))
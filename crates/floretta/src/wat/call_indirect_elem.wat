@@ -0,0 +1,21 @@
+(module
+  (import "env" "offset" (global $offset i32))
+  (type $unary (func (param f64) (result f64)))
+  (table 3 funcref)
+  (elem (global.get $offset) funcref (ref.func $square) (ref.func $cube))
+  (elem declare func $square)
+  (elem funcref (ref.null func))
+  (func $square (type $unary)
+    (f64.mul
+      (local.get 0)
+      (local.get 0)))
+  (func $cube (type $unary)
+    (f64.mul
+      (f64.mul
+        (local.get 0)
+        (local.get 0))
+      (local.get 0)))
+  (func (export "apply") (param i32 f64) (result f64)
+    (call_indirect (type $unary)
+      (local.get 1)
+      (local.get 0))))
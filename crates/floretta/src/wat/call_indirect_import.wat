@@ -0,0 +1,18 @@
+(module
+  (import "env" "table" (table $table 2 funcref))
+  (type $unary (func (param f64) (result f64)))
+  (elem (table $table) (i32.const 0) func $square $cube)
+  (func $square (type $unary)
+    (f64.mul
+      (local.get 0)
+      (local.get 0)))
+  (func $cube (type $unary)
+    (f64.mul
+      (f64.mul
+        (local.get 0)
+        (local.get 0))
+      (local.get 0)))
+  (func (export "apply") (param i32 f64) (result f64)
+    (call_indirect $table (type $unary)
+      (local.get 1)
+      (local.get 0))))
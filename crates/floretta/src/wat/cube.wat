@@ -0,0 +1,7 @@
+(module
+  (func (export "cube") (param f64) (result f64)
+    (f64.mul
+      (f64.mul
+        (local.get 0)
+        (local.get 0))
+      (local.get 0))))
@@ -0,0 +1,6 @@
+(module
+  (@custom "my_section" "hello")
+  (func $square (export "square") (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (local.get 0))))
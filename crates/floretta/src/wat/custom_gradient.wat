@@ -0,0 +1,23 @@
+(module
+  (global $x (mut f64) (f64.const 0))
+  (func $cube (export "cube") (param f64) (result f64)
+    (global.set $x
+      (local.get 0))
+    (f64.mul
+      (f64.mul
+        (local.get 0)
+        (local.get 0))
+      (local.get 0)))
+  (func (export "cube_grad") (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (f64.mul
+        (f64.const 3)
+        (f64.mul
+          (global.get $x)
+          (global.get $x)))))
+  (func (export "double_cube") (param f64) (result f64)
+    (f64.mul
+      (f64.const 2)
+      (call $cube
+        (local.get 0)))))
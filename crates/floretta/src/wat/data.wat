@@ -0,0 +1,12 @@
+(module
+  (memory 1)
+  (data (i32.const 0) "\00\00\00\00\00\00\08\40")
+  (data (i32.const 8) "\00\00\00\00\00\00\14\40")
+  (func (export "scale") (param f64) (result f64)
+    (f64.mul
+      (f64.mul
+        (f64.load
+          (i32.const 0))
+        (f64.load
+          (i32.const 8)))
+      (local.get 0))))
@@ -0,0 +1,12 @@
+(module
+  (import "env" "scale" (global $scale f64))
+  (import "env" "base" (global $base i32))
+  (memory 1)
+  (data (global.get $base) "\00\00\00\00\00\00\08\40")
+  (func (export "load") (param f64) (result f64)
+    (f64.mul
+      (f64.mul
+        (f64.load
+          (global.get $base))
+        (global.get $scale))
+      (local.get 0))))
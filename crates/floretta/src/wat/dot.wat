@@ -0,0 +1,9 @@
+(module
+  (func (export "dot") (param f64 f64 f64 f64) (result f64)
+    (f64.add
+      (f64.mul
+        (local.get 0)
+        (local.get 2))
+      (f64.mul
+        (local.get 1)
+        (local.get 3)))))
@@ -0,0 +1,4 @@
+(module
+  (func (export "abs") (param f32) (result f32)
+    (f32.abs
+      (local.get 0))))
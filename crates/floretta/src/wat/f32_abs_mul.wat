@@ -0,0 +1,6 @@
+(module
+  (func (export "abs_mul") (param f32 f32) (result f32)
+    (f32.mul
+      (f32.abs
+        (local.get 0))
+      (local.get 1))))
@@ -0,0 +1,4 @@
+(module
+  (func (export "ceil") (param f32) (result f32)
+    (f32.ceil
+      (local.get 0))))
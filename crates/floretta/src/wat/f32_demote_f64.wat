@@ -0,0 +1,4 @@
+(module
+  (func (export "demote") (param f64) (result f32)
+    (f32.demote_f64
+      (local.get 0))))
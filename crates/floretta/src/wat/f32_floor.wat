@@ -0,0 +1,4 @@
+(module
+  (func (export "floor") (param f32) (result f32)
+    (f32.floor
+      (local.get 0))))
@@ -0,0 +1,4 @@
+(module
+  (func (export "nearest") (param f32) (result f32)
+    (f32.nearest
+      (local.get 0))))
@@ -0,0 +1,5 @@
+(module
+  (func (export "bits") (param f32) (result f32)
+    (f32.reinterpret_i32
+      (i32.reinterpret_f32
+        (local.get 0)))))
@@ -0,0 +1,4 @@
+(module
+  (func (export "trunc") (param f32) (result f32)
+    (f32.trunc
+      (local.get 0))))
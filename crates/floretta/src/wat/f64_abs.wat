@@ -0,0 +1,4 @@
+(module
+  (func (export "abs") (param f64) (result f64)
+    (f64.abs
+      (local.get 0))))
@@ -0,0 +1,6 @@
+(module
+  (func (export "abs_mul") (param f64 f64) (result f64)
+    (f64.mul
+      (f64.abs
+        (local.get 0))
+      (local.get 1))))
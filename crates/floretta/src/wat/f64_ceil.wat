@@ -0,0 +1,4 @@
+(module
+  (func (export "ceil") (param f64) (result f64)
+    (f64.ceil
+      (local.get 0))))
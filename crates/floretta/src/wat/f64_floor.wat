@@ -0,0 +1,4 @@
+(module
+  (func (export "floor") (param f64) (result f64)
+    (f64.floor
+      (local.get 0))))
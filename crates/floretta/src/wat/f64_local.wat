@@ -0,0 +1,10 @@
+(module
+  (func (export "cube") (param f64) (result f64)
+    (local i32 f64)
+    (local.set 2
+      (f64.mul
+        (local.get 0)
+        (local.get 0)))
+    (f64.mul
+      (local.get 2)
+      (local.get 0))))
@@ -0,0 +1,4 @@
+(module
+  (func (export "nearest") (param f64) (result f64)
+    (f64.nearest
+      (local.get 0))))
@@ -0,0 +1,4 @@
+(module
+  (func (export "promote") (param f32) (result f64)
+    (f64.promote_f32
+      (local.get 0))))
@@ -0,0 +1,5 @@
+(module
+  (func (export "bits") (param f64) (result f64)
+    (f64.reinterpret_i64
+      (i64.reinterpret_f64
+        (local.get 0)))))
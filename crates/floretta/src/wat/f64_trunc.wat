@@ -0,0 +1,4 @@
+(module
+  (func (export "trunc") (param f64) (result f64)
+    (f64.trunc
+      (local.get 0))))
@@ -0,0 +1,7 @@
+(module
+  (global $c f64
+    (f64.const 2.5))
+  (func (export "scale") (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (global.get $c))))
@@ -0,0 +1,8 @@
+(module
+  (global $n i32
+    (i32.const 3))
+  (func (export "scale") (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (f64.convert_i32_s
+        (global.get $n)))))
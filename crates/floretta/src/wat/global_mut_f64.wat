@@ -0,0 +1,11 @@
+(module
+  (global $square (mut f64)
+    (f64.const 0))
+  (func (export "cube") (param f64) (result f64)
+    (global.set $square
+      (f64.mul
+        (local.get 0)
+        (local.get 0)))
+    (f64.mul
+      (global.get $square)
+      (local.get 0))))
@@ -0,0 +1,14 @@
+(module
+  (global $n (mut i32)
+    (i32.const 0))
+  (global $x (export "x") (mut f64)
+    (f64.const 0))
+  (func (export "set") (param i32 f64)
+    (global.set $n
+      (local.get 0))
+    (global.set $x
+      (local.get 1)))
+  (func (export "get_n") (result i32)
+    (global.get $n))
+  (func (export "get_x") (result f64)
+    (global.get $x)))
@@ -0,0 +1,15 @@
+(module
+  (memory 1)
+  (func (export "roundtrip") (param i32) (result i32 i32 i32)
+    (i32.store
+      (i32.const 0)
+      (local.get 0))
+    (i32.store8
+      (i32.const 4)
+      (local.get 0))
+    (i32.load
+      (i32.const 0))
+    (i32.load8_u
+      (i32.const 4))
+    (i32.load8_s
+      (i32.const 4))))
@@ -0,0 +1,4 @@
+(module
+  (func (export "wrap") (param i64) (result i32)
+    (i32.wrap_i64
+      (local.get 0))))
@@ -0,0 +1,15 @@
+(module
+  (memory 1)
+  (func (export "roundtrip") (param i64) (result i64 i64 i64)
+    (i64.store
+      (i32.const 0)
+      (local.get 0))
+    (i64.store16
+      (i32.const 8)
+      (local.get 0))
+    (i64.load
+      (i32.const 0))
+    (i64.load16_u
+      (i32.const 8))
+    (i64.load16_s
+      (i32.const 8))))
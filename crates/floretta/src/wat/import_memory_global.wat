@@ -0,0 +1,13 @@
+(module
+  (import "env" "square" (func $square (param f64) (result f64)))
+  (import "env" "memory" (memory 1))
+  (import "env" "scale" (global $scale f64))
+  (func (export "scaled_square") (param f64) (result f64)
+    (f64.store
+      (i32.const 0)
+      (call $square
+        (local.get 0)))
+    (f64.mul
+      (f64.load
+        (i32.const 0))
+      (global.get $scale))))
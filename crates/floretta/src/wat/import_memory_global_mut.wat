@@ -0,0 +1,17 @@
+(module
+  (import "env" "memory" (memory 1))
+  (import "env" "offset" (global $offset (mut f64)))
+  (import "env" "scale" (global $scale f64))
+  (global $bias f64 (f64.const 0.5))
+  (func (export "affine") (param f64) (result f64)
+    (f64.store
+      (i32.const 0)
+      (local.get 0))
+    (f64.add
+      (f64.add
+        (f64.mul
+          (f64.load
+            (i32.const 0))
+          (global.get $scale))
+        (global.get $offset))
+      (global.get $bias))))
@@ -0,0 +1,10 @@
+(module
+  (func (export "double_square") (param f64 f64) (result f64)
+    (local.set 1
+      (f64.mul
+        (local.get 0)
+        (local.get 0)))
+    (f64.add
+      (local.tee 0
+        (local.get 1))
+      (local.get 0))))
@@ -0,0 +1,13 @@
+(module
+  (func (export "square_twice") (param f64 i32 i32) (result f64)
+    (loop
+      (local.set 0
+        (f64.mul
+          (local.get 0)
+          (local.get 0)))
+      ;; Branch on the old value of local 1 after overwriting it with local 2.
+      (local.get 1)
+      (local.set 1
+        (local.get 2))
+      (br_if 0))
+    (local.get 0)))
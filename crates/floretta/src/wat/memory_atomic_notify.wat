@@ -0,0 +1,7 @@
+(module
+  (memory 1 1 shared)
+  (func (export "notify") (param f64) (result i32 f64)
+    (memory.atomic.notify
+      (i32.const 0)
+      (i32.const 1))
+    (local.get 0)))
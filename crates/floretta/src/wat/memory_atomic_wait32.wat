@@ -0,0 +1,8 @@
+(module
+  (memory 1 1 shared)
+  (func (export "wait") (param i32 f64) (result i32 f64)
+    (memory.atomic.wait32
+      (i32.const 0)
+      (local.get 0)
+      (i64.const 0))
+    (local.get 1)))
@@ -0,0 +1,60 @@
+(module
+  (memory 1)
+  (func (export "copy_up") (param f64 f64) (result f64)
+    (f64.store
+      (i32.const 0)
+      (local.get 0))
+    (f64.store
+      (i32.const 8)
+      (local.get 1))
+    (memory.copy
+      (i32.const 8)
+      (i32.const 0)
+      (i32.const 16))
+    (f64.mul
+      (f64.load
+        (i32.const 8))
+      (f64.load
+        (i32.const 16))))
+  (func (export "copy_down") (param f64 f64) (result f64)
+    (f64.store
+      (i32.const 8)
+      (local.get 0))
+    (f64.store
+      (i32.const 16)
+      (local.get 1))
+    (memory.copy
+      (i32.const 0)
+      (i32.const 8)
+      (i32.const 16))
+    (f64.div
+      (f64.load
+        (i32.const 0))
+      (f64.load
+        (i32.const 8))))
+  (func (export "copy_f64") (param f64) (result f64)
+    (f64.store
+      (i32.const 0)
+      (local.get 0))
+    (memory.copy
+      (i32.const 8)
+      (i32.const 0)
+      (i32.const 8))
+    (f64.mul
+      (f64.load
+        (i32.const 0))
+      (f64.load
+        (i32.const 8))))
+  (func (export "copy_f32") (param f32) (result f32)
+    (f32.store
+      (i32.const 0)
+      (local.get 0))
+    (memory.copy
+      (i32.const 4)
+      (i32.const 0)
+      (i32.const 4))
+    (f32.mul
+      (f32.load
+        (i32.const 0))
+      (f32.load
+        (i32.const 4)))))
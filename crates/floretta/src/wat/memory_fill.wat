@@ -0,0 +1,14 @@
+(module
+  (memory 1)
+  (func (export "fill") (param f64) (result f64)
+    (f64.store
+      (i32.const 0)
+      (local.get 0))
+    (memory.fill
+      (i32.const 0)
+      (i32.const 0)
+      (i32.const 8))
+    (f64.add
+      (f64.load
+        (i32.const 0))
+      (local.get 0))))
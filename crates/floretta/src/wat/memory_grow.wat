@@ -0,0 +1,14 @@
+(module
+  (memory 1)
+  (func (export "grow") (param f64) (result i32 i32 f64)
+    (memory.grow
+      (i32.const 1))
+    (memory.size)
+    (f64.store
+      (i32.const 65536)
+      (local.get 0))
+    (f64.mul
+      (f64.load
+        (i32.const 65536))
+      (f64.load
+        (i32.const 65536)))))
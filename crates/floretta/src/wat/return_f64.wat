@@ -0,0 +1,10 @@
+(module
+  (func (export "maybe_square") (param i32 f64) (result f64)
+    (if
+      (local.get 0)
+      (then
+        (return
+          (local.get 1))))
+    (f64.mul
+      (local.get 1)
+      (local.get 1))))
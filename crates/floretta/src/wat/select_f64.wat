@@ -0,0 +1,6 @@
+(module
+  (func (export "select") (param f64 f64 i32) (result f64)
+    (select
+      (local.get 0)
+      (local.get 1)
+      (local.get 2))))
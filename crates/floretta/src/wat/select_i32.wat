@@ -0,0 +1,6 @@
+(module
+  (func (export "select") (param i32 i32 i32) (result i32)
+    (select
+      (local.get 0)
+      (local.get 1)
+      (local.get 2))))
@@ -0,0 +1,9 @@
+(module
+  (func (export "select") (param f64 i32 i32) (result f64)
+    (f64.mul
+      (local.get 0)
+      (f64.convert_i32_s
+        (select
+          (local.get 1)
+          (i32.const 2)
+          (local.get 2))))))
@@ -0,0 +1,12 @@
+(module
+  (func $scale (export "scale") (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (f64.convert_i32_s
+        (i32.extend8_s
+          (i32.const 3)))))
+  (func (export "mul_scaled") (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (call $scale
+        (local.get 0)))))
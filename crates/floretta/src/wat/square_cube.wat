@@ -0,0 +1,11 @@
+(module
+  (func (export "square") (param f64) (result f64)
+    (f64.mul
+      (local.get 0)
+      (local.get 0)))
+  (func (export "cube") (param f64) (result f64)
+    (f64.mul
+      (f64.mul
+        (local.get 0)
+        (local.get 0))
+      (local.get 0))))
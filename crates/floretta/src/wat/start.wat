@@ -0,0 +1,14 @@
+(module
+  (global $a f64 (f64.const 1.5))
+  (global $b f64 (f64.const 2))
+  (global $k (mut f64) (f64.const 0))
+  (func $init
+    (global.set $k
+      (f64.mul
+        (global.get $a)
+        (global.get $b))))
+  (start $init)
+  (func (export "scale") (param f64) (result f64)
+    (f64.mul
+      (global.get $k)
+      (local.get 0))))
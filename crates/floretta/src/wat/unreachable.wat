@@ -0,0 +1,14 @@
+(module
+  (func (export "square") (param f64) (result f64)
+    (if
+      (f64.lt
+        (local.get 0)
+        (f64.const 0))
+      (then
+        (unreachable)))
+    (nop)
+    (f64.mul
+      (local.get 0)
+      (local.get 0)))
+  (func (export "trap") (param f64) (result f64)
+    (unreachable)))
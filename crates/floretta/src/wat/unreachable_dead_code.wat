@@ -0,0 +1,17 @@
+(module
+  (func (export "square") (param f64) (result f64)
+    (if
+      (f64.lt
+        (local.get 0)
+        (f64.const 0))
+      (then
+        unreachable
+        f64.add
+        (block (param f64) (result f64)
+          f64.neg)
+        i32.const 0
+        select
+        drop))
+    (f64.mul
+      (local.get 0)
+      (local.get 0))))
@@ -0,0 +1,10 @@
+#![no_main]
+
+use floretta::Autodiff;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|wasm: &[u8]| {
+    // Invalid input should produce an error; any panic is a bug.
+    let _ = Autodiff::new().forward(wasm);
+    let _ = Autodiff::new().reverse(wasm);
+});